@@ -0,0 +1,227 @@
+//! Declarative tree definitions
+//!
+//! A tree can be described within a [TOML](https://github.com/toml-lang/toml) file, where every
+//! node references a parser by the name it was registered with in a `ParserRegistry`:
+//!
+//! ```toml
+//! [roots]
+//! start = "first"
+//!
+//! [[parser]]
+//! id = "first"
+//! parser = "Parser1"
+//! children = ["second", "third"]
+//!
+//! [[parser]]
+//! id = "second"
+//! parser = "Parser2"
+//! enabled = false
+//!
+//! [[parser]]
+//! id = "third"
+//! parser = "Parser3"
+//! ```
+//!
+//! The first enabled parser will be the default root of the tree. Children are tried by the
+//! traversal in the order they are listed, whereas disabled parsers are skipped together with all
+//! links and roots pointing to them. Children which are additionally listed in `reenter` will be
+//! linked via `LinkKind::Reenter`, for example to parse the inner packets of a tunnel.
+//!
+//! An existing tree can be converted back into its configuration via `Peel::to_config` or saved
+//! directly via `Peel::to_config_file`. Nodes will then be identified by their graph indices and
+//! reference their parsers by `Parsable::name` together with `Parsable::parameters`. The shared
+//! `data` of the tree is not part of the configuration.
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use toml;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+
+use prelude::*;
+use registry::{ParserRegistry, Parameters};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+/// The description of a complete tree
+pub struct TreeConfig {
+    /// Named roots of the tree, pointing to node identifiers
+    #[serde(default)]
+    pub roots: BTreeMap<String, String>,
+
+    /// All nodes of the tree
+    #[serde(default, rename = "parser")]
+    pub parsers: Vec<NodeConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// The description of a single node within the tree
+pub struct NodeConfig {
+    /// The unique identifier of the node
+    pub id: String,
+
+    /// The name of the parser within the registry
+    pub parser: String,
+
+    /// Disabled nodes will not be part of the tree
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// The identifiers of the child nodes in traversal order
+    #[serde(default)]
+    pub children: Vec<String>,
+
+    /// The identifiers of the children which are re-entered for encapsulated packets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reenter: Vec<String>,
+
+    /// Parameters which will be passed to the parser factory
+    #[serde(default)]
+    pub parameters: Parameters,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl TreeConfig {
+    /// Parse a configuration from a TOML string
+    pub fn from_toml(input: &str) -> Result<Self, PeelError> {
+        Ok(toml::from_str(input)?)
+    }
+
+    /// Read a configuration from a TOML file
+    pub fn from_file<P>(path: P) -> Result<Self, PeelError>
+        where P: AsRef<Path>
+    {
+        let mut input = String::new();
+        File::open(path)?.read_to_string(&mut input)?;
+        Self::from_toml(&input)
+    }
+
+    /// Convert the configuration into a TOML string
+    pub fn to_toml(&self) -> Result<String, PeelError> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+impl<D> Peel<D> {
+    /// Create a new `Peel` instance from a tree configuration
+    ///
+    /// # Errors
+    /// When a node identifier is not unique, a link or root points to an unknown node, a
+    /// re-entered node is no child or a parser could not be created by the registry.
+    pub fn from_config(config: &TreeConfig, registry: &ParserRegistry<D>) -> Result<Self, PeelError> {
+        let mut peel = Peel::new();
+
+        // Create the nodes
+        let mut disabled = vec![];
+        let mut nodes = HashMap::new();
+        for node in &config.parsers {
+            if nodes.contains_key(&node.id) || disabled.contains(&&node.id) {
+                return Err(PeelError::new(ErrorType::InvalidConfig,
+                                          &format!("Duplicate node identifier '{}'", node.id)));
+            }
+            if node.enabled {
+                let parser = registry.create(&node.parser, &node.parameters)?;
+                nodes.insert(&node.id, peel.new_boxed_parser(parser));
+            } else {
+                info!("Skipping disabled node '{}'", node.id);
+                disabled.push(&node.id);
+            }
+        }
+
+        // Lookup a node identifier, where disabled nodes result in `None`
+        let lookup = |id: &String| -> Result<Option<NodeIndex>, PeelError> {
+            match nodes.get(id) {
+                Some(node) => Ok(Some(*node)),
+                None if disabled.contains(&id) => Ok(None),
+                None => {
+                    Err(PeelError::new(ErrorType::InvalidConfig,
+                                       &format!("Unknown node identifier '{}'", id)))
+                }
+            }
+        };
+
+        // Link the nodes, the traversal tries the last linked child first
+        for node in config.parsers.iter().filter(|n| n.enabled) {
+            if let Some(id) = node.reenter.iter().find(|id| !node.children.contains(id)) {
+                return Err(PeelError::new(ErrorType::InvalidConfig,
+                                          &format!("Re-entered node '{}' is no child of '{}'", id, node.id)));
+            }
+            let left = nodes[&node.id];
+            for child in node.children.iter().rev() {
+                if let Some(right) = lookup(child)? {
+                    let kind = if node.reenter.contains(child) {
+                        LinkKind::Reenter
+                    } else {
+                        LinkKind::Child
+                    };
+                    peel.link_with(left, right, kind);
+                }
+            }
+        }
+
+        // Register the roots
+        for (name, id) in &config.roots {
+            if let Some(node) = lookup(id)? {
+                peel.add_root(name, node);
+            }
+        }
+
+        Ok(peel)
+    }
+
+    /// Create a new `Peel` instance from a TOML configuration file
+    pub fn from_config_file<P>(path: P, registry: &ParserRegistry<D>) -> Result<Self, PeelError>
+        where P: AsRef<Path>
+    {
+        Self::from_config(&TreeConfig::from_file(path)?, registry)
+    }
+
+    /// Save the current tree structure into a TOML configuration file
+    pub fn to_config_file<P>(&self, path: P) -> Result<(), PeelError>
+        where P: AsRef<Path>
+    {
+        let mut f = File::create(path)?;
+        f.write_all(self.to_config().to_toml()?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Create a tree configuration from the current tree structure. The default root will be
+    /// the first node of the configuration.
+    pub fn to_config(&self) -> TreeConfig {
+        let mut nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        if let Some(root) = self.root {
+            nodes.retain(|node| *node != root);
+            nodes.insert(0, root);
+        }
+
+        TreeConfig {
+            roots: self.roots
+                .iter()
+                .map(|(name, node)| (name.clone(), node.index().to_string()))
+                .collect(),
+            parsers: nodes.into_iter()
+                .map(|node| {
+                    NodeConfig {
+                        id: node.index().to_string(),
+                        parser: self.graph[node].name(),
+                        enabled: true,
+                        children: self.graph
+                            .edges(node)
+                            .map(|edge| edge.target().index().to_string())
+                            .collect(),
+                        reenter: self.graph
+                            .edges(node)
+                            .filter(|edge| *edge.weight() == LinkKind::Reenter)
+                            .map(|edge| edge.target().index().to_string())
+                            .collect(),
+                        parameters: self.graph[node].parameters(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
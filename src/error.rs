@@ -2,6 +2,7 @@
 use std::error::Error;
 use std::{fmt, io};
 use nom::Needed;
+use toml;
 
 /// Representation for an error of the library
 pub struct PeelError {
@@ -65,6 +66,8 @@ macro_rules! from_error {
 
 from_error! {
     io::Error,
+    toml::de::Error,
+    toml::ser::Error,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -79,6 +82,12 @@ pub enum ErrorType {
     /// A parser got not enough data
     Incomplete(Needed),
 
+    /// No parser was registered with the requested name
+    UnknownParser,
+
+    /// A tree configuration is not valid
+    InvalidConfig,
+
     /// The error originates from another error
     Other,
 }
@@ -5,13 +5,14 @@ mod parser3;
 mod parser4;
 
 use self::prelude::*;
+use registry::{ParserRegistry, ParserInfo};
 
 pub mod prelude {
     //! Sensible defaults for the example parsers
     pub use std::fmt;
     pub use Peel;
     pub use parser::{Parsable, ParserResult, ParserResultVec};
-    pub use super::peel_example;
+    pub use super::{peel_example, example_registry};
     pub use nom::IResult;
 
     pub use example::parser1::*;
@@ -40,3 +41,17 @@ pub fn peel_example() -> Peel<()> {
 
     p
 }
+
+/// Return a `ParserRegistry` containing all example parsers
+pub fn example_registry() -> ParserRegistry<()> {
+    let mut r = ParserRegistry::new();
+    r.register(ParserInfo::new("Parser1", "1.0.0", "Parses the character '1'"),
+               |_| Ok(Box::new(Parser1)));
+    r.register(ParserInfo::new("Parser2", "1.0.0", "Parses the character '2'"),
+               |_| Ok(Box::new(Parser2)));
+    r.register(ParserInfo::new("Parser3", "1.0.0", "Parses the character '3'"),
+               |_| Ok(Box::new(Parser3)));
+    r.register(ParserInfo::new("Parser4", "1.0.0", "Parses the character '4'"),
+               |_| Ok(Box::new(Parser4)));
+    r
+}
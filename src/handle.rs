@@ -0,0 +1,202 @@
+//! Runtime modification of trees shared between threads
+//!
+//! A `TreeHandle` holds the current topology of a tree as `TreeConfig` snapshot together with an
+//! epoch counter. Control threads modify the tree via the handle, which publishes a new snapshot
+//! and increments the epoch. Every worker thread owns a `TreeWorker` with its own `Peel`
+//! instance, which will be rebuilt from the latest snapshot before the next traversal if the epoch
+//! changed. This means that workers never block each other and always traverse a consistent tree.
+//! Modifications are built without holding the snapshot lock, which is only taken to publish.
+//!
+//! A rebuild creates every parser anew by the registry, even the ones of unchanged nodes, so state
+//! kept within the parsers is lost on every modification. State which has to survive belongs into
+//! the shared data of the tree, which is handed over to the rebuilt tree.
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use prelude::*;
+
+/// The latest published tree topology
+struct Snapshot {
+    epoch: usize,
+    config: Arc<TreeConfig>,
+}
+
+/// A thread safe handle to a tree which can be modified during runtime
+pub struct TreeHandle<D> {
+    registry: Arc<ParserRegistry<D>>,
+    snapshot: Arc<RwLock<Snapshot>>,
+    epoch: Arc<AtomicUsize>,
+    modification: Arc<Mutex<()>>,
+}
+
+impl<D> Clone for TreeHandle<D> {
+    fn clone(&self) -> Self {
+        TreeHandle {
+            registry: self.registry.clone(),
+            snapshot: self.snapshot.clone(),
+            epoch: self.epoch.clone(),
+            modification: self.modification.clone(),
+        }
+    }
+}
+
+impl<D> TreeHandle<D> {
+    /// Create a new `TreeHandle` from a tree configuration
+    ///
+    /// # Errors
+    /// When the tree could not be built from the configuration.
+    pub fn new(config: TreeConfig, registry: ParserRegistry<D>) -> Result<Self, PeelError> {
+        Peel::from_config(&config, &registry)?;
+        Ok(TreeHandle {
+            registry: Arc::new(registry),
+            snapshot: Arc::new(RwLock::new(Snapshot {
+                epoch: 0,
+                config: Arc::new(config),
+            })),
+            epoch: Arc::new(AtomicUsize::new(0)),
+            modification: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Create a new `TreeHandle` from the topology of an existing tree
+    pub fn from_peel(peel: &Peel<D>, registry: ParserRegistry<D>) -> Result<Self, PeelError> {
+        Self::new(peel.to_config(), registry)
+    }
+
+    /// The current epoch, which will be incremented on every modification
+    pub fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// The registry used to build the trees
+    pub fn registry(&self) -> &ParserRegistry<D> {
+        &self.registry
+    }
+
+    /// The configuration of the latest published tree
+    pub fn config(&self) -> Result<Arc<TreeConfig>, PeelError> {
+        let snapshot = self.snapshot.read().map_err(|_| poisoned())?;
+        Ok(snapshot.config.clone())
+    }
+
+    /// Replace the whole tree by a new configuration
+    ///
+    /// # Errors
+    /// When the tree could not be built from the configuration. The current tree will not be
+    /// changed in this case.
+    pub fn replace(&self, config: TreeConfig) -> Result<usize, PeelError> {
+        Peel::from_config(&config, &self.registry)?;
+        let _modification = self.modification.lock().map_err(|_| poisoned())?;
+        self.publish(config)
+    }
+
+    /// Modify the tree by the given closure, which gets a freshly built `Peel` instance of the
+    /// latest snapshot. The resulting tree will be published afterwards and the new epoch
+    /// returned. Concurrent modifications are serialized, whereas readers and workers are not
+    /// blocked while the closure runs.
+    ///
+    /// # Errors
+    /// When the tree could not be built or the closure fails. The current tree will not be
+    /// changed in this case.
+    pub fn modify<F>(&self, f: F) -> Result<usize, PeelError>
+        where F: FnOnce(&mut Peel<D>, &ParserRegistry<D>) -> Result<(), PeelError>
+    {
+        let _modification = self.modification.lock().map_err(|_| poisoned())?;
+        let config = self.config()?;
+        let mut peel = Peel::from_config(&config, &self.registry)?;
+        f(&mut peel, &self.registry)?;
+        self.publish(peel.to_config())
+    }
+
+    /// Create a new worker which traverses the latest tree
+    pub fn worker(&self) -> Result<TreeWorker<D>, PeelError> {
+        let (epoch, peel) = self.build()?;
+        Ok(TreeWorker {
+            handle: self.clone(),
+            epoch: epoch,
+            peel: peel,
+        })
+    }
+
+    /// Build a new `Peel` instance from the latest snapshot
+    fn build(&self) -> Result<(usize, Peel<D>), PeelError> {
+        let (epoch, config) = {
+            let snapshot = self.snapshot.read().map_err(|_| poisoned())?;
+            (snapshot.epoch, snapshot.config.clone())
+        };
+        Ok((epoch, Peel::from_config(&config, &self.registry)?))
+    }
+
+    /// Store a new snapshot and increment the epoch
+    fn publish(&self, config: TreeConfig) -> Result<usize, PeelError> {
+        let mut snapshot = self.snapshot.write().map_err(|_| poisoned())?;
+        snapshot.epoch += 1;
+        snapshot.config = Arc::new(config);
+        self.epoch.store(snapshot.epoch, Ordering::Release);
+        info!("Published tree epoch {}", snapshot.epoch);
+        Ok(snapshot.epoch)
+    }
+}
+
+/// A per thread tree instance which follows the modifications of its `TreeHandle`
+pub struct TreeWorker<D> {
+    handle: TreeHandle<D>,
+    epoch: usize,
+    peel: Peel<D>,
+}
+
+impl<D> TreeWorker<D> {
+    /// The epoch of the currently used tree
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Access the currently used tree
+    pub fn peel(&mut self) -> &mut Peel<D> {
+        &mut self.peel
+    }
+
+    /// Rebuild the tree if a newer snapshot was published. All parsers will be created anew, where
+    /// only the shared data of the tree will be kept. Returns true if the tree was rebuilt.
+    ///
+    /// # Errors
+    /// When the tree could not be built, whereas the current tree will be kept.
+    pub fn refresh(&mut self) -> Result<bool, PeelError> {
+        if self.handle.epoch() == self.epoch {
+            return Ok(false);
+        }
+        let (epoch, mut peel) = self.handle.build()?;
+        peel.data = self.peel.data.take();
+        self.peel = peel;
+        self.epoch = epoch;
+        debug!("Worker switched to tree epoch {}", epoch);
+        Ok(true)
+    }
+
+    /// Refresh the tree if needed and traverse it from its root
+    pub fn traverse<'a>(&mut self, input: &'a [u8], result: ParserResultVec) -> PeelResult<'a> {
+        self.refresh_or_keep();
+        self.peel.traverse(input, result)
+    }
+
+    /// Refresh the tree if needed and traverse it from the named root
+    pub fn traverse_root<'a>(&mut self, name: &str, input: &'a [u8], result: ParserResultVec) -> PeelResult<'a> {
+        self.refresh_or_keep();
+        self.peel.traverse_root(name, input, result)
+    }
+
+    /// Continue the last traversal without refreshing the tree
+    pub fn continue_traverse<'a>(&mut self, input: &'a [u8], result: ParserResultVec) -> PeelResult<'a> {
+        self.peel.continue_traverse(input, result)
+    }
+
+    fn refresh_or_keep(&mut self) {
+        if let Err(error) = self.refresh() {
+            warn!("Keeping tree epoch {}: {}", self.epoch, error);
+        }
+    }
+}
+
+fn poisoned() -> PeelError {
+    PeelError::new(ErrorType::Other, "Tree handle lock poisoned")
+}
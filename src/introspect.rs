@@ -0,0 +1,122 @@
+//! Lookup of nodes and introspection of the tree structure
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+
+use prelude::*;
+use parser::Parser;
+
+impl<D> Peel<D> {
+    /// Find all nodes whose parsers are named like the given one
+    pub fn find(&self, name: &str) -> Vec<NodeIndex> {
+        self.find_by(|parser| parser.name() == name)
+    }
+
+    /// Find all nodes whose parsers are of the given type
+    pub fn find_type<T>(&self) -> Vec<NodeIndex>
+        where T: Parsable<D> + 'static,
+              D: 'static
+    {
+        self.find_by(|parser| parser.parser_type() == TypeId::of::<T>())
+    }
+
+    /// Find all nodes whose parsers match the given predicate
+    pub fn find_by<P>(&self, predicate: P) -> Vec<NodeIndex>
+        where P: Fn(&Parser<D>) -> bool
+    {
+        self.graph.node_indices().filter(|node| predicate(&self.graph[*node])).collect()
+    }
+
+    /// Get the children of a node in the order they are tried by the traversal
+    pub fn children(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        self.graph.neighbors_directed(node, Direction::Outgoing).collect()
+    }
+
+    /// Get the parents of a node
+    pub fn parents(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        self.graph.neighbors_directed(node, Direction::Incoming).collect()
+    }
+
+    /// Find the shortest path between two nodes, including both of them
+    pub fn path(&self, from: NodeIndex, to: NodeIndex) -> Option<Vec<NodeIndex>> {
+        if !self.graph.contains_node(from) || !self.graph.contains_node(to) {
+            return None;
+        }
+
+        // Breadth first search remembering the predecessors
+        let mut predecessors = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while current != from {
+                    current = predecessors[&current];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for child in self.graph.neighbors_directed(node, Direction::Outgoing) {
+                if child != from && !predecessors.contains_key(&child) {
+                    predecessors.insert(child, node);
+                    queue.push_back(child);
+                }
+            }
+        }
+        None
+    }
+
+    /// Write a single node and all its not yet visited children as textual tree
+    fn fmt_node(&self,
+                f: &mut fmt::Formatter,
+                node: NodeIndex,
+                prefix: &str,
+                visited: &mut HashSet<NodeIndex>)
+                -> fmt::Result {
+        let children = self.children(node);
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+            if visited.insert(*child) {
+                writeln!(f, "{}{}{} ({})", prefix, branch, self.graph[*child].name(), child.index())?;
+                self.fmt_node(f, *child, &format!("{}{}", prefix, indent), visited)?;
+            } else {
+                writeln!(f, "{}{}{} ({}) …", prefix, branch, self.graph[*child].name(), child.index())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Print the tree starting at the default root, followed by the named roots and all remaining
+/// nodes. Every node is expanded only once, further occurrences are marked by `…`.
+impl<D> fmt::Display for Peel<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut starts: Vec<(Option<&str>, NodeIndex)> = self.root.into_iter().map(|root| (None, root)).collect();
+        let mut roots: Vec<(&String, &NodeIndex)> = self.roots.iter().collect();
+        roots.sort();
+        starts.extend(roots.into_iter().map(|(name, node)| (Some(name.as_str()), *node)));
+        starts.extend(self.graph.node_indices().map(|node| (None, node)));
+
+        let mut visited = HashSet::new();
+        for (name, node) in starts {
+            if !visited.insert(node) {
+                if let Some(name) = name {
+                    writeln!(f, "[{}] {} ({}) …", name, self.graph[node].name(), node.index())?;
+                }
+                continue;
+            }
+            if let Some(name) = name {
+                write!(f, "[{}] ", name)?;
+            }
+            writeln!(f, "{} ({})", self.graph[node].name(), node.index())?;
+            self.fmt_node(f, node, "", &mut visited)?;
+        }
+        Ok(())
+    }
+}
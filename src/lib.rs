@@ -18,33 +18,45 @@ extern crate nom;
 extern crate log;
 extern crate petgraph;
 extern crate mowl;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
 
 #[macro_use]
 pub mod error;
 pub mod parser;
+pub mod registry;
+pub mod config;
+pub mod handle;
+pub mod introspect;
 pub mod example;
+pub mod packet;
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 use log::LogLevel;
 use nom::{IResult, generate_colors, prepare_errors, print_codes, print_offsets};
 
-use petgraph::{Graph, Direction};
-use petgraph::dot::{Dot, Config};
+use petgraph::Direction;
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::StableGraph;
-use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::visit::EdgeRef;
 
 use prelude::*;
 use parser::Parser;
 
 /// Provides sensible imports at all
 pub mod prelude {
-    pub use super::{Peel, PeelResult};
+    pub use super::{Peel, PeelResult, LinkKind};
     pub use error::{PeelError, ErrorType};
     pub use parser::{Parsable, ParserResult, ParserResultVec};
+    pub use registry::{ParserRegistry, ParserInfo, Parameters};
+    pub use config::{TreeConfig, NodeConfig};
+    pub use handle::{TreeHandle, TreeWorker};
 }
 
 #[derive(Debug)]
@@ -58,30 +70,67 @@ pub struct PeelResult<'a> {
 
     /// Possible error which occured during the parsing
     pub error: Option<PeelError>,
+
+    /// The nodes which parsed successfully, in traversal order
+    pub path: Vec<NodeIndex>,
+
+    /// The nodes which were tried but failed or needed more data
+    pub failed: Vec<NodeIndex>,
+
+    /// The first node which needed more data, where the traversal can be resumed
+    pub incomplete: Option<NodeIndex>,
+
+    /// The encapsulation depth of every result, where zero is the outermost layer
+    pub depths: Vec<usize>,
+
+    /// The encapsulation depth of the current traversal position
+    depth: usize,
 }
 
 impl<'a> PeelResult<'a> {
     /// Create a new `ParserResult`
     fn new(result: ParserResultVec, left_input: &'a [u8], error: Option<PeelError>) -> Self {
         PeelResult {
+            depths: vec![0; result.len()],
+            depth: 0,
             result: result,
             left_input: left_input,
             error: error,
+            path: vec![],
+            failed: vec![],
+            incomplete: None,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The kind of a link between two nodes
+pub enum LinkKind {
+    /// The child parses the left input of its parent
+    Child,
+
+    /// The target parses a packet encapsulated by its parent, which re-enters the tree one
+    /// encapsulation level deeper
+    Reenter,
+}
+
 /// The main peeling structure
 pub struct Peel<D> {
     /// The memory arena of the tree
-    pub graph: StableGraph<Parser<D>, ()>,
+    pub graph: StableGraph<Parser<D>, LinkKind>,
 
     /// The first node added will be the root
     pub root: Option<NodeIndex>,
 
+    /// Additional named entry points into the tree
+    pub roots: HashMap<String, NodeIndex>,
+
     /// Additional data for which can be shared accross the parsers
     pub data: Option<D>,
 
+    /// The maximum encapsulation depth, where deeper re-entering links will be skipped
+    pub max_depth: usize,
+
     /// The current parsing position for continue traversal support
     last_position: NodeIndex,
 }
@@ -92,7 +141,9 @@ impl<D> Peel<D> {
         Peel {
             graph: StableGraph::new(),
             root: None,
+            roots: HashMap::new(),
             data: None,
+            max_depth: 8,
             last_position: NodeIndex::new(0),
         }
     }
@@ -111,10 +162,15 @@ impl<D> Peel<D> {
     pub fn new_parser<T>(&mut self, parser: T) -> NodeIndex
         where T: Parsable<D> + 'static
     {
+        self.new_boxed_parser(Box::new(parser))
+    }
+
+    /// Add an already boxed Parser and return a corresponding Node
+    pub fn new_boxed_parser(&mut self, parser: Parser<D>) -> NodeIndex {
         info!("New parser: {:?}", parser);
 
         // Create a new node
-        let new_node = self.graph.add_node(Box::new(parser));
+        let new_node = self.graph.add_node(parser);
 
         // Check if the root node is already set. If not, then this will be the root
         if self.root.is_none() {
@@ -127,16 +183,142 @@ impl<D> Peel<D> {
 
     /// Append the second node to the first one within the current tree structure
     pub fn link(&mut self, left: NodeIndex, right: NodeIndex) {
-        info!("Link: {:?} → {:?}", self.graph[left], self.graph[right]);
-        self.graph.add_edge(left, right, ());
+        self.link_with(left, right, LinkKind::Child);
+    }
+
+    /// Let the traversal re-enter the tree at the second node for packets encapsulated by the
+    /// first one, like the inner frame of a tunnel
+    pub fn link_reenter(&mut self, left: NodeIndex, right: NodeIndex) {
+        self.link_with(left, right, LinkKind::Reenter);
     }
 
-    /// Remove a parser from the graph and return if existing.
+    /// Link two nodes with the given kind of link
+    pub fn link_with(&mut self, left: NodeIndex, right: NodeIndex, kind: LinkKind) {
+        info!("Link ({:?}): {:?} → {:?}", kind, self.graph[left], self.graph[right]);
+        self.graph.add_edge(left, right, kind);
+    }
+
+    /// Remove a parser from the graph and return if existing. Every root pointing to the node
+    /// will be removed as well.
     pub fn remove(&mut self, node: NodeIndex) -> Option<Parser<D>> {
         info!("Removed: {:?}", self.graph[node]);
+        if self.root == Some(node) {
+            self.root = None;
+        }
+        self.roots.retain(|_, root| *root != node);
         self.graph.remove_node(node)
     }
 
+    /// Insert all nodes of another tree and link its root to the given node. The named roots of
+    /// the other tree will not be taken over and its re-entering links are limited by the
+    /// `max_depth` of this tree, whereas the one of the other tree is dropped. Returns the mapping
+    /// from the node indices of the other tree to the new ones.
+    pub fn graft(&mut self, node: NodeIndex, mut subtree: Peel<D>) -> HashMap<NodeIndex, NodeIndex> {
+        let nodes: Vec<NodeIndex> = subtree.graph.node_indices().collect();
+        let mapping = self.move_nodes(&mut subtree, &nodes);
+        if let Some(root) = subtree.root {
+            self.link(node, mapping[&root]);
+        }
+        mapping
+    }
+
+    /// Remove a node together with all of its descendants which are not reachable from the rest
+    /// of the tree and return them as a new tree. The node will be the root of the new tree,
+    /// which keeps the `max_depth` of this one, and named roots pointing into the detached nodes
+    /// will be moved as well. Returns the new tree and the mapping from the current node indices
+    /// to the ones of the new tree.
+    pub fn detach(&mut self, node: NodeIndex) -> (Peel<D>, HashMap<NodeIndex, NodeIndex>) {
+        // Collect all descendants which have only parents within the subtree
+        let mut nodes = vec![node];
+        let mut i = 0;
+        while i < nodes.len() {
+            for child in self.graph.neighbors_directed(nodes[i], Direction::Outgoing) {
+                if !nodes.contains(&child) &&
+                   self.graph
+                    .neighbors_directed(child, Direction::Incoming)
+                    .all(|parent| parent == child || nodes.contains(&parent)) {
+                    nodes.push(child);
+                }
+            }
+            i += 1;
+        }
+
+        // Take over the roots
+        let mut subtree = Peel::new();
+        subtree.max_depth = self.max_depth;
+        let roots: Vec<(String, NodeIndex)> = self.roots
+            .iter()
+            .filter(|&(_, root)| nodes.contains(root))
+            .map(|(name, root)| (name.clone(), *root))
+            .collect();
+        if self.root.map_or(false, |root| nodes.contains(&root)) {
+            self.root = None;
+        }
+
+        let mapping = subtree.move_nodes(self, &nodes);
+        for (name, root) in roots {
+            self.roots.remove(&name);
+            subtree.add_root(&name, mapping[&root]);
+        }
+        (subtree, mapping)
+    }
+
+    /// Move the given nodes and the edges between them from another tree into this one. The
+    /// first moved node becomes the root if none exists.
+    fn move_nodes(&mut self, other: &mut Peel<D>, nodes: &[NodeIndex]) -> HashMap<NodeIndex, NodeIndex> {
+        // Collect the edges in the order they were linked
+        let mut edges = vec![];
+        for node in nodes {
+            let mut targets: Vec<(NodeIndex, LinkKind)> = other.graph
+                .edges(*node)
+                .filter(|edge| nodes.contains(&edge.target()))
+                .map(|edge| (edge.target(), *edge.weight()))
+                .collect();
+            targets.reverse();
+            edges.extend(targets.into_iter().map(|(target, kind)| (*node, target, kind)));
+        }
+
+        // Move the parsers
+        let mut mapping = HashMap::new();
+        for node in nodes {
+            if let Some(parser) = other.graph.remove_node(*node) {
+                mapping.insert(*node, self.new_boxed_parser(parser));
+            }
+        }
+
+        for (left, right, kind) in edges {
+            self.link_with(mapping[&left], mapping[&right], kind);
+        }
+        mapping
+    }
+
+    /// Register an existing node as named entry point for the traversal. An already existing
+    /// root with the same name will be replaced.
+    pub fn add_root(&mut self, name: &str, node: NodeIndex) {
+        info!("New root '{}': {:?}", name, self.graph[node]);
+        self.roots.insert(name.to_owned(), node);
+    }
+
+    /// Create a new parser and register it as named entry point for the traversal
+    pub fn new_root<T>(&mut self, name: &str, parser: T) -> NodeIndex
+        where T: Parsable<D> + 'static
+    {
+        let new_node = self.new_parser(parser);
+        self.add_root(name, new_node);
+        new_node
+    }
+
+    /// Unregister a named root and return its node if existing. The node itself stays within
+    /// the tree.
+    pub fn remove_root(&mut self, name: &str) -> Option<NodeIndex> {
+        self.roots.remove(name)
+    }
+
+    /// Get the node of a named root
+    pub fn get_root(&self, name: &str) -> Option<NodeIndex> {
+        self.roots.get(name).cloned()
+    }
+
     /// Link multiple nodes together
     pub fn link_nodes(&mut self, edges: &[(NodeIndex, NodeIndex)]) {
         for &(left, right) in edges {
@@ -171,6 +353,20 @@ impl<D> Peel<D> {
         }
     }
 
+    /// Recursive traversal with the named root as starting point
+    ///
+    /// # Errors
+    /// When no root with the given name was found or the first parser already fails.
+    pub fn traverse_root<'a>(&mut self, name: &str, input: &'a [u8], result: ParserResultVec) -> PeelResult<'a> {
+        match self.get_root(name) {
+            Some(node) => self.traverse_recursive(node, PeelResult::new(result, input, None)),
+            None => PeelResult::new(result,
+                                    input,
+                                    Some(PeelError::new(ErrorType::NoTreeRoot,
+                                                        &format!("No tree root named '{}' found", name)))),
+        }
+    }
+
     /// Continue the traversal from the last processed node. This can be useful if you want to
     /// continue traversal after an incomplete parsing.
     pub fn continue_traverse<'a>(&mut self, input: &'a [u8], result: ParserResultVec) -> PeelResult<'a> {
@@ -180,6 +376,42 @@ impl<D> Peel<D> {
         self.traverse_recursive(start_node, result)
     }
 
+    /// Recursive traversal with the given node as starting point. This can be used to resume the
+    /// traversal at a node which needed more data before.
+    ///
+    /// # Errors
+    /// When the node does not exist or its parser already fails.
+    pub fn traverse_node<'a>(&mut self, node: NodeIndex, input: &'a [u8], result: ParserResultVec) -> PeelResult<'a> {
+        if !self.graph.contains_node(node) {
+            return PeelResult::new(result,
+                                   input,
+                                   Some(PeelError::new(ErrorType::NoTreeRoot, "Start node not found")));
+        }
+        self.traverse_recursive(node, PeelResult::new(result, input, None))
+    }
+
+    /// Recursive traversal of the children of the given node as if its parser had produced the
+    /// provided results. This allows feeding a subtree with data which was not parsed by the
+    /// tree itself, like reassembled streams.
+    pub fn traverse_children<'a>(&mut self,
+                                 node: NodeIndex,
+                                 input: &'a [u8],
+                                 result: ParserResultVec)
+                                 -> PeelResult<'a> {
+        let result = PeelResult::new(result, input, None);
+        if !self.graph.contains_node(node) {
+            return result;
+        }
+        self.last_position = node;
+        self.traverse_edges(node, result)
+    }
+
+    /// Get the node processed last by the traversal, which is the node to resume at if the
+    /// traversal was incomplete
+    pub fn last_position(&self) -> NodeIndex {
+        self.last_position
+    }
+
     /// Do parsing until all possible paths failed. This is equivalent in finding the deepest
     /// possible parsing result within the tree. The result will be assembled together in the
     /// given result vector, which will be returned at the end.
@@ -207,13 +439,19 @@ impl<D> Peel<D> {
                            parser,
                            left_input.len());
                     peel_result.result.push(parser_result);
+                    peel_result.depths.push(peel_result.depth);
                     peel_result.left_input = left_input;
+                    peel_result.path.push(node_id);
                     None
                 }
 
                 // Parser has not enough data
                 IResult::Incomplete(needed) => {
                     debug!("{:?} needs more data", parser);
+                    peel_result.failed.push(node_id);
+                    if peel_result.incomplete.is_none() {
+                        peel_result.incomplete = Some(node_id);
+                    }
                     peel_result.error = Some(PeelError::new(ErrorType::Incomplete(needed),
                                                             &format!("Incomplete parser: '{:?}'", parser)));
                     return peel_result;
@@ -222,6 +460,7 @@ impl<D> Peel<D> {
                 // Parsing failed
                 IResult::Error(error) => {
                     trace!("Failed parser: {:?}", parser);
+                    peel_result.failed.push(node_id);
                     if peel_result.result.is_empty() {
                         peel_result.error = Some(PeelError::new(ErrorType::NoParserSucceed,
                                                                 "No parser succeed at all"));
@@ -241,45 +480,119 @@ impl<D> Peel<D> {
             }
 
             // Continue traversal if needed
-            _ => {
-                let mut edges = self.graph.neighbors_directed(node_id, Direction::Outgoing).detach();
-                while let Some(node) = edges.next_node(&self.graph) {
-                    // Save the previous result length
-                    let prev_len = peel_result.result.len();
-
-                    // Do the recursion
-                    peel_result = self.traverse_recursive(node, peel_result);
-
-                    // Stop going deeper if something was added to the result
-                    if prev_len < peel_result.result.len() {
-                        break;
-                    }
-                }
-            }
+            _ => peel_result = self.traverse_edges(node_id, peel_result),
         };
 
         // Return the current result
         peel_result
     }
 
+    /// Try all children of the node until the first one succeeds
+    fn traverse_edges<'a>(&mut self, node_id: NodeIndex, mut peel_result: PeelResult<'a>) -> PeelResult<'a> {
+        let mut edges = self.graph.neighbors_directed(node_id, Direction::Outgoing).detach();
+        while let Some((edge, node)) = edges.next(&self.graph) {
+            // Re-entering the tree increases the encapsulation depth
+            let reenter = self.graph[edge] == LinkKind::Reenter;
+            if reenter {
+                if peel_result.depth >= self.max_depth {
+                    debug!("Maximum encapsulation depth {} reached", self.max_depth);
+                    continue;
+                }
+                peel_result.depth += 1;
+            }
+
+            // Save the previous result length
+            let prev_len = peel_result.result.len();
+
+            // Do the recursion
+            peel_result = self.traverse_recursive(node, peel_result);
+            if reenter {
+                peel_result.depth -= 1;
+            }
+
+            // Stop going deeper if something was added to the result
+            if prev_len < peel_result.result.len() {
+                break;
+            }
+        }
+        peel_result
+    }
+
     /// Create a graphviz `graph.dot` file representation in the current directory
     pub fn create_dot_file(&mut self) -> Result<(), PeelError> {
-        // Create a temporarily graph for conversion
-        let mut graph = Graph::<_, ()>::new();
+        self.create_dot_file_at("graph.dot", None)
+    }
+
+    /// Create a graphviz file representation at the given path. The path of a traversal will be
+    /// highlighted if a `PeelResult` is provided.
+    pub fn create_dot_file_at<P>(&self, path: P, highlight: Option<&PeelResult>) -> Result<(), PeelError>
+        where P: AsRef<Path>
+    {
+        let mut f = File::create(path)?;
+        self.write_dot(&mut f, highlight)
+    }
+
+    /// Write a graphviz representation of the tree into the given writer. Nodes are labeled by
+    /// their parser names, edges by the order in which the traversal tries them, where
+    /// re-entering edges are dashed. If a `PeelResult` is provided, all successful nodes of its
+    /// traversal will be colored green and all failed attempts red.
+    pub fn write_dot<W>(&self, writer: &mut W, highlight: Option<&PeelResult>) -> Result<(), PeelError>
+        where W: Write
+    {
+        // Collect the highlighted nodes and edges
+        let mut succeed = HashSet::new();
+        let mut failed = HashSet::new();
+        let mut path_edges = HashSet::new();
+        if let Some(peel_result) = highlight {
+            succeed.extend(peel_result.path.iter().cloned());
+            failed.extend(peel_result.failed.iter().cloned());
+            for pair in peel_result.path.windows(2) {
+                path_edges.insert((pair[0], pair[1]));
+            }
+        }
+
+        writeln!(writer, "digraph {{")?;
 
         // Convert the nodes
         for node_id in self.graph.node_indices() {
-            let parser = &self.graph[node_id];
-            graph.add_node(format!("{:?}", parser));
+            let color = if succeed.contains(&node_id) {
+                ", style=filled, fillcolor=green"
+            } else if failed.contains(&node_id) {
+                ", style=filled, fillcolor=red"
+            } else {
+                ""
+            };
+            writeln!(writer,
+                     "    {} [label=\"{}\"{}]",
+                     node_id.index(),
+                     escape_dot(&self.graph[node_id].name()),
+                     color)?;
         }
 
-        // Convert the edges
-        for edge in self.graph.edge_references() {
-            graph.add_edge(edge.source(), edge.target(), ());
+        // Convert the edges, which are tried in the order of their neighbor iteration
+        for node_id in self.graph.node_indices() {
+            for (priority, edge) in self.graph.edges(node_id).enumerate() {
+                let color = if path_edges.contains(&(edge.source(), edge.target())) {
+                    ", color=green"
+                } else {
+                    ""
+                };
+                let style = if *edge.weight() == LinkKind::Reenter {
+                    ", style=dashed"
+                } else {
+                    ""
+                };
+                writeln!(writer,
+                         "    {} -> {} [label=\"{}\"{}{}]",
+                         edge.source().index(),
+                         edge.target().index(),
+                         priority,
+                         color,
+                         style)?;
+            }
         }
 
-        let mut f = File::create("graph.dot")?;
-        f.write_all(format!("{:?}", Dot::with_config(&graph, &[Config::EdgeNoLabel])).as_bytes())?;
+        writeln!(writer, "}}")?;
         Ok(())
     }
 
@@ -355,3 +668,8 @@ impl<D> Peel<D> {
         }
     }
 }
+
+/// Escape a label to be used within a graphviz file
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,104 @@
+//! Address Resolution Protocol related packets
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Instant;
+
+use nom::{IResult, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The ARP parser
+#[derive(Debug)]
+pub struct ArpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an ARP packet for Ethernet and IPv4
+pub struct ArpPacket {
+    /// The operation
+    pub operation: ArpOperation,
+
+    /// The hardware address of the sender
+    pub sender_mac: MacAddress,
+
+    /// The IP address of the sender
+    pub sender_ip: Ipv4Addr,
+
+    /// The hardware address of the target, unknown for requests
+    pub target_mac: MacAddress,
+
+    /// The IP address of the target
+    pub target_ip: Ipv4Addr,
+
+    /// The sender address was known with another hardware address before
+    pub conflict: Option<NeighborConflict>,
+}
+
+impl ArpPacket {
+    /// Returns true if the sender announces its own address
+    pub fn is_gratuitous(&self) -> bool {
+        self.sender_ip == self.target_ip
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The ARP operations
+pub enum ArpOperation {
+    /// Ask for the hardware address of the target
+    Request,
+
+    /// Answer with the hardware address of the sender
+    Reply,
+
+    /// Any other operation
+    Unknown(u16),
+}
+
+impl From<u16> for ArpOperation {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => ArpOperation::Request,
+            2 => ArpOperation::Reply,
+            other => ArpOperation::Unknown(other),
+        }
+    }
+}
+
+impl Parsable<PacketData> for ArpParser {
+    /// Parse an ARP packet from a byte slice. Only the resolution of IPv4 addresses via Ethernet
+    /// is supported. The left input will be the link layer padding.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the link layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ethertype(result) != Some(EtherType::Arp) {
+            return reject(input);
+        }
+
+        let (rest, mut packet) = try_parse!(input,
+            do_parse!(
+                tag!([0x00, 0x01, 0x08, 0x00, 0x06, 0x04]) >>
+                operation: be_u16 >>
+                sender_mac: take!(6) >>
+                sender_ip: be_u32 >>
+                target_mac: take!(6) >>
+                target_ip: be_u32 >>
+                (ArpPacket {
+                    operation: ArpOperation::from(operation),
+                    sender_mac: mac(sender_mac),
+                    sender_ip: Ipv4Addr::from(sender_ip),
+                    target_mac: mac(target_mac),
+                    target_ip: Ipv4Addr::from(target_ip),
+                    conflict: None,
+                })
+            )
+        );
+
+        // Probes do not announce any address
+        if let (false, Some(cache)) = (packet.sender_ip.is_unspecified(), data.and_then(|d| d.neighbors.as_mut())) {
+            packet.conflict = cache.learn(IpAddr::V4(packet.sender_ip), packet.sender_mac, Instant::now());
+        }
+
+        IResult::Done(rest, Box::new(packet))
+    }
+}
@@ -0,0 +1,347 @@
+//! Cryptographic primitives needed to read the protected packets of the handshake
+//!
+//! Only the functions needed to remove the protection of QUIC Initial packets are provided,
+//! whose keys are derived from public data. They are not intended to protect any secrets and
+//! are therefore only available within the crate.
+
+/// The round constants of SHA-256
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The initial hash value of SHA-256
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// The substitution box of AES
+const AES_SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+/// Compute the SHA-256 digest of the data
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = SHA256_H;
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = (data.len() as u64).wrapping_mul(8);
+    message.extend_from_slice(&[(bits >> 56) as u8, (bits >> 48) as u8, (bits >> 40) as u8, (bits >> 32) as u8,
+                                (bits >> 24) as u8, (bits >> 16) as u8, (bits >> 8) as u8, bits as u8]);
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from(word[0]) << 24 | u32::from(word[1]) << 16 | u32::from(word[2]) << 8 |
+                   u32::from(word[3]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ w[i - 15] >> 3;
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ w[i - 2] >> 10;
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut v = state;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = v[4] & v[5] ^ !v[4] & v[6];
+            let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = v[0] & v[1] ^ v[0] & v[2] ^ v[1] & v[2];
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+        }
+        for (s, v) in state.iter_mut().zip(v.iter()) {
+            *s = s.wrapping_add(*v);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (i, s) in state.iter().enumerate() {
+        digest[4 * i] = (s >> 24) as u8;
+        digest[4 * i + 1] = (s >> 16) as u8;
+        digest[4 * i + 2] = (s >> 8) as u8;
+        digest[4 * i + 3] = *s as u8;
+    }
+    digest
+}
+
+/// Compute the HMAC-SHA256 of the data
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(data);
+    let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+/// Extract a pseudorandom key from the input keying material via HKDF-SHA256
+pub fn hkdf_extract(salt: &[u8], ikm: &[u8]) -> [u8; 32] {
+    hmac_sha256(salt, ikm)
+}
+
+/// Expand the pseudorandom key to the given length via HKDF-SHA256 with the TLS 1.3 label
+pub fn hkdf_expand_label(secret: &[u8], label: &str, length: usize) -> Vec<u8> {
+    let mut info = vec![(length >> 8) as u8, length as u8, (6 + label.len()) as u8];
+    info.extend_from_slice(b"tls13 ");
+    info.extend_from_slice(label.as_bytes());
+    info.push(0);
+
+    let mut output = vec![];
+    let mut previous = vec![];
+    let mut counter = 1u8;
+    while output.len() < length {
+        let mut data = previous;
+        data.extend_from_slice(&info);
+        data.push(counter);
+        previous = hmac_sha256(secret, &data).to_vec();
+        output.extend_from_slice(&previous);
+        counter += 1;
+    }
+    output.truncate(length);
+    output
+}
+
+/// The expanded key schedule of AES-128
+pub struct Aes128 {
+    round_keys: [[u8; 16]; 11],
+}
+
+impl Aes128 {
+    /// Expand the key
+    pub fn new(key: &[u8; 16]) -> Self {
+        let mut words = [[0u8; 4]; 44];
+        for (i, word) in key.chunks(4).enumerate() {
+            words[i].copy_from_slice(word);
+        }
+        let mut rcon = 1u8;
+        for i in 4..44 {
+            let mut word = words[i - 1];
+            if i % 4 == 0 {
+                word = [AES_SBOX[word[1] as usize] ^ rcon,
+                        AES_SBOX[word[2] as usize],
+                        AES_SBOX[word[3] as usize],
+                        AES_SBOX[word[0] as usize]];
+                rcon = xtime(rcon);
+            }
+            for j in 0..4 {
+                words[i][j] = words[i - 4][j] ^ word[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; 11];
+        for (i, round_key) in round_keys.iter_mut().enumerate() {
+            for j in 0..4 {
+                round_key[4 * j..4 * j + 4].copy_from_slice(&words[4 * i + j]);
+            }
+        }
+        Aes128 { round_keys: round_keys }
+    }
+
+    /// Encrypt a single block
+    pub fn encrypt(&self, block: &[u8; 16]) -> [u8; 16] {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+        for round in 1..11 {
+            for byte in state.iter_mut() {
+                *byte = AES_SBOX[*byte as usize];
+            }
+
+            // Shift the rows of the column major state
+            let copy = state;
+            for column in 0..4 {
+                for row in 0..4 {
+                    state[4 * column + row] = copy[4 * ((column + row) % 4) + row];
+                }
+            }
+
+            if round < 10 {
+                for column in state.chunks_mut(4) {
+                    let (a0, a1, a2, a3) = (column[0], column[1], column[2], column[3]);
+                    let all = a0 ^ a1 ^ a2 ^ a3;
+                    column[0] ^= all ^ xtime(a0 ^ a1);
+                    column[1] ^= all ^ xtime(a1 ^ a2);
+                    column[2] ^= all ^ xtime(a2 ^ a3);
+                    column[3] ^= all ^ xtime(a3 ^ a0);
+                }
+            }
+            add_round_key(&mut state, &self.round_keys[round]);
+        }
+        state
+    }
+}
+
+/// Multiply by x within GF(2^8)
+fn xtime(value: u8) -> u8 {
+    if value & 0x80 != 0 {
+        value << 1 ^ 0x1b
+    } else {
+        value << 1
+    }
+}
+
+fn add_round_key(state: &mut [u8; 16], round_key: &[u8; 16]) {
+    for (byte, key) in state.iter_mut().zip(round_key.iter()) {
+        *byte ^= *key;
+    }
+}
+
+/// Multiply two elements of GF(2^128) as defined by GCM
+fn gf128_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0;
+    let mut v = y;
+    for i in 0..128 {
+        if x >> (127 - i) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 {
+            v >> 1 ^ 0xe1 << 120
+        } else {
+            v >> 1
+        };
+    }
+    z
+}
+
+/// Convert up to 16 bytes into a big endian block, padded with zeros
+fn block(data: &[u8]) -> u128 {
+    let mut block = [0u8; 16];
+    block[..data.len()].copy_from_slice(data);
+    block.iter().fold(0, |value, byte| value << 8 | u128::from(*byte))
+}
+
+fn bytes(value: u128) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (120 - 8 * i)) as u8;
+    }
+    bytes
+}
+
+/// Decrypt and authenticate data via AES-128-GCM, where the tag is appended to the ciphertext.
+/// Returns `None` if the authentication fails.
+pub fn aes128_gcm_open(key: &[u8; 16], nonce: &[u8; 12], aad: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < 16 {
+        return None;
+    }
+    let (ciphertext, tag) = data.split_at(data.len() - 16);
+    let aes = Aes128::new(key);
+    let h = block(&aes.encrypt(&[0; 16]));
+
+    // Authenticate the additional data and the ciphertext
+    let mut ghash = 0;
+    for chunk in aad.chunks(16).chain(ciphertext.chunks(16)) {
+        ghash = gf128_mul(ghash ^ block(chunk), h);
+    }
+    let lengths = u128::from(aad.len() as u64 * 8) << 64 | u128::from(ciphertext.len() as u64 * 8);
+    ghash = gf128_mul(ghash ^ lengths, h);
+
+    let mut counter = [0u8; 16];
+    counter[..12].copy_from_slice(nonce);
+    counter[15] = 1;
+    let expected = bytes(ghash ^ block(&aes.encrypt(&counter)));
+    if expected.iter().zip(tag.iter()).fold(0, |diff, (a, b)| diff | a ^ b) != 0 {
+        return None;
+    }
+
+    // Decrypt in counter mode, starting with the counter following the one of the tag
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for (i, chunk) in ciphertext.chunks(16).enumerate() {
+        let count = 2 + i as u32;
+        counter[12..].copy_from_slice(&[(count >> 24) as u8, (count >> 16) as u8, (count >> 8) as u8, count as u8]);
+        let stream = aes.encrypt(&counter);
+        plaintext.extend(chunk.iter().zip(stream.iter()).map(|(c, s)| c ^ s));
+    }
+    Some(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &str) -> Vec<u8> {
+        (0..data.len()).step_by(2).map(|i| u8::from_str_radix(&data[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn key(data: &str) -> [u8; 16] {
+        let mut key = [0; 16];
+        key.copy_from_slice(&hex(data));
+        key
+    }
+
+    #[test]
+    fn sha256_known_answers() {
+        // FIPS 180-2 appendix B.1 and B.2
+        assert_eq!(sha256(b"abc").to_vec(),
+                   hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"));
+        assert_eq!(sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq").to_vec(),
+                   hex("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"));
+    }
+
+    #[test]
+    fn hmac_sha256_known_answers() {
+        // RFC 4231 test cases 2 and 6, where the latter key is longer than a block
+        assert_eq!(hmac_sha256(b"Jefe", b"what do ya want for nothing?").to_vec(),
+                   hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"));
+        assert_eq!(hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First").to_vec(),
+                   hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"));
+    }
+
+    #[test]
+    fn aes128_known_answers() {
+        // FIPS-197 appendix B and C.1
+        let aes = Aes128::new(&key("2b7e151628aed2a6abf7158809cf4f3c"));
+        assert_eq!(aes.encrypt(&key("3243f6a8885a308d313198a2e0370734")),
+                   key("3925841d02dc09fbdc118597196a0b32"));
+        let aes = Aes128::new(&key("000102030405060708090a0b0c0d0e0f"));
+        assert_eq!(aes.encrypt(&key("00112233445566778899aabbccddeeff")),
+                   key("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    }
+
+    #[test]
+    fn aes128_gcm_known_answer() {
+        // Test case 3 of the GCM specification
+        let mut nonce = [0; 12];
+        nonce.copy_from_slice(&hex("cafebabefacedbaddecaf888"));
+        let mut data = hex("42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5a\
+                            ac84aa051ba30b396a0aac973d58e091473f59854d5c2af327cd64a62cf35abd2ba6fab4");
+        let plaintext = hex("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e24\
+                             49a6b525b16aedf5aa0de657ba637b391aafd255");
+        let key = key("feffe9928665731c6d6a8f9467308308");
+        assert_eq!(aes128_gcm_open(&key, &nonce, &[], &data), Some(plaintext));
+
+        // The tag has to match
+        data[0] ^= 1;
+        assert!(aes128_gcm_open(&key, &nonce, &[], &data).is_none());
+    }
+}
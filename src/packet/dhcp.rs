@@ -0,0 +1,760 @@
+//! Dynamic Host Configuration Protocol related packets for IPv4 and IPv6
+//!
+//! DHCPv6 relay agents encapsulate the whole message of the client into an option, which will be
+//! parsed recursively up to a limited depth.
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use nom::{IResult, ErrorKind, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The port of DHCPv4 servers
+pub const DHCPV4_SERVER_PORT: u16 = 67;
+
+/// The port of DHCPv4 clients
+pub const DHCPV4_CLIENT_PORT: u16 = 68;
+
+/// The port of DHCPv6 clients
+pub const DHCPV6_CLIENT_PORT: u16 = 546;
+
+/// The port of DHCPv6 servers and relay agents
+pub const DHCPV6_SERVER_PORT: u16 = 547;
+
+/// The value separating the BOOTP header from the DHCPv4 options
+const MAGIC_COOKIE: u32 = 0x6382_5363;
+
+/// The maximum nesting of DHCPv6 relay messages and encapsulated options
+const MAX_DEPTH: usize = 32;
+
+/// The DHCPv4 parser
+#[derive(Debug)]
+pub struct Dhcpv4Parser;
+
+/// The DHCPv6 parser
+#[derive(Debug)]
+pub struct Dhcpv6Parser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a DHCPv4 message
+pub struct Dhcpv4Packet {
+    /// The message was sent by a server
+    pub reply: bool,
+
+    /// The type of the client hardware address
+    pub hardware_type: u8,
+
+    /// The number of relay agents which forwarded the message
+    pub hops: u8,
+
+    /// The identifier to match requests and replies
+    pub transaction_id: u32,
+
+    /// The seconds elapsed since the client started the exchange
+    pub seconds: u16,
+
+    /// Replies should be broadcast to the client
+    pub broadcast: bool,
+
+    /// The current address of the client
+    pub client_address: Ipv4Addr,
+
+    /// The address assigned to the client
+    pub your_address: Ipv4Addr,
+
+    /// The address of the next server to use for booting
+    pub server_address: Ipv4Addr,
+
+    /// The address of the relay agent
+    pub relay_address: Ipv4Addr,
+
+    /// The hardware address of the client
+    pub client_hardware_address: Vec<u8>,
+
+    /// The optional host name of the server
+    pub server_name: String,
+
+    /// The optional boot file name
+    pub boot_file: String,
+
+    /// The options following the magic cookie
+    pub options: Vec<Dhcpv4Option>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The types of DHCPv4 messages
+pub enum Dhcpv4MessageType {
+    /// A client looks for servers
+    Discover,
+
+    /// A server offers an address
+    Offer,
+
+    /// A client requests the offered address or renews its lease
+    Request,
+
+    /// A client found the address to be in use
+    Decline,
+
+    /// A server acknowledges the request
+    Ack,
+
+    /// A server refuses the request
+    Nak,
+
+    /// A client gives up its address
+    Release,
+
+    /// A client asks for configuration only
+    Inform,
+
+    /// Any other type
+    Unknown(u8),
+}
+
+impl From<u8> for Dhcpv4MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Dhcpv4MessageType::Discover,
+            2 => Dhcpv4MessageType::Offer,
+            3 => Dhcpv4MessageType::Request,
+            4 => Dhcpv4MessageType::Decline,
+            5 => Dhcpv4MessageType::Ack,
+            6 => Dhcpv4MessageType::Nak,
+            7 => Dhcpv4MessageType::Release,
+            8 => Dhcpv4MessageType::Inform,
+            other => Dhcpv4MessageType::Unknown(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A DHCPv4 option, where options with an unexpected length are passed through as unknown
+pub enum Dhcpv4Option {
+    /// The type of the message (53)
+    MessageType(Dhcpv4MessageType),
+
+    /// The address requested by the client (50)
+    RequestedAddress(Ipv4Addr),
+
+    /// The lease time in seconds (51)
+    LeaseTime(u32),
+
+    /// The address of the selected server (54)
+    ServerIdentifier(Ipv4Addr),
+
+    /// The unique identifier of the client (61)
+    ClientIdentifier {
+        /// The hardware type, or zero for other identifiers
+        kind: u8,
+
+        /// The identifier
+        id: Vec<u8>,
+    },
+
+    /// The information added by a relay agent (82)
+    RelayAgent(Vec<Dhcpv4SubOption>),
+
+    /// Any other option, whose data is passed through
+    Unknown {
+        /// The option code
+        code: u8,
+
+        /// The raw option data
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A sub-option of the relay agent information, like the circuit (1) or remote (2) identifier
+pub struct Dhcpv4SubOption {
+    /// The sub-option code
+    pub code: u8,
+
+    /// The sub-option data
+    pub data: Vec<u8>,
+}
+
+impl Dhcpv4Packet {
+    /// Get the type of the message, which is not available for plain BOOTP
+    pub fn message_type(&self) -> Option<Dhcpv4MessageType> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv4Option::MessageType(kind) => Some(kind),
+            _ => None,
+        }).next()
+    }
+
+    /// Get the address requested by the client
+    pub fn requested_address(&self) -> Option<Ipv4Addr> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv4Option::RequestedAddress(address) => Some(address),
+            _ => None,
+        }).next()
+    }
+
+    /// Get the address identifying the server
+    pub fn server_identifier(&self) -> Option<Ipv4Addr> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv4Option::ServerIdentifier(address) => Some(address),
+            _ => None,
+        }).next()
+    }
+
+    /// Get the lease time in seconds
+    pub fn lease_time(&self) -> Option<u32> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv4Option::LeaseTime(time) => Some(time),
+            _ => None,
+        }).next()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a DHCPv6 message
+pub struct Dhcpv6Packet {
+    /// The type of the message
+    pub kind: Dhcpv6MessageType,
+
+    /// The identifier to match requests and replies, zero for relay messages
+    pub transaction_id: u32,
+
+    /// The addresses of relay messages
+    pub relay: Option<Dhcpv6Relay>,
+
+    /// The options of the message
+    pub options: Vec<Dhcpv6Option>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The types of DHCPv6 messages
+pub enum Dhcpv6MessageType {
+    /// A client looks for servers
+    Solicit,
+
+    /// A server is available
+    Advertise,
+
+    /// A client requests addresses from a server
+    Request,
+
+    /// A client checks its addresses after moving
+    Confirm,
+
+    /// A client extends its lease with the original server
+    Renew,
+
+    /// A client extends its lease with any server
+    Rebind,
+
+    /// A server replies to a client
+    Reply,
+
+    /// A client gives up its addresses
+    Release,
+
+    /// A client found addresses to be in use
+    Decline,
+
+    /// A server asks the client to renew
+    Reconfigure,
+
+    /// A client asks for configuration only
+    InformationRequest,
+
+    /// A relay agent forwards a message to a server
+    RelayForward,
+
+    /// A server sends a message to a relay agent
+    RelayReply,
+
+    /// Any other type
+    Unknown(u8),
+}
+
+impl From<u8> for Dhcpv6MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Dhcpv6MessageType::Solicit,
+            2 => Dhcpv6MessageType::Advertise,
+            3 => Dhcpv6MessageType::Request,
+            4 => Dhcpv6MessageType::Confirm,
+            5 => Dhcpv6MessageType::Renew,
+            6 => Dhcpv6MessageType::Rebind,
+            7 => Dhcpv6MessageType::Reply,
+            8 => Dhcpv6MessageType::Release,
+            9 => Dhcpv6MessageType::Decline,
+            10 => Dhcpv6MessageType::Reconfigure,
+            11 => Dhcpv6MessageType::InformationRequest,
+            12 => Dhcpv6MessageType::RelayForward,
+            13 => Dhcpv6MessageType::RelayReply,
+            other => Dhcpv6MessageType::Unknown(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The header of DHCPv6 relay messages
+pub struct Dhcpv6Relay {
+    /// The number of relay agents which forwarded the message
+    pub hop_count: u8,
+
+    /// The address identifying the link of the client
+    pub link_address: Ipv6Addr,
+
+    /// The address of the client or relay agent the message was received from
+    pub peer_address: Ipv6Addr,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A DHCPv6 option, where options with an unexpected length are passed through as unknown
+pub enum Dhcpv6Option {
+    /// The identifier of the client (1)
+    ClientId(Duid),
+
+    /// The identifier of the server (2)
+    ServerId(Duid),
+
+    /// An identity association for non-temporary addresses (3)
+    IaNa {
+        /// The identifier of the association
+        iaid: u32,
+
+        /// The time in seconds until the client renews
+        t1: u32,
+
+        /// The time in seconds until the client rebinds
+        t2: u32,
+
+        /// The encapsulated options, like the addresses
+        options: Vec<Dhcpv6Option>,
+    },
+
+    /// An address of an identity association (5)
+    IaAddress {
+        /// The address
+        address: Ipv6Addr,
+
+        /// The preferred lifetime in seconds
+        preferred_lifetime: u32,
+
+        /// The valid lifetime in seconds
+        valid_lifetime: u32,
+
+        /// The encapsulated options
+        options: Vec<Dhcpv6Option>,
+    },
+
+    /// The requested option codes (6)
+    OptionRequest(Vec<u16>),
+
+    /// The time elapsed since the client started the exchange in hundredths of a second (8)
+    ElapsedTime(u16),
+
+    /// The message forwarded by a relay agent (9)
+    RelayMessage(Box<Dhcpv6Packet>),
+
+    /// The status of an exchange (13)
+    StatusCode {
+        /// The status, where zero means success
+        code: u16,
+
+        /// The message for humans
+        message: String,
+    },
+
+    /// The interface of the relay agent the message was received on (18)
+    InterfaceId(Vec<u8>),
+
+    /// Any other option, whose data is passed through
+    Unknown {
+        /// The option code
+        code: u16,
+
+        /// The raw option data
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A DHCP unique identifier
+pub enum Duid {
+    /// Based on the link layer address and a time (1)
+    LinkLayerTime {
+        /// The hardware type
+        hardware_type: u16,
+
+        /// The seconds since midnight of January 1, 2000 UTC
+        time: u32,
+
+        /// The link layer address
+        address: Vec<u8>,
+    },
+
+    /// Assigned by the vendor (2)
+    Enterprise {
+        /// The enterprise number of the vendor
+        enterprise: u32,
+
+        /// The identifier
+        id: Vec<u8>,
+    },
+
+    /// Based on the link layer address (3)
+    LinkLayer {
+        /// The hardware type
+        hardware_type: u16,
+
+        /// The link layer address
+        address: Vec<u8>,
+    },
+
+    /// Based on a universally unique identifier (4)
+    Uuid(Vec<u8>),
+
+    /// Any other identifier
+    Unknown {
+        /// The type of the identifier
+        kind: u16,
+
+        /// The raw identifier
+        data: Vec<u8>,
+    },
+}
+
+impl Dhcpv6Packet {
+    /// Get the identifier of the client
+    pub fn client_id(&self) -> Option<&Duid> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv6Option::ClientId(ref duid) => Some(duid),
+            _ => None,
+        }).next()
+    }
+
+    /// Get the identifier of the server
+    pub fn server_id(&self) -> Option<&Duid> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv6Option::ServerId(ref duid) => Some(duid),
+            _ => None,
+        }).next()
+    }
+
+    /// Get the message encapsulated by a relay message
+    pub fn relayed(&self) -> Option<&Dhcpv6Packet> {
+        self.options.iter().filter_map(|o| match *o {
+            Dhcpv6Option::RelayMessage(ref packet) => Some(&**packet),
+            _ => None,
+        }).next()
+    }
+
+    /// Get the message of the client or server by following all relay messages
+    pub fn innermost(&self) -> &Dhcpv6Packet {
+        let mut packet = self;
+        while let Some(relayed) = packet.relayed() {
+            packet = relayed;
+        }
+        packet
+    }
+}
+
+impl Parsable<PacketData> for Dhcpv4Parser {
+    /// Parse a DHCPv4 message from a byte slice. The left input will be empty.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or using the well known ports
+        if result.map_or(false, |r| !r.is_empty()) &&
+           !transport_ports(result).map_or(false, |(s, d)| {
+            [s, d].iter().any(|p| *p == DHCPV4_SERVER_PORT || *p == DHCPV4_CLIENT_PORT)
+        }) {
+            return reject(input);
+        }
+
+        let (_, packet) = try_parse!(input,
+            do_parse!(
+                op: verify!(be_u8, |o: u8| o == 1 || o == 2) >>
+                hardware_type: be_u8 >>
+                hardware_length: verify!(be_u8, |l: u8| l <= 16) >>
+                hops: be_u8 >>
+                transaction_id: be_u32 >>
+                seconds: be_u16 >>
+                flags: be_u16 >>
+                client_address: be_u32 >>
+                your_address: be_u32 >>
+                server_address: be_u32 >>
+                relay_address: be_u32 >>
+                client_hardware_address: take!(16) >>
+                server_name: take!(64) >>
+                boot_file: take!(128) >>
+                verify!(be_u32, |c: u32| c == MAGIC_COOKIE) >>
+                options: dhcpv4_options >>
+                (Dhcpv4Packet {
+                    reply: op == 2,
+                    hardware_type: hardware_type,
+                    hops: hops,
+                    transaction_id: transaction_id,
+                    seconds: seconds,
+                    broadcast: flags & 0x8000 != 0,
+                    client_address: Ipv4Addr::from(client_address),
+                    your_address: Ipv4Addr::from(your_address),
+                    server_address: Ipv4Addr::from(server_address),
+                    relay_address: Ipv4Addr::from(relay_address),
+                    client_hardware_address: client_hardware_address[..hardware_length as usize].to_vec(),
+                    server_name: null_terminated(server_name),
+                    boot_file: null_terminated(boot_file),
+                    options: options,
+                })
+            )
+        );
+
+        IResult::Done(&input[input.len()..], Box::new(packet))
+    }
+}
+
+impl Parsable<PacketData> for Dhcpv6Parser {
+    /// Parse a DHCPv6 message including all relayed messages from a byte slice. The left input
+    /// will be empty.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or using the well known ports
+        if result.map_or(false, |r| !r.is_empty()) &&
+           !transport_ports(result).map_or(false, |(s, d)| {
+            [s, d].iter().any(|p| *p == DHCPV6_CLIENT_PORT || *p == DHCPV6_SERVER_PORT)
+        }) {
+            return reject(input);
+        }
+
+        let (rest, packet) = try_parse!(input, apply!(dhcpv6_message, 0));
+        IResult::Done(rest, Box::new(packet))
+    }
+}
+
+/// Convert a field padded with zeros into a string
+fn null_terminated(input: &[u8]) -> String {
+    let end = input.iter().position(|b| *b == 0).unwrap_or(input.len());
+    String::from_utf8_lossy(&input[..end]).into_owned()
+}
+
+/// Parse the DHCPv4 options until the end option, skipping all padding
+fn dhcpv4_options(mut input: &[u8]) -> IResult<&[u8], Vec<Dhcpv4Option>> {
+    let mut options = vec![];
+    loop {
+        let (rest, code) = try_parse!(input, be_u8);
+        match code {
+            0 => input = rest,
+            255 => return IResult::Done(rest, options),
+            _ => {
+                let (rest, data) = try_parse!(rest, length_bytes!(be_u8));
+                options.push(dhcpv4_option(code, data));
+                input = rest;
+            }
+        }
+    }
+}
+
+/// Interpret the data of a DHCPv4 option
+fn dhcpv4_option(code: u8, data: &[u8]) -> Dhcpv4Option {
+    let address = || Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+    match code {
+        53 if data.len() == 1 => Dhcpv4Option::MessageType(Dhcpv4MessageType::from(data[0])),
+        50 if data.len() == 4 => Dhcpv4Option::RequestedAddress(address()),
+        51 if data.len() == 4 => Dhcpv4Option::LeaseTime(u32::from(address())),
+        54 if data.len() == 4 => Dhcpv4Option::ServerIdentifier(address()),
+        61 if !data.is_empty() => {
+            Dhcpv4Option::ClientIdentifier {
+                kind: data[0],
+                id: data[1..].to_vec(),
+            }
+        }
+        82 => {
+            let sub_options: IResult<&[u8], Vec<(u8, &[u8])>> =
+                many0!(data, complete!(pair!(be_u8, length_bytes!(be_u8))));
+            match sub_options {
+                IResult::Done(&[], sub_options) => {
+                    Dhcpv4Option::RelayAgent(sub_options.into_iter()
+                        .map(|(code, data)| {
+                            Dhcpv4SubOption {
+                                code: code,
+                                data: data.to_vec(),
+                            }
+                        })
+                        .collect())
+                }
+                _ => {
+                    Dhcpv4Option::Unknown {
+                        code: code,
+                        data: data.to_vec(),
+                    }
+                }
+            }
+        }
+        _ => {
+            Dhcpv4Option::Unknown {
+                code: code,
+                data: data.to_vec(),
+            }
+        }
+    }
+}
+
+/// Parse a DHCPv6 message, which fills the whole input
+fn dhcpv6_message(input: &[u8], depth: usize) -> IResult<&[u8], Dhcpv6Packet> {
+    let (rest, kind) = try_parse!(input, map!(be_u8, Dhcpv6MessageType::from));
+    let (rest, transaction_id, relay) = match kind {
+        Dhcpv6MessageType::RelayForward |
+        Dhcpv6MessageType::RelayReply => {
+            let (rest, relay) = try_parse!(rest,
+                do_parse!(
+                    hop_count: be_u8 >>
+                    link_address: take!(16) >>
+                    peer_address: take!(16) >>
+                    (Dhcpv6Relay {
+                        hop_count: hop_count,
+                        link_address: ipv6_address(link_address),
+                        peer_address: ipv6_address(peer_address),
+                    })
+                )
+            );
+            (rest, 0, Some(relay))
+        }
+        Dhcpv6MessageType::Unknown(_) => return reject(input),
+        _ => {
+            let (rest, id) = try_parse!(rest, take!(3));
+            (rest, id.iter().fold(0, |v, b| v << 8 | u32::from(*b)), None)
+        }
+    };
+
+    let (rest, options) = try_parse!(rest, apply!(dhcpv6_options, depth));
+    IResult::Done(rest,
+                  Dhcpv6Packet {
+                      kind: kind,
+                      transaction_id: transaction_id,
+                      relay: relay,
+                      options: options,
+                  })
+}
+
+/// Parse DHCPv6 options, which fill the whole input
+fn dhcpv6_options(mut input: &[u8], depth: usize) -> IResult<&[u8], Vec<Dhcpv6Option>> {
+    if depth >= MAX_DEPTH {
+        return IResult::Error(error_position!(ErrorKind::Verify, input));
+    }
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (rest, (code, data)) = try_parse!(input, pair!(be_u16, length_bytes!(be_u16)));
+        options.push(dhcpv6_option(code, data, depth));
+        input = rest;
+    }
+    IResult::Done(input, options)
+}
+
+/// Interpret the data of a DHCPv6 option, where encapsulated options are one level deeper
+fn dhcpv6_option(code: u16, data: &[u8], depth: usize) -> Dhcpv6Option {
+    let option = match code {
+        1 => duid(data).map(Dhcpv6Option::ClientId),
+        2 => duid(data).map(Dhcpv6Option::ServerId),
+        3 => {
+            do_parse!(data,
+                iaid: be_u32 >>
+                t1: be_u32 >>
+                t2: be_u32 >>
+                options: apply!(dhcpv6_options, depth + 1) >>
+                (Dhcpv6Option::IaNa {
+                    iaid: iaid,
+                    t1: t1,
+                    t2: t2,
+                    options: options,
+                })
+            )
+        }
+        5 => {
+            do_parse!(data,
+                address: take!(16) >>
+                preferred_lifetime: be_u32 >>
+                valid_lifetime: be_u32 >>
+                options: apply!(dhcpv6_options, depth + 1) >>
+                (Dhcpv6Option::IaAddress {
+                    address: ipv6_address(address),
+                    preferred_lifetime: preferred_lifetime,
+                    valid_lifetime: valid_lifetime,
+                    options: options,
+                })
+            )
+        }
+        6 if data.len() % 2 == 0 => map!(data, many0!(complete!(be_u16)), Dhcpv6Option::OptionRequest),
+        8 if data.len() == 2 => map!(data, be_u16, Dhcpv6Option::ElapsedTime),
+        9 => map!(data, apply!(dhcpv6_message, depth + 1), |p| Dhcpv6Option::RelayMessage(Box::new(p))),
+        13 if data.len() >= 2 => {
+            IResult::Done(&data[data.len()..],
+                          Dhcpv6Option::StatusCode {
+                              code: u16::from(data[0]) << 8 | u16::from(data[1]),
+                              message: String::from_utf8_lossy(&data[2..]).into_owned(),
+                          })
+        }
+        18 => IResult::Done(&data[data.len()..], Dhcpv6Option::InterfaceId(data.to_vec())),
+        _ => reject(data),
+    };
+
+    match option {
+        IResult::Done(&[], option) => option,
+        _ => {
+            Dhcpv6Option::Unknown {
+                code: code,
+                data: data.to_vec(),
+            }
+        }
+    }
+}
+
+/// Parse a DHCP unique identifier
+fn duid(input: &[u8]) -> IResult<&[u8], Duid> {
+    let (rest, kind) = try_parse!(input, be_u16);
+    let duid = match kind {
+        1 => {
+            try_parse!(rest,
+                do_parse!(
+                    hardware_type: be_u16 >>
+                    time: be_u32 >>
+                    (Duid::LinkLayerTime {
+                        hardware_type: hardware_type,
+                        time: time,
+                        address: rest[6..].to_vec(),
+                    })
+                )
+            ).1
+        }
+        2 => {
+            try_parse!(rest,
+                do_parse!(
+                    enterprise: be_u32 >>
+                    (Duid::Enterprise {
+                        enterprise: enterprise,
+                        id: rest[4..].to_vec(),
+                    })
+                )
+            ).1
+        }
+        3 => {
+            try_parse!(rest,
+                do_parse!(
+                    hardware_type: be_u16 >>
+                    (Duid::LinkLayer {
+                        hardware_type: hardware_type,
+                        address: rest[2..].to_vec(),
+                    })
+                )
+            ).1
+        }
+        4 if rest.len() == 16 => Duid::Uuid(rest.to_vec()),
+        _ => {
+            Duid::Unknown {
+                kind: kind,
+                data: rest.to_vec(),
+            }
+        }
+    };
+    IResult::Done(&rest[rest.len()..], duid)
+}
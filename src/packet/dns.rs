@@ -0,0 +1,522 @@
+//! Domain Name System related packets
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use nom::{IResult, ErrorKind, Needed, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The well known DNS port
+pub const DNS_PORT: u16 = 53;
+
+/// The maximum length of a domain name
+const MAX_NAME_LENGTH: usize = 255;
+
+/// The DNS parser
+#[derive(Debug)]
+pub struct DnsParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a DNS message
+pub struct DnsPacket {
+    /// The identifier to match queries and responses
+    pub id: u16,
+
+    /// The message is a response
+    pub response: bool,
+
+    /// The kind of query
+    pub opcode: u8,
+
+    /// The responding server is an authority for the domain
+    pub authoritative: bool,
+
+    /// The message was truncated
+    pub truncated: bool,
+
+    /// Recursive resolution is requested
+    pub recursion_desired: bool,
+
+    /// Recursive resolution is supported by the server
+    pub recursion_available: bool,
+
+    /// All data in the response was authenticated via DNSSEC
+    pub authentic_data: bool,
+
+    /// DNSSEC validation is disabled
+    pub checking_disabled: bool,
+
+    /// The response code
+    pub rcode: u8,
+
+    /// The questions
+    pub questions: Vec<DnsQuestion>,
+
+    /// The resource records answering the questions
+    pub answers: Vec<DnsRecord>,
+
+    /// The resource records pointing to authorities
+    pub authorities: Vec<DnsRecord>,
+
+    /// The resource records holding additional information
+    pub additionals: Vec<DnsRecord>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The types of resource records
+pub enum DnsRecordType {
+    /// IPv4 address
+    A,
+
+    /// Authoritative name server
+    Ns,
+
+    /// Canonical name of an alias
+    Cname,
+
+    /// Start of a zone of authority
+    Soa,
+
+    /// Domain name pointer
+    Ptr,
+
+    /// Mail exchange
+    Mx,
+
+    /// Text strings
+    Txt,
+
+    /// IPv6 address
+    Aaaa,
+
+    /// Service locator
+    Srv,
+
+    /// EDNS0 pseudo record
+    Opt,
+
+    /// Any other type
+    Unknown(u16),
+}
+
+impl From<u16> for DnsRecordType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => DnsRecordType::A,
+            2 => DnsRecordType::Ns,
+            5 => DnsRecordType::Cname,
+            6 => DnsRecordType::Soa,
+            12 => DnsRecordType::Ptr,
+            15 => DnsRecordType::Mx,
+            16 => DnsRecordType::Txt,
+            28 => DnsRecordType::Aaaa,
+            33 => DnsRecordType::Srv,
+            41 => DnsRecordType::Opt,
+            other => DnsRecordType::Unknown(other),
+        }
+    }
+}
+
+impl From<DnsRecordType> for u16 {
+    fn from(kind: DnsRecordType) -> Self {
+        match kind {
+            DnsRecordType::A => 1,
+            DnsRecordType::Ns => 2,
+            DnsRecordType::Cname => 5,
+            DnsRecordType::Soa => 6,
+            DnsRecordType::Ptr => 12,
+            DnsRecordType::Mx => 15,
+            DnsRecordType::Txt => 16,
+            DnsRecordType::Aaaa => 28,
+            DnsRecordType::Srv => 33,
+            DnsRecordType::Opt => 41,
+            DnsRecordType::Unknown(other) => other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A question of a DNS message
+pub struct DnsQuestion {
+    /// The queried domain name
+    pub name: String,
+
+    /// The queried record type
+    pub kind: DnsRecordType,
+
+    /// The queried class
+    pub class: u16,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A resource record of a DNS message
+pub struct DnsRecord {
+    /// The domain name the record belongs to
+    pub name: String,
+
+    /// The class, which is the UDP payload size for OPT records
+    pub class: u16,
+
+    /// The time to live in seconds, which contains the extended flags for OPT records
+    pub ttl: u32,
+
+    /// The type specific data
+    pub data: DnsRecordData,
+}
+
+impl DnsRecord {
+    /// Get the type of the record
+    pub fn kind(&self) -> DnsRecordType {
+        match self.data {
+            DnsRecordData::A(_) => DnsRecordType::A,
+            DnsRecordData::Ns(_) => DnsRecordType::Ns,
+            DnsRecordData::Cname(_) => DnsRecordType::Cname,
+            DnsRecordData::Soa { .. } => DnsRecordType::Soa,
+            DnsRecordData::Ptr(_) => DnsRecordType::Ptr,
+            DnsRecordData::Mx { .. } => DnsRecordType::Mx,
+            DnsRecordData::Txt(_) => DnsRecordType::Txt,
+            DnsRecordData::Aaaa(_) => DnsRecordType::Aaaa,
+            DnsRecordData::Srv { .. } => DnsRecordType::Srv,
+            DnsRecordData::Opt(_) => DnsRecordType::Opt,
+            DnsRecordData::Unknown { kind, .. } => DnsRecordType::Unknown(kind),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The data of a resource record
+pub enum DnsRecordData {
+    /// IPv4 address
+    A(Ipv4Addr),
+
+    /// Authoritative name server
+    Ns(String),
+
+    /// Canonical name of an alias
+    Cname(String),
+
+    /// Start of a zone of authority
+    Soa {
+        /// The primary name server
+        mname: String,
+
+        /// The mailbox of the responsible person
+        rname: String,
+
+        /// The version of the zone
+        serial: u32,
+
+        /// The refresh interval in seconds
+        refresh: u32,
+
+        /// The retry interval in seconds
+        retry: u32,
+
+        /// The expiry limit in seconds
+        expire: u32,
+
+        /// The time to live for negative responses in seconds
+        minimum: u32,
+    },
+
+    /// Domain name pointer
+    Ptr(String),
+
+    /// Mail exchange
+    Mx {
+        /// Lower values are preferred
+        preference: u16,
+
+        /// The mail server
+        exchange: String,
+    },
+
+    /// Text strings
+    Txt(Vec<Vec<u8>>),
+
+    /// IPv6 address
+    Aaaa(Ipv6Addr),
+
+    /// Service locator
+    Srv {
+        /// Lower values are preferred
+        priority: u16,
+
+        /// The relative weight of records with the same priority
+        weight: u16,
+
+        /// The port of the service
+        port: u16,
+
+        /// The host providing the service
+        target: String,
+    },
+
+    /// EDNS0 options
+    Opt(Vec<EdnsOption>),
+
+    /// Any other record, whose data is passed through
+    Unknown {
+        /// The record type
+        kind: u16,
+
+        /// The raw record data
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An option of the EDNS0 pseudo record
+pub struct EdnsOption {
+    /// The option code
+    pub code: u16,
+
+    /// The option data
+    pub data: Vec<u8>,
+}
+
+impl Parsable<PacketData> for DnsParser {
+    /// Parse a DNS message from a byte slice. Messages carried by TCP are prefixed by their
+    /// length. The left input will be the data following the message.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or using the well known port
+        let ports = transport_ports(result);
+        if input.is_empty() ||
+           result.map_or(false, |r| !r.is_empty()) && !ports.map_or(false, |(s, d)| s == DNS_PORT || d == DNS_PORT) {
+            return reject(input);
+        }
+
+        // Get the message boundaries
+        let (message, rest) = if last_result::<TcpPacket>(result).is_some() ||
+                                 last_result::<TcpStream>(result).is_some() {
+            let (data, length) = try_parse!(input, be_u16);
+            if data.len() < length as usize {
+                return IResult::Incomplete(Needed::Size(length as usize + 2));
+            }
+            data.split_at(length as usize)
+        } else {
+            (input, &input[input.len()..])
+        };
+
+        match dns_message(message) {
+            IResult::Done(_, packet) => IResult::Done(rest, Box::new(packet)),
+            IResult::Error(e) => IResult::Error(e),
+            IResult::Incomplete(_) => IResult::Error(error_position!(ErrorKind::Complete, message)),
+        }
+    }
+}
+
+/// Parse a whole DNS message
+fn dns_message(message: &[u8]) -> IResult<&[u8], DnsPacket> {
+    let (mut input, (id, flags, questions, answers, authorities, additionals)) = try_parse!(message,
+        tuple!(be_u16, be_u16, be_u16, be_u16, be_u16, be_u16));
+
+    let counts = [answers, authorities, additionals];
+    let questions_count = questions;
+    let mut questions = vec![];
+    for _ in 0..questions_count {
+        let (rest, question) = try_parse!(input,
+            do_parse!(
+                name: apply!(dns_name, message) >>
+                kind: be_u16 >>
+                class: be_u16 >>
+                (DnsQuestion {
+                    name: name,
+                    kind: DnsRecordType::from(kind),
+                    class: class,
+                })
+            )
+        );
+        input = rest;
+        questions.push(question);
+    }
+
+    let mut sections = vec![];
+    for count in &counts {
+        let mut records = vec![];
+        for _ in 0..*count {
+            let (rest, record) = try_parse!(input, apply!(dns_record, message));
+            input = rest;
+            records.push(record);
+        }
+        sections.push(records);
+    }
+    let additionals = sections.pop().unwrap_or_default();
+    let authorities = sections.pop().unwrap_or_default();
+    let answers = sections.pop().unwrap_or_default();
+
+    IResult::Done(input,
+                  DnsPacket {
+                      id: id,
+                      response: flags & 0x8000 != 0,
+                      opcode: ((flags >> 11) & 0x0f) as u8,
+                      authoritative: flags & 0x0400 != 0,
+                      truncated: flags & 0x0200 != 0,
+                      recursion_desired: flags & 0x0100 != 0,
+                      recursion_available: flags & 0x0080 != 0,
+                      authentic_data: flags & 0x0020 != 0,
+                      checking_disabled: flags & 0x0010 != 0,
+                      rcode: (flags & 0x000f) as u8,
+                      questions: questions,
+                      answers: answers,
+                      authorities: authorities,
+                      additionals: additionals,
+                  })
+}
+
+/// Parse a domain name, where compression pointers refer to the whole message and the input has
+/// to be a part of it. Every pointer has to point before itself and before the previous pointer,
+/// which avoids loops.
+fn dns_name<'a>(input: &'a [u8], message: &'a [u8]) -> IResult<&'a [u8], String> {
+    let mut labels: Vec<String> = vec![];
+    let mut length = 0;
+    let mut rest = None;
+    let mut data = input;
+    let mut position = input.as_ptr() as usize - message.as_ptr() as usize;
+    let mut limit = message.len();
+    loop {
+        let (left, label_length) = try_parse!(data, be_u8);
+        match label_length & 0xc0 {
+            // The end of the name or a label
+            0x00 => {
+                if label_length == 0 {
+                    if rest.is_none() {
+                        rest = Some(left);
+                    }
+                    break;
+                }
+                let (left, label) = try_parse!(left, take!(label_length));
+                length += label.len() + 1;
+                if length > MAX_NAME_LENGTH {
+                    return IResult::Error(error_position!(ErrorKind::Verify, input));
+                }
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += label.len() + 1;
+                data = left;
+            }
+
+            // A pointer to a previous name
+            0xc0 => {
+                let (left, pointer) = try_parse!(data, be_u16);
+                let offset = (pointer & 0x3fff) as usize;
+                if offset >= ::std::cmp::min(position, limit) {
+                    return IResult::Error(error_position!(ErrorKind::Verify, input));
+                }
+                if rest.is_none() {
+                    rest = Some(left);
+                }
+                limit = offset;
+                position = offset;
+                data = &message[offset..];
+            }
+
+            // Extended label types are not supported
+            _ => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+        }
+    }
+
+    let name = if labels.is_empty() {
+        ".".to_owned()
+    } else {
+        labels.join(".")
+    };
+    IResult::Done(rest.unwrap_or(data), name)
+}
+
+/// Parse a resource record
+fn dns_record<'a>(input: &'a [u8], message: &'a [u8]) -> IResult<&'a [u8], DnsRecord> {
+    let (rest, (name, kind, class, ttl, rdata)) = try_parse!(input,
+        tuple!(apply!(dns_name, message), be_u16, be_u16, be_u32, length_bytes!(be_u16)));
+
+    let data = match DnsRecordType::from(kind) {
+        DnsRecordType::A if rdata.len() == 4 => DnsRecordData::A(Ipv4Addr::from(try_parse!(rdata, be_u32).1)),
+        DnsRecordType::Aaaa if rdata.len() == 16 => DnsRecordData::Aaaa(ipv6_address(rdata)),
+        DnsRecordType::Ns => DnsRecordData::Ns(try_parse!(rdata, apply!(dns_name, message)).1),
+        DnsRecordType::Cname => DnsRecordData::Cname(try_parse!(rdata, apply!(dns_name, message)).1),
+        DnsRecordType::Ptr => DnsRecordData::Ptr(try_parse!(rdata, apply!(dns_name, message)).1),
+        DnsRecordType::Mx => {
+            try_parse!(rdata,
+                do_parse!(
+                    preference: be_u16 >>
+                    exchange: apply!(dns_name, message) >>
+                    (DnsRecordData::Mx {
+                        preference: preference,
+                        exchange: exchange,
+                    })
+                )
+            ).1
+        }
+        DnsRecordType::Soa => {
+            try_parse!(rdata,
+                do_parse!(
+                    mname: apply!(dns_name, message) >>
+                    rname: apply!(dns_name, message) >>
+                    serial: be_u32 >>
+                    refresh: be_u32 >>
+                    retry: be_u32 >>
+                    expire: be_u32 >>
+                    minimum: be_u32 >>
+                    (DnsRecordData::Soa {
+                        mname: mname,
+                        rname: rname,
+                        serial: serial,
+                        refresh: refresh,
+                        retry: retry,
+                        expire: expire,
+                        minimum: minimum,
+                    })
+                )
+            ).1
+        }
+        DnsRecordType::Txt => {
+            let strings = try_parse!(rdata, many0!(complete!(length_bytes!(be_u8)))).1;
+            DnsRecordData::Txt(strings.into_iter().map(|s| s.to_vec()).collect())
+        }
+        DnsRecordType::Srv => {
+            try_parse!(rdata,
+                do_parse!(
+                    priority: be_u16 >>
+                    weight: be_u16 >>
+                    port: be_u16 >>
+                    target: apply!(dns_name, message) >>
+                    (DnsRecordData::Srv {
+                        priority: priority,
+                        weight: weight,
+                        port: port,
+                        target: target,
+                    })
+                )
+            ).1
+        }
+        DnsRecordType::Opt => {
+            let options = try_parse!(rdata,
+                many0!(complete!(do_parse!(
+                    code: be_u16 >>
+                    data: length_bytes!(be_u16) >>
+                    (EdnsOption {
+                        code: code,
+                        data: data.to_vec(),
+                    })
+                )))
+            ).1;
+            DnsRecordData::Opt(options)
+        }
+        _ => {
+            DnsRecordData::Unknown {
+                kind: kind,
+                data: rdata.to_vec(),
+            }
+        }
+    };
+
+    IResult::Done(rest,
+                  DnsRecord {
+                      name: name,
+                      class: class,
+                      ttl: ttl,
+                      data: data,
+                  })
+}
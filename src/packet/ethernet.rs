@@ -0,0 +1,224 @@
+//! Ethernet II and IEEE 802.3 with LLC/SNAP related packets
+use std::fmt;
+
+use nom::{IResult, Needed, be_u8, be_u16};
+
+use packet::prelude::*;
+
+/// The Ethernet parser
+#[derive(Debug)]
+pub struct EthernetParser;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// A hardware address
+pub struct MacAddress(pub [u8; 6]);
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+               self.0[0],
+               self.0[1],
+               self.0[2],
+               self.0[3],
+               self.0[4],
+               self.0[5])
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// The protocol of the payload following a link layer header
+pub enum EtherType {
+    /// Internet Protocol version 4
+    Ipv4,
+
+    /// Address Resolution Protocol
+    Arp,
+
+    /// IEEE 802.1Q customer VLAN tag
+    Vlan,
+
+    /// IEEE 802.1ad service VLAN tag
+    ProviderBridging,
+
+    /// Internet Protocol version 6
+    Ipv6,
+
+    /// MPLS unicast
+    Mpls,
+
+    /// MPLS multicast
+    MplsMulticast,
+
+    /// Transparent Ethernet bridging, an encapsulated Ethernet frame
+    TransparentEthernetBridging,
+
+    /// Any other protocol
+    Unknown(u16),
+}
+
+impl From<u16> for EtherType {
+    fn from(value: u16) -> Self {
+        match value {
+            0x0800 => EtherType::Ipv4,
+            0x0806 => EtherType::Arp,
+            0x8100 => EtherType::Vlan,
+            0x88a8 => EtherType::ProviderBridging,
+            0x86dd => EtherType::Ipv6,
+            0x8847 => EtherType::Mpls,
+            0x8848 => EtherType::MplsMulticast,
+            0x6558 => EtherType::TransparentEthernetBridging,
+            other => EtherType::Unknown(other),
+        }
+    }
+}
+
+impl From<EtherType> for u16 {
+    fn from(ethertype: EtherType) -> Self {
+        match ethertype {
+            EtherType::Ipv4 => 0x0800,
+            EtherType::Arp => 0x0806,
+            EtherType::Vlan => 0x8100,
+            EtherType::ProviderBridging => 0x88a8,
+            EtherType::Ipv6 => 0x86dd,
+            EtherType::Mpls => 0x8847,
+            EtherType::MplsMulticast => 0x8848,
+            EtherType::TransparentEthernetBridging => 0x6558,
+            EtherType::Unknown(other) => other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an Ethernet frame header
+pub struct EthernetPacket {
+    /// The destination hardware address
+    pub dst: MacAddress,
+
+    /// The source hardware address
+    pub src: MacAddress,
+
+    /// The protocol of the payload, which is not available for plain LLC frames
+    pub ethertype: Option<EtherType>,
+
+    /// The payload length of IEEE 802.3 frames
+    pub length: Option<u16>,
+
+    /// The IEEE 802.2 LLC header of IEEE 802.3 frames
+    pub llc: Option<LlcHeader>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an IEEE 802.2 logical link control header
+pub struct LlcHeader {
+    /// Destination service access point
+    pub dsap: u8,
+
+    /// Source service access point
+    pub ssap: u8,
+
+    /// Control field, which has two bytes for information and supervisory frames
+    pub control: u16,
+
+    /// The subnetwork access protocol extension
+    pub snap: Option<SnapHeader>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a subnetwork access protocol header
+pub struct SnapHeader {
+    /// The organizationally unique identifier
+    pub oui: [u8; 3],
+
+    /// The protocol identifier, an `EtherType` for the zero OUI
+    pub protocol: u16,
+}
+
+/// The largest value of the type field which is interpreted as IEEE 802.3 length
+const MAX_LENGTH: u16 = 1500;
+
+impl Parsable<PacketData> for EthernetParser {
+    /// Parse an Ethernet frame from a byte slice
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or encapsulated by a tunnel
+        if result.map_or(false, |r| !r.is_empty()) &&
+           next_ethertype(result) != Some(EtherType::TransparentEthernetBridging) {
+            return reject(input);
+        }
+
+        let (input, (dst, src, ethertype)) = try_parse!(input,
+                                                       do_parse!(dst: take!(6) >>
+                                                                 src: take!(6) >>
+                                                                 ethertype: be_u16 >>
+                                                                 (mac(dst), mac(src), ethertype)));
+
+        // Ethernet II frame
+        if ethertype > MAX_LENGTH {
+            return IResult::Done(input,
+                                 Box::new(EthernetPacket {
+                                     dst: dst,
+                                     src: src,
+                                     ethertype: Some(EtherType::from(ethertype)),
+                                     length: None,
+                                     llc: None,
+                                 }));
+        }
+
+        // IEEE 802.3 frame, strip the padding
+        if (ethertype as usize) > input.len() {
+            return IResult::Incomplete(Needed::Size(ethertype as usize - input.len()));
+        }
+        let payload = &input[..ethertype as usize];
+        let (payload, llc) = try_parse!(payload, llc_header);
+        let protocol = match llc.snap {
+            Some(SnapHeader { oui: [0, 0, 0], protocol }) => Some(EtherType::from(protocol)),
+            _ => None,
+        };
+        IResult::Done(payload,
+                      Box::new(EthernetPacket {
+                          dst: dst,
+                          src: src,
+                          ethertype: protocol,
+                          length: Some(ethertype),
+                          llc: Some(llc),
+                      }))
+    }
+}
+
+/// Parse an IEEE 802.2 LLC header including an optional SNAP extension
+fn llc_header(input: &[u8]) -> IResult<&[u8], LlcHeader> {
+    do_parse!(input,
+        dsap: be_u8 >>
+        ssap: be_u8 >>
+        control: be_u8 >>
+        // Unnumbered frames have the two lowest bits set, all others a second control byte
+        extended: cond!(control & 0x03 != 0x03, be_u8) >>
+        snap: cond!(dsap == 0xaa && ssap == 0xaa,
+                    do_parse!(oui: take!(3) >>
+                              protocol: be_u16 >>
+                              (SnapHeader {
+                                  oui: [oui[0], oui[1], oui[2]],
+                                  protocol: protocol,
+                              }))) >>
+        (LlcHeader {
+            dsap: dsap,
+            ssap: ssap,
+            control: match extended {
+                Some(extended) => (control as u16) << 8 | extended as u16,
+                None => control as u16,
+            },
+            snap: snap,
+        })
+    )
+}
+
+/// Convert a six byte slice into a `MacAddress`
+pub fn mac(input: &[u8]) -> MacAddress {
+    let mut address = [0; 6];
+    address.copy_from_slice(&input[..6]);
+    MacAddress(address)
+}
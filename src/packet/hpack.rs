@@ -0,0 +1,311 @@
+//! HPACK header compression for HTTP/2
+//!
+//! Every direction of a connection uses its own `HpackDecoder`, since the dynamic table is
+//! updated by the decoded header blocks. The blocks have to be decoded in the order they were
+//! sent, otherwise the table gets out of sync and all following blocks are decoded wrong.
+use std::collections::{HashMap, VecDeque};
+
+use packet::http::HttpHeaders;
+
+/// The additional size of every dynamic table entry
+const ENTRY_OVERHEAD: usize = 32;
+
+/// The static table, which is addressed by the indices 1 to 61
+const STATIC_TABLE: [(&str, &str); 61] = [(":authority", ""),
+                                          (":method", "GET"),
+                                          (":method", "POST"),
+                                          (":path", "/"),
+                                          (":path", "/index.html"),
+                                          (":scheme", "http"),
+                                          (":scheme", "https"),
+                                          (":status", "200"),
+                                          (":status", "204"),
+                                          (":status", "206"),
+                                          (":status", "304"),
+                                          (":status", "400"),
+                                          (":status", "404"),
+                                          (":status", "500"),
+                                          ("accept-charset", ""),
+                                          ("accept-encoding", "gzip, deflate"),
+                                          ("accept-language", ""),
+                                          ("accept-ranges", ""),
+                                          ("accept", ""),
+                                          ("access-control-allow-origin", ""),
+                                          ("age", ""),
+                                          ("allow", ""),
+                                          ("authorization", ""),
+                                          ("cache-control", ""),
+                                          ("content-disposition", ""),
+                                          ("content-encoding", ""),
+                                          ("content-language", ""),
+                                          ("content-length", ""),
+                                          ("content-location", ""),
+                                          ("content-range", ""),
+                                          ("content-type", ""),
+                                          ("cookie", ""),
+                                          ("date", ""),
+                                          ("etag", ""),
+                                          ("expect", ""),
+                                          ("expires", ""),
+                                          ("from", ""),
+                                          ("host", ""),
+                                          ("if-match", ""),
+                                          ("if-modified-since", ""),
+                                          ("if-none-match", ""),
+                                          ("if-range", ""),
+                                          ("if-unmodified-since", ""),
+                                          ("last-modified", ""),
+                                          ("link", ""),
+                                          ("location", ""),
+                                          ("max-forwards", ""),
+                                          ("proxy-authenticate", ""),
+                                          ("proxy-authorization", ""),
+                                          ("range", ""),
+                                          ("referer", ""),
+                                          ("refresh", ""),
+                                          ("retry-after", ""),
+                                          ("server", ""),
+                                          ("set-cookie", ""),
+                                          ("strict-transport-security", ""),
+                                          ("transfer-encoding", ""),
+                                          ("user-agent", ""),
+                                          ("vary", ""),
+                                          ("via", ""),
+                                          ("www-authenticate", "")];
+
+/// The Huffman code of every symbol as code and number of bits, where symbol 256 is the end of
+/// string marker
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28), (0xfffffe5, 28),
+    (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28),
+    (0xfffffef, 28), (0xffffff0, 28), (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11), (0x3fa, 10), (0x3fb, 10),
+    (0xf9, 8), (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6), (0x1a, 6), (0x1b, 6),
+    (0x1c, 6), (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13), (0x21, 6),
+    (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7),
+    (0x69, 7), (0x6a, 7), (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5),
+    (0x25, 6), (0x26, 6), (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7),
+    (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14),
+    (0x1ffd, 13), (0xffffffc, 28), (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23),
+    (0x7fffdb, 23), (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24), (0x7fffe1, 23),
+    (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24), (0x3fffda, 22), (0x1fffdd, 21),
+    (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22),
+    (0x7fffeb, 23), (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20), (0x3fffe2, 22),
+    (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19), (0x3fffe7, 22), (0x7ffff2, 23),
+    (0x3fffe8, 22), (0x1ffffec, 25), (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21),
+    (0x3ffffe6, 26), (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28), (0x7ffffe3, 27),
+    (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23), (0x3fffea, 22), (0x3fffeb, 22),
+    (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27),
+    (0x7ffffe9, 27), (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26), (0x3fffffff, 30),
+];
+
+#[derive(Debug)]
+/// A decoder for the header blocks of one direction
+pub struct HpackDecoder {
+    /// The upper limit for size updates of the dynamic table. The limit announced by the
+    /// receiver is not always visible to an observer, so this should be generous.
+    pub max_size: usize,
+
+    /// The current maximum size of the dynamic table
+    size_limit: usize,
+
+    /// The size of all entries within the dynamic table
+    size: usize,
+
+    /// The dynamic table with the newest entry first
+    table: VecDeque<(String, String)>,
+
+    /// Lookup of the symbols by their number of bits and code
+    huffman: HashMap<(u8, u32), u16>,
+}
+
+impl Default for HpackDecoder {
+    fn default() -> Self {
+        HpackDecoder::new(4096)
+    }
+}
+
+impl HpackDecoder {
+    /// Create a new `HpackDecoder` with the initial size of the dynamic table
+    pub fn new(size: usize) -> Self {
+        HpackDecoder {
+            max_size: ::std::cmp::max(size, 64 * 1024),
+            size_limit: size,
+            size: 0,
+            table: VecDeque::new(),
+            huffman: HUFFMAN_CODES.iter()
+                .enumerate()
+                .map(|(symbol, &(code, bits))| ((bits, code), symbol as u16))
+                .collect(),
+        }
+    }
+
+    /// The number of entries within the dynamic table
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns true if the dynamic table is empty
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// The size of the dynamic table as defined by HPACK
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Decode a complete header block and update the dynamic table. Returns `None` if the block
+    /// is malformed, the dynamic table should not be used afterwards.
+    pub fn decode(&mut self, mut input: &[u8]) -> Option<HttpHeaders> {
+        let mut headers = HttpHeaders::default();
+        while let Some(&first) = input.first() {
+            if first & 0x80 != 0 {
+                // Indexed header field
+                let (rest, index) = integer(input, 7)?;
+                let (name, value) = self.entry(index)?;
+                headers.insert(&name, &value);
+                input = rest;
+            } else if first & 0xe0 == 0x20 {
+                // Dynamic table size update
+                let (rest, size) = integer(input, 5)?;
+                if size > self.max_size {
+                    return None;
+                }
+                self.size_limit = size;
+                self.evict(0);
+                input = rest;
+            } else {
+                // Literal header field with incremental indexing, without indexing or never indexed
+                let indexing = first & 0x40 != 0;
+                let (rest, index) = integer(input, if indexing { 6 } else { 4 })?;
+                let (rest, name) = if index == 0 {
+                    self.string(rest)?
+                } else {
+                    (rest, self.entry(index)?.0)
+                };
+                let (rest, value) = self.string(rest)?;
+                headers.insert(&name, &value);
+                if indexing {
+                    self.add(name, value);
+                }
+                input = rest;
+            }
+        }
+        Some(headers)
+    }
+
+    /// Get an entry of the static or dynamic table
+    fn entry(&self, index: usize) -> Option<(String, String)> {
+        match index {
+            0 => None,
+            _ if index <= STATIC_TABLE.len() => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Some((name.to_owned(), value.to_owned()))
+            }
+            _ => self.table.get(index - STATIC_TABLE.len() - 1).cloned(),
+        }
+    }
+
+    /// Add an entry to the dynamic table, where entries larger than the table empty it
+    fn add(&mut self, name: String, value: String) {
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        self.evict(size);
+        if size <= self.size_limit {
+            self.size += size;
+            self.table.push_front((name, value));
+        }
+    }
+
+    /// Remove the oldest entries until the given size fits into the table
+    fn evict(&mut self, needed: usize) {
+        while self.size + needed > self.size_limit {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+
+    /// Decode a string literal, which might be Huffman encoded
+    fn string<'a>(&self, input: &'a [u8]) -> Option<(&'a [u8], String)> {
+        let huffman = input.first()? & 0x80 != 0;
+        let (rest, length) = integer(input, 7)?;
+        if rest.len() < length {
+            return None;
+        }
+        let data = if huffman {
+            self.huffman_decode(&rest[..length])?
+        } else {
+            rest[..length].to_vec()
+        };
+        Some((&rest[length..], String::from_utf8_lossy(&data).into_owned()))
+    }
+
+    /// Decode Huffman encoded data, where the padding has to consist of up to seven one bits
+    fn huffman_decode(&self, input: &[u8]) -> Option<Vec<u8>> {
+        let mut output = vec![];
+        let (mut code, mut bits) = (0u32, 0u8);
+        for byte in input {
+            for shift in (0..8).rev() {
+                code = code << 1 | u32::from(byte >> shift & 1);
+                bits += 1;
+                match self.huffman.get(&(bits, code)) {
+                    Some(&256) => return None,
+                    Some(&symbol) => {
+                        output.push(symbol as u8);
+                        code = 0;
+                        bits = 0;
+                    }
+                    None if bits >= 30 => return None,
+                    None => {}
+                }
+            }
+        }
+        if bits > 7 || code != (1 << bits) - 1 {
+            return None;
+        }
+        Some(output)
+    }
+}
+
+/// Decode an integer with a prefix of the given number of bits
+fn integer(input: &[u8], prefix: u8) -> Option<(&[u8], usize)> {
+    let mask = (1u8 << prefix) - 1;
+    let mut value = usize::from(input.first()? & mask);
+    if value < usize::from(mask) {
+        return Some((&input[1..], value));
+    }
+    for (i, byte) in input[1..].iter().enumerate() {
+        if i > 3 {
+            return None;
+        }
+        value += usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((&input[i + 2..], value));
+        }
+    }
+    None
+}
@@ -0,0 +1,362 @@
+//! Hypertext Transfer Protocol version 1 related packets
+//!
+//! The parser detects messages by their start line, independently from the used ports. Messages
+//! are only returned as a whole including their body, if parts are missing the parser returns
+//! `Incomplete`, which allows resuming the traversal as soon as more data is available.
+use std::str;
+
+use nom::{IResult, ErrorKind, Needed};
+
+use packet::prelude::*;
+
+/// The maximum size of the start line and headers
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// The HTTP/1.x parser
+#[derive(Debug)]
+pub struct HttpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an HTTP/1.x message
+pub struct HttpPacket {
+    /// The request or status line
+    pub start: HttpStart,
+
+    /// The headers
+    pub headers: HttpHeaders,
+
+    /// The body with the transfer encoding removed
+    pub body: Vec<u8>,
+
+    /// The trailers of a chunked body
+    pub trailers: HttpHeaders,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The supported protocol versions
+pub enum HttpVersion {
+    /// HTTP/1.0
+    Http10,
+
+    /// HTTP/1.1
+    Http11,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The first line of a message
+pub enum HttpStart {
+    /// A request line
+    Request {
+        /// The method
+        method: String,
+
+        /// The request target
+        target: String,
+
+        /// The protocol version
+        version: HttpVersion,
+    },
+
+    /// A status line
+    Response {
+        /// The protocol version
+        version: HttpVersion,
+
+        /// The status code
+        status: u16,
+
+        /// The reason phrase
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+/// The header fields of a message in their original order, where the lookup of names is case
+/// insensitive
+pub struct HttpHeaders {
+    entries: Vec<(String, String)>,
+}
+
+impl HttpHeaders {
+    /// Get the value of the first field with the given name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).into_iter().next()
+    }
+
+    /// Get the values of all fields with the given name
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// Returns true if a field with the given name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Iterate over all fields
+    pub fn iter<'a>(&'a self) -> ::std::slice::Iter<'a, (String, String)> {
+        self.entries.iter()
+    }
+
+    /// The number of fields
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no fields exist
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Append a field
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.entries.push((name.to_owned(), value.to_owned()));
+    }
+
+    /// The length of the body if given by the `Content-Length` field
+    pub fn content_length(&self) -> Option<usize> {
+        self.get("Content-Length").and_then(|v| v.trim().parse().ok())
+    }
+
+    /// Returns true if the body uses the chunked transfer encoding
+    pub fn is_chunked(&self) -> bool {
+        self.get_all("Transfer-Encoding")
+            .iter()
+            .flat_map(|v| v.split(','))
+            .last()
+            .map_or(false, |v| v.trim().eq_ignore_ascii_case("chunked"))
+    }
+
+    /// The value of the `Host` field
+    pub fn host(&self) -> Option<&str> {
+        self.get("Host")
+    }
+
+    /// The value of the `Content-Type` field
+    pub fn content_type(&self) -> Option<&str> {
+        self.get("Content-Type")
+    }
+
+    /// Returns true if the connection will be closed after the message
+    pub fn connection_close(&self) -> bool {
+        self.get_all("Connection")
+            .iter()
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().eq_ignore_ascii_case("close"))
+    }
+}
+
+impl Parsable<PacketData> for HttpParser {
+    /// Parse an HTTP/1.x message from a byte slice. The left input will be the data following
+    /// the message. Responses without a length are delimited by the end of the stream.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or carried by TCP
+        if input.is_empty() ||
+           result.map_or(false, |r| !r.is_empty()) && last_result::<TcpPacket>(result).is_none() &&
+           tcp_stream(result).is_none() {
+            return reject(input);
+        }
+
+        // Parse the start line and the headers
+        let (rest, start) = try_parse!(input, start_line);
+        let (rest, headers) = try_parse!(rest, header_fields);
+
+        // Determine the length of the body
+        let (rest, body, trailers) = match start {
+            HttpStart::Response { status, .. } if status < 200 || status == 204 || status == 304 => {
+                (rest, vec![], HttpHeaders::default())
+            }
+            _ if headers.is_chunked() => {
+                let (rest, (body, trailers)) = try_parse!(rest, chunked_body);
+                (rest, body, trailers)
+            }
+            _ if headers.contains("Content-Length") => {
+                let length = match headers.content_length() {
+                    Some(length) => length,
+                    None => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+                };
+                if rest.len() < length {
+                    return match (input.len() - rest.len()).checked_add(length) {
+                        Some(size) => IResult::Incomplete(Needed::Size(size)),
+                        None => IResult::Error(error_position!(ErrorKind::Verify, input)),
+                    };
+                }
+                (&rest[length..], rest[..length].to_vec(), HttpHeaders::default())
+            }
+            HttpStart::Request { .. } => (rest, vec![], HttpHeaders::default()),
+
+            // The body of the response ends with the connection
+            HttpStart::Response { .. } => {
+                if tcp_stream(result).map_or(false, |s| !s.finished) {
+                    return IResult::Incomplete(Needed::Unknown);
+                }
+                (&rest[rest.len()..], rest.to_vec(), HttpHeaders::default())
+            }
+        };
+
+        IResult::Done(rest,
+                      Box::new(HttpPacket {
+                          start: start,
+                          headers: headers,
+                          body: body,
+                          trailers: trailers,
+                      }))
+    }
+}
+
+/// Take a line terminated by CRLF or LF, returning it without the terminator
+pub(crate) fn line(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    match input.iter().position(|b| *b == b'\n') {
+        Some(end) => {
+            let line = &input[..end];
+            let line = if line.last() == Some(&b'\r') {
+                &line[..end - 1]
+            } else {
+                line
+            };
+            IResult::Done(&input[end + 1..], line)
+        }
+        None if input.len() > MAX_HEADER_SIZE => IResult::Error(error_position!(ErrorKind::Verify, input)),
+        None => IResult::Incomplete(Needed::Unknown),
+    }
+}
+
+/// Returns true if the byte is allowed within a token
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Parse the protocol version
+fn version(input: &[u8]) -> Option<HttpVersion> {
+    match input {
+        b"HTTP/1.0" => Some(HttpVersion::Http10),
+        b"HTTP/1.1" => Some(HttpVersion::Http11),
+        _ => None,
+    }
+}
+
+/// Parse a request or status line
+fn start_line(input: &[u8]) -> IResult<&[u8], HttpStart> {
+    // Reject anything which can not become a start line as early as possible
+    let method_length = input.iter().take_while(|b| b.is_ascii_uppercase() || **b == b'-').count();
+    let status_line = b"HTTP/1.".starts_with(&input[..::std::cmp::min(input.len(), 7)]) ||
+                      input.starts_with(b"HTTP/1.");
+    if !status_line && (method_length == 0 || method_length < input.len() && input[method_length] != b' ') {
+        return IResult::Error(error_position!(ErrorKind::Tag, input));
+    }
+
+    let (rest, line) = try_parse!(input, line);
+    let line = match str::from_utf8(line) {
+        Ok(line) => line,
+        Err(_) => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+    };
+    let mut parts = line.splitn(3, ' ');
+    let start = match (parts.next(), parts.next(), parts.next()) {
+        (Some(v), Some(status), reason) if status_line => {
+            match (version(v.as_bytes()), status.parse()) {
+                (Some(version), Ok(code)) if status.len() == 3 && code >= 100 => {
+                    HttpStart::Response {
+                        version: version,
+                        status: code,
+                        reason: reason.unwrap_or("").to_owned(),
+                    }
+                }
+                _ => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+            }
+        }
+        (Some(method), Some(target), Some(v)) if !target.is_empty() && !target.contains(' ') => {
+            match version(v.as_bytes()) {
+                Some(version) => {
+                    HttpStart::Request {
+                        method: method.to_owned(),
+                        target: target.to_owned(),
+                        version: version,
+                    }
+                }
+                None => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+            }
+        }
+        _ => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+    };
+    IResult::Done(rest, start)
+}
+
+/// Parse header fields until an empty line
+pub(crate) fn header_fields(input: &[u8]) -> IResult<&[u8], HttpHeaders> {
+    let mut headers = HttpHeaders::default();
+    let mut rest = input;
+    loop {
+        let (left, line) = try_parse!(rest, line);
+        rest = left;
+        if line.is_empty() {
+            break;
+        }
+        if input.len() - rest.len() > MAX_HEADER_SIZE {
+            return IResult::Error(error_position!(ErrorKind::Verify, input));
+        }
+
+        // Obsolete line folding continues the previous value
+        if line[0] == b' ' || line[0] == b'\t' {
+            match headers.entries.last_mut() {
+                Some(&mut (_, ref mut value)) => {
+                    value.push(' ');
+                    value.push_str(String::from_utf8_lossy(line).trim());
+                    continue;
+                }
+                None => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+            }
+        }
+
+        let colon = match line.iter().position(|b| *b == b':') {
+            Some(colon) if colon > 0 && line[..colon].iter().all(|b| is_token(*b)) => colon,
+            _ => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+        };
+        let name = String::from_utf8_lossy(&line[..colon]);
+        let value = String::from_utf8_lossy(&line[colon + 1..]);
+        headers.insert(&name, value.trim());
+    }
+    IResult::Done(rest, headers)
+}
+
+/// Parse a chunked body including its trailers
+fn chunked_body(input: &[u8]) -> IResult<&[u8], (Vec<u8>, HttpHeaders)> {
+    let mut body = vec![];
+    let mut rest = input;
+    loop {
+        let (left, size_line) = try_parse!(rest, line);
+        let size = size_line.split(|b| *b == b';').next().unwrap_or(size_line);
+        let size = match str::from_utf8(size).ok().and_then(|s| usize::from_str_radix(s.trim(), 16).ok()) {
+            Some(size) => size,
+            None => return IResult::Error(error_position!(ErrorKind::HexDigit, rest)),
+        };
+        if size == 0 {
+            rest = left;
+            break;
+        }
+
+        // The chunk data is followed by a line break
+        if left.len() < size {
+            return match (input.len() - left.len()).checked_add(size).and_then(|s| s.checked_add(2)) {
+                Some(size) => IResult::Incomplete(Needed::Size(size)),
+                None => IResult::Error(error_position!(ErrorKind::HexDigit, rest)),
+            };
+        }
+        let (data, left) = left.split_at(size);
+        let (left, terminator) = try_parse!(left, line);
+        if !terminator.is_empty() {
+            return IResult::Error(error_position!(ErrorKind::CrLf, rest));
+        }
+        body.extend_from_slice(data);
+        rest = left;
+    }
+    let (rest, trailers) = try_parse!(rest, header_fields);
+    IResult::Done(rest, (body, trailers))
+}
@@ -0,0 +1,550 @@
+//! Hypertext Transfer Protocol version 2 related packets
+//!
+//! Every traversal parses a single frame, where the connection preface is consumed together with
+//! the first frame of the client. Header blocks are decoded via the `Http2Cache` of the shared
+//! `PacketData`, which keeps the HPACK state of every direction. Since this state depends on all
+//! previous blocks, the parser should be fed with ordered data via `Peel::traverse_streams`.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use nom::{IResult, ErrorKind, Needed, be_u8, be_u16, be_u32, be_u64};
+
+use packet::prelude::*;
+
+/// The connection preface sent by the client
+pub const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// The length of the frame header
+const FRAME_HEADER_LENGTH: usize = 9;
+
+/// The maximum size of a header block collected from CONTINUATION frames
+const MAX_HEADER_BLOCK: usize = 64 * 1024;
+
+/// The frame ends the stream
+const FLAG_END_STREAM: u8 = 0x01;
+
+/// The frame acknowledges settings or a ping
+const FLAG_ACK: u8 = 0x01;
+
+/// The frame completes a header block
+const FLAG_END_HEADERS: u8 = 0x04;
+
+/// The payload is padded
+const FLAG_PADDED: u8 = 0x08;
+
+/// The frame contains priority information
+const FLAG_PRIORITY: u8 = 0x20;
+
+/// The HTTP/2 parser
+#[derive(Debug)]
+pub struct Http2Parser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an HTTP/2 frame
+pub struct Http2Frame {
+    /// The frame was preceded by the connection preface
+    pub preface: bool,
+
+    /// The length of the payload
+    pub length: u32,
+
+    /// The type specific flags
+    pub flags: u8,
+
+    /// The stream identifier, zero for the whole connection
+    pub stream_id: u32,
+
+    /// The payload
+    pub payload: Http2Payload,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The frame types
+pub enum Http2Payload {
+    /// Application data without padding
+    Data(Vec<u8>),
+
+    /// Opens a stream
+    Headers {
+        /// The priority of the stream
+        priority: Option<Http2Priority>,
+
+        /// The decoded header block, not available until the block is complete
+        headers: Option<HttpHeaders>,
+    },
+
+    /// Changes the priority of a stream
+    Priority(Http2Priority),
+
+    /// Terminates a stream
+    RstStream {
+        /// The reason
+        error_code: u32,
+    },
+
+    /// Configuration parameters of the sender
+    Settings(Vec<Http2Setting>),
+
+    /// Announces a stream initiated by the server
+    PushPromise {
+        /// The announced stream
+        promised_stream_id: u32,
+
+        /// The decoded header block, not available until the block is complete
+        headers: Option<HttpHeaders>,
+    },
+
+    /// Measures the round trip time or checks the connection
+    Ping(u64),
+
+    /// Shuts the connection down
+    GoAway {
+        /// The last stream which was or might be processed
+        last_stream_id: u32,
+
+        /// The reason
+        error_code: u32,
+
+        /// Additional diagnostic data
+        debug_data: Vec<u8>,
+    },
+
+    /// Increases the flow control window
+    WindowUpdate {
+        /// The number of additional bytes
+        increment: u32,
+    },
+
+    /// Continues a header block
+    Continuation {
+        /// The decoded header block, not available until the block is complete
+        headers: Option<HttpHeaders>,
+    },
+
+    /// Any other frame
+    Unknown {
+        /// The frame type
+        kind: u8,
+
+        /// The raw payload
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The priority of a stream
+pub struct Http2Priority {
+    /// The stream becomes the only dependency of the parent
+    pub exclusive: bool,
+
+    /// The parent stream
+    pub dependency: u32,
+
+    /// The weight minus one
+    pub weight: u8,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The connection settings
+pub enum Http2Setting {
+    /// The maximum size of the dynamic HPACK table of the sender's decoder
+    HeaderTableSize(u32),
+
+    /// Server push is allowed
+    EnablePush(u32),
+
+    /// The maximum number of concurrent streams
+    MaxConcurrentStreams(u32),
+
+    /// The initial flow control window
+    InitialWindowSize(u32),
+
+    /// The largest accepted frame payload
+    MaxFrameSize(u32),
+
+    /// The largest accepted header list
+    MaxHeaderListSize(u32),
+
+    /// Any other setting
+    Unknown {
+        /// The setting identifier
+        id: u16,
+
+        /// The value
+        value: u32,
+    },
+}
+
+impl Http2Frame {
+    /// The frame is the last one of the stream
+    pub fn end_stream(&self) -> bool {
+        match self.payload {
+            Http2Payload::Data(_) |
+            Http2Payload::Headers { .. } => self.flags & FLAG_END_STREAM != 0,
+            _ => false,
+        }
+    }
+
+    /// The frame completes a header block
+    pub fn end_headers(&self) -> bool {
+        match self.payload {
+            Http2Payload::Headers { .. } |
+            Http2Payload::PushPromise { .. } |
+            Http2Payload::Continuation { .. } => self.flags & FLAG_END_HEADERS != 0,
+            _ => false,
+        }
+    }
+
+    /// The frame acknowledges settings or a ping
+    pub fn ack(&self) -> bool {
+        match self.payload {
+            Http2Payload::Settings(_) |
+            Http2Payload::Ping(_) => self.flags & FLAG_ACK != 0,
+            _ => false,
+        }
+    }
+
+    /// The decoded header block if the frame completed one
+    pub fn headers(&self) -> Option<&HttpHeaders> {
+        match self.payload {
+            Http2Payload::Headers { ref headers, .. } |
+            Http2Payload::PushPromise { ref headers, .. } |
+            Http2Payload::Continuation { ref headers } => headers.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// The state of a single direction
+struct Http2Direction {
+    /// The decoder for the header blocks
+    decoder: HpackDecoder,
+
+    /// The stream and data of a header block waiting for continuation
+    pending: Option<(u32, Vec<u8>)>,
+
+    /// The arrival of the last frame
+    last_seen: Instant,
+}
+
+#[derive(Debug)]
+/// The HPACK state of all HTTP/2 connections with a timeout. Every direction is identified by the
+/// key of its TCP stream, where `None` is used if the parser runs without a stream.
+pub struct Http2Cache {
+    /// Idle directions will be dropped after this duration
+    pub timeout: Duration,
+
+    directions: HashMap<Option<StreamKey>, Http2Direction>,
+}
+
+impl Default for Http2Cache {
+    fn default() -> Self {
+        Http2Cache::new(Duration::from_secs(120))
+    }
+}
+
+impl Http2Cache {
+    /// Create a new `Http2Cache`
+    pub fn new(timeout: Duration) -> Self {
+        Http2Cache {
+            timeout: timeout,
+            directions: HashMap::new(),
+        }
+    }
+
+    /// The number of tracked directions
+    pub fn len(&self) -> usize {
+        self.directions.len()
+    }
+
+    /// Returns true if no directions are tracked
+    pub fn is_empty(&self) -> bool {
+        self.directions.is_empty()
+    }
+
+    /// Get the header block decoder of a direction
+    pub fn decoder(&self, key: Option<StreamKey>) -> Option<&HpackDecoder> {
+        self.directions.get(&key).map(|d| &d.decoder)
+    }
+
+    /// Drop all idle directions which exceeded the timeout and return their number
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let timeout = self.timeout;
+        let count = self.directions.len();
+        self.directions.retain(|_, d| now.duration_since(d.last_seen) < timeout);
+        count - self.directions.len()
+    }
+
+    /// Get the state of a direction, which will be created if not existing
+    fn direction(&mut self, key: Option<StreamKey>, now: Instant) -> &mut Http2Direction {
+        self.expire(now);
+        let direction = self.directions.entry(key).or_insert_with(|| {
+            Http2Direction {
+                decoder: HpackDecoder::default(),
+                pending: None,
+                last_seen: now,
+            }
+        });
+        direction.last_seen = now;
+        direction
+    }
+}
+
+impl Parsable<PacketData> for Http2Parser {
+    /// Parse an HTTP/2 frame from a byte slice. The left input will be the data following the
+    /// frame.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or a TCP stream
+        let stream = tcp_stream(result);
+        if input.is_empty() || result.map_or(false, |r| !r.is_empty()) && stream.is_none() {
+            return reject(input);
+        }
+        let key = stream.map(|s| s.key);
+        let mut cache = data.and_then(|d| d.http2.as_mut());
+
+        // Consume the connection preface of the client
+        let (rest, preface) = if input.starts_with(HTTP2_PREFACE) {
+            (&input[HTTP2_PREFACE.len()..], true)
+        } else if HTTP2_PREFACE.starts_with(input) {
+            return IResult::Incomplete(Needed::Size(HTTP2_PREFACE.len() + FRAME_HEADER_LENGTH));
+        } else {
+            (input, false)
+        };
+        let known = cache.as_ref().map_or(false, |c| c.directions.contains_key(&key));
+
+        // Otherwise a direction starts with the settings of the server
+        if rest.len() < FRAME_HEADER_LENGTH {
+            if preface || known {
+                return IResult::Incomplete(Needed::Size(input.len() - rest.len() + FRAME_HEADER_LENGTH));
+            }
+            return reject(input);
+        }
+        let (_, (length, kind, flags, stream_id)) = try_parse!(rest,
+            do_parse!(
+                length_high: be_u8 >>
+                length_low: be_u16 >>
+                kind: be_u8 >>
+                flags: be_u8 >>
+                stream_id: be_u32 >>
+                (u32::from(length_high) << 16 | u32::from(length_low), kind, flags, stream_id & 0x7fff_ffff)
+            )
+        );
+        if !preface && !known && (kind != 4 || stream_id != 0 || flags & !FLAG_ACK != 0 || length % 6 != 0) {
+            return reject(input);
+        }
+        let end = FRAME_HEADER_LENGTH + length as usize;
+        if rest.len() < end {
+            return IResult::Incomplete(Needed::Size(input.len() - rest.len() + end));
+        }
+        let payload = &rest[FRAME_HEADER_LENGTH..end];
+
+        let payload = match frame_payload(payload, kind, flags) {
+            IResult::Done(fragment, payload) => (fragment, payload),
+            IResult::Error(e) => return IResult::Error(e),
+            IResult::Incomplete(_) => return IResult::Error(error_position!(ErrorKind::Complete, payload)),
+        };
+
+        // Decode complete header blocks, where other frames must not interrupt them
+        let payload = match cache.as_mut() {
+            Some(cache) => {
+                let direction = cache.direction(key, Instant::now());
+                match header_block(direction, payload, flags, stream_id) {
+                    Some(payload) => payload,
+                    None => {
+                        debug!("Invalid HTTP/2 header block on stream {}", stream_id);
+                        cache.directions.remove(&key);
+                        return IResult::Error(error_position!(ErrorKind::Verify, input));
+                    }
+                }
+            }
+            None => payload.1,
+        };
+
+        // The state of a closed direction is not needed any more
+        let rest = &rest[end..];
+        if let (Some(cache), Some(stream)) = (cache, stream) {
+            if rest.is_empty() && (stream.finished || stream.reset) {
+                cache.directions.remove(&key);
+            }
+        }
+
+        IResult::Done(rest,
+                      Box::new(Http2Frame {
+                          preface: preface,
+                          length: length,
+                          flags: flags,
+                          stream_id: stream_id,
+                          payload: payload,
+                      }))
+    }
+}
+
+/// Remove the padding of a frame payload
+fn unpadded(input: &[u8], flags: u8) -> IResult<&[u8], &[u8]> {
+    if flags & FLAG_PADDED == 0 {
+        return IResult::Done(input, input);
+    }
+    let (rest, padding) = try_parse!(input, be_u8);
+    if padding as usize > rest.len() {
+        return IResult::Error(error_position!(ErrorKind::Verify, input));
+    }
+    IResult::Done(rest, &rest[..rest.len() - padding as usize])
+}
+
+/// Verify the length of a fixed size payload
+fn exact(input: &[u8], length: usize) -> IResult<&[u8], ()> {
+    if input.len() != length {
+        return IResult::Error(error_position!(ErrorKind::Verify, input));
+    }
+    IResult::Done(input, ())
+}
+
+/// Parse the priority of a stream
+fn priority(input: &[u8]) -> IResult<&[u8], Http2Priority> {
+    do_parse!(input,
+        dependency: be_u32 >>
+        weight: be_u8 >>
+        (Http2Priority {
+            exclusive: dependency & 0x8000_0000 != 0,
+            dependency: dependency & 0x7fff_ffff,
+            weight: weight,
+        })
+    )
+}
+
+/// Parse a single setting
+fn setting(input: &[u8]) -> IResult<&[u8], Http2Setting> {
+    do_parse!(input,
+        id: be_u16 >>
+        value: be_u32 >>
+        (match id {
+            1 => Http2Setting::HeaderTableSize(value),
+            2 => Http2Setting::EnablePush(value),
+            3 => Http2Setting::MaxConcurrentStreams(value),
+            4 => Http2Setting::InitialWindowSize(value),
+            5 => Http2Setting::MaxFrameSize(value),
+            6 => Http2Setting::MaxHeaderListSize(value),
+            _ => Http2Setting::Unknown { id: id, value: value },
+        })
+    )
+}
+
+/// Parse the payload of a frame, where the left input is the header block fragment of frames
+/// carrying one
+fn frame_payload(input: &[u8], kind: u8, flags: u8) -> IResult<&[u8], Http2Payload> {
+    let payload = match kind {
+        0 => Http2Payload::Data(try_parse!(input, apply!(unpadded, flags)).1.to_vec()),
+        1 => {
+            let (_, block) = try_parse!(input, apply!(unpadded, flags));
+            let (block, priority) = if flags & FLAG_PRIORITY != 0 {
+                let (block, priority) = try_parse!(block, priority);
+                (block, Some(priority))
+            } else {
+                (block, None)
+            };
+            return IResult::Done(block,
+                                 Http2Payload::Headers {
+                                     priority: priority,
+                                     headers: None,
+                                 });
+        }
+        2 => {
+            try_parse!(input, apply!(exact, 5));
+            Http2Payload::Priority(try_parse!(input, priority).1)
+        }
+        3 => {
+            try_parse!(input, apply!(exact, 4));
+            Http2Payload::RstStream { error_code: try_parse!(input, be_u32).1 }
+        }
+        4 => {
+            if input.len() % 6 != 0 || flags & FLAG_ACK != 0 && !input.is_empty() {
+                return IResult::Error(error_position!(ErrorKind::Verify, input));
+            }
+            Http2Payload::Settings(try_parse!(input, many0!(complete!(setting))).1)
+        }
+        5 => {
+            let (_, block) = try_parse!(input, apply!(unpadded, flags));
+            let (block, promised_stream_id) = try_parse!(block, be_u32);
+            return IResult::Done(block,
+                                 Http2Payload::PushPromise {
+                                     promised_stream_id: promised_stream_id & 0x7fff_ffff,
+                                     headers: None,
+                                 });
+        }
+        6 => {
+            try_parse!(input, apply!(exact, 8));
+            Http2Payload::Ping(try_parse!(input, be_u64).1)
+        }
+        7 => {
+            let (debug_data, (last_stream_id, error_code)) = try_parse!(input, tuple!(be_u32, be_u32));
+            Http2Payload::GoAway {
+                last_stream_id: last_stream_id & 0x7fff_ffff,
+                error_code: error_code,
+                debug_data: debug_data.to_vec(),
+            }
+        }
+        8 => {
+            try_parse!(input, apply!(exact, 4));
+            Http2Payload::WindowUpdate { increment: try_parse!(input, be_u32).1 & 0x7fff_ffff }
+        }
+        9 => return IResult::Done(input, Http2Payload::Continuation { headers: None }),
+        _ => {
+            Http2Payload::Unknown {
+                kind: kind,
+                data: input.to_vec(),
+            }
+        }
+    };
+    IResult::Done(&input[input.len()..], payload)
+}
+
+/// Collect the fragments of header blocks and decode them when complete. Returns `None` on
+/// protocol violations, oversized blocks or if the block could not be decoded.
+fn header_block(direction: &mut Http2Direction,
+                payload: (&[u8], Http2Payload),
+                flags: u8,
+                stream_id: u32)
+                -> Option<Http2Payload> {
+    let (fragment, payload) = payload;
+    let block = match (direction.pending.take(), &payload) {
+        (None, &Http2Payload::Headers { .. }) |
+        (None, &Http2Payload::PushPromise { .. }) => fragment.to_vec(),
+        (Some((id, mut block)), &Http2Payload::Continuation { .. }) if id == stream_id => {
+            block.extend_from_slice(fragment);
+            block
+        }
+        (None, &Http2Payload::Continuation { .. }) |
+        (Some(_), _) => return None,
+        (None, _) => return Some(payload),
+    };
+
+    if block.len() > MAX_HEADER_BLOCK {
+        warn!("HTTP/2 header block on stream {} exceeds {} bytes", stream_id, MAX_HEADER_BLOCK);
+        return None;
+    }
+    if flags & FLAG_END_HEADERS == 0 {
+        direction.pending = Some((stream_id, block));
+        return Some(payload);
+    }
+    let headers = direction.decoder.decode(&block)?;
+    Some(match payload {
+        Http2Payload::Headers { priority, .. } => {
+            Http2Payload::Headers {
+                priority: priority,
+                headers: Some(headers),
+            }
+        }
+        Http2Payload::PushPromise { promised_stream_id, .. } => {
+            Http2Payload::PushPromise {
+                promised_stream_id: promised_stream_id,
+                headers: Some(headers),
+            }
+        }
+        _ => Http2Payload::Continuation { headers: Some(headers) },
+    })
+}
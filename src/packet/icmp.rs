@@ -0,0 +1,292 @@
+//! Internet Control Message Protocol related packets for IPv4 and IPv6
+//!
+//! Error messages contain the beginning of the datagram which caused the error. The left input of
+//! the parsers will be this datagram, whereas the IP parsers accept it if they are linked as
+//! children of the ICMP parsers.
+use std::net::Ipv4Addr;
+
+use nom::{IResult, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The ICMPv4 parser
+#[derive(Debug)]
+pub struct Icmpv4Parser;
+
+/// The ICMPv6 parser
+#[derive(Debug)]
+pub struct Icmpv6Parser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an ICMPv4 message header
+pub struct Icmpv4Packet {
+    /// The message type specific code
+    pub code: u8,
+
+    /// The checksum over the whole message
+    pub checksum: u16,
+
+    /// Indicates if the checksum is correct
+    pub checksum_valid: bool,
+
+    /// The message
+    pub message: Icmpv4Message,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The ICMPv4 message types
+pub enum Icmpv4Message {
+    /// A reply to an echo request
+    EchoReply {
+        /// The identifier of the request
+        id: u16,
+
+        /// The sequence number of the request
+        sequence: u16,
+    },
+
+    /// The destination could not be reached
+    DestinationUnreachable {
+        /// The MTU of the next hop if fragmentation was needed
+        next_hop_mtu: u16,
+    },
+
+    /// Datagrams should be sent to another router
+    Redirect {
+        /// The router to use
+        gateway: Ipv4Addr,
+    },
+
+    /// An echo request
+    EchoRequest {
+        /// The identifier of the request
+        id: u16,
+
+        /// The sequence number of the request
+        sequence: u16,
+    },
+
+    /// The time to live or reassembly time was exceeded
+    TimeExceeded,
+
+    /// The header of the datagram contained an error
+    ParameterProblem {
+        /// Points to the erroneous byte
+        pointer: u8,
+    },
+
+    /// Any other message
+    Other {
+        /// The message type
+        kind: u8,
+
+        /// The type specific rest of the header
+        rest: u32,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an ICMPv6 message header
+pub struct Icmpv6Packet {
+    /// The message type specific code
+    pub code: u8,
+
+    /// The checksum over the pseudo header and the whole message
+    pub checksum: u16,
+
+    /// Indicates if the checksum is correct, which is only known if the preceding IPv6 header is
+    /// available
+    pub checksum_valid: Option<bool>,
+
+    /// The message
+    pub message: Icmpv6Message,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The ICMPv6 message types
+pub enum Icmpv6Message {
+    /// The destination could not be reached
+    DestinationUnreachable,
+
+    /// The datagram exceeded the MTU of the next link
+    PacketTooBig {
+        /// The MTU of the next link
+        mtu: u32,
+    },
+
+    /// The hop limit or reassembly time was exceeded
+    TimeExceeded,
+
+    /// The header of the datagram contained an error
+    ParameterProblem {
+        /// Points to the erroneous byte
+        pointer: u32,
+    },
+
+    /// An echo request
+    EchoRequest {
+        /// The identifier of the request
+        id: u16,
+
+        /// The sequence number of the request
+        sequence: u16,
+    },
+
+    /// A reply to an echo request
+    EchoReply {
+        /// The identifier of the request
+        id: u16,
+
+        /// The sequence number of the request
+        sequence: u16,
+    },
+
+    /// Any other message
+    Other {
+        /// The message type
+        kind: u8,
+
+        /// The type specific rest of the header
+        rest: u32,
+    },
+}
+
+impl Icmpv4Packet {
+    /// Returns true if the message reports an error and contains the original datagram
+    pub fn is_error(&self) -> bool {
+        matches!(self.message,
+                 Icmpv4Message::DestinationUnreachable { .. } |
+                 Icmpv4Message::Redirect { .. } |
+                 Icmpv4Message::TimeExceeded |
+                 Icmpv4Message::ParameterProblem { .. })
+    }
+}
+
+impl Icmpv6Packet {
+    /// Returns true if the message reports an error and contains the original datagram
+    pub fn is_error(&self) -> bool {
+        matches!(self.message,
+                 Icmpv6Message::DestinationUnreachable |
+                 Icmpv6Message::PacketTooBig { .. } |
+                 Icmpv6Message::TimeExceeded |
+                 Icmpv6Message::ParameterProblem { .. })
+    }
+}
+
+/// Returns true if the results contain an ICMP error, which means that the following results
+/// belong to the original datagram and do not describe the current traffic
+pub fn within_icmp_error(result: Option<&ParserResultVec>) -> bool {
+    result.map_or(false, |r| {
+        r.iter().any(|r| {
+            r.downcast_ref::<Icmpv4Packet>().map_or(false, |p| p.is_error()) ||
+            r.downcast_ref::<Icmpv6Packet>().map_or(false, |p| p.is_error())
+        })
+    })
+}
+
+impl Parsable<PacketData> for Icmpv4Parser {
+    /// Parse an ICMPv4 message from a byte slice. The left input will be the original datagram
+    /// for error messages and the data otherwise.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the network layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ip_protocol(result) != Some(IpProtocol::Icmp) {
+            return reject(input);
+        }
+
+        let (rest, (kind, code, checksum, header)) = try_parse!(input, tuple!(be_u8, be_u8, be_u16, be_u32));
+        let message = match kind {
+            0 => {
+                Icmpv4Message::EchoReply {
+                    id: (header >> 16) as u16,
+                    sequence: header as u16,
+                }
+            }
+            3 => Icmpv4Message::DestinationUnreachable { next_hop_mtu: header as u16 },
+            5 => Icmpv4Message::Redirect { gateway: Ipv4Addr::from(header) },
+            8 => {
+                Icmpv4Message::EchoRequest {
+                    id: (header >> 16) as u16,
+                    sequence: header as u16,
+                }
+            }
+            11 => Icmpv4Message::TimeExceeded,
+            12 => Icmpv4Message::ParameterProblem { pointer: (header >> 24) as u8 },
+            _ => {
+                Icmpv4Message::Other {
+                    kind: kind,
+                    rest: header,
+                }
+            }
+        };
+
+        let packet = Icmpv4Packet {
+            code: code,
+            checksum: checksum,
+            checksum_valid: internet_checksum(input) == 0,
+            message: message,
+        };
+        if !packet.checksum_valid {
+            debug!("Invalid ICMPv4 checksum {:#06x}", packet.checksum);
+        }
+
+        IResult::Done(rest, Box::new(packet))
+    }
+}
+
+impl Parsable<PacketData> for Icmpv6Parser {
+    /// Parse an ICMPv6 message from a byte slice. The left input will be the original datagram
+    /// for error messages and the message body otherwise.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the network layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ip_protocol(result) != Some(IpProtocol::Icmpv6) {
+            return reject(input);
+        }
+
+        let (rest, (kind, code, checksum, header)) = try_parse!(input, tuple!(be_u8, be_u8, be_u16, be_u32));
+        let message = match kind {
+            1 => Icmpv6Message::DestinationUnreachable,
+            2 => Icmpv6Message::PacketTooBig { mtu: header },
+            3 => Icmpv6Message::TimeExceeded,
+            4 => Icmpv6Message::ParameterProblem { pointer: header },
+            128 => {
+                Icmpv6Message::EchoRequest {
+                    id: (header >> 16) as u16,
+                    sequence: header as u16,
+                }
+            }
+            129 => {
+                Icmpv6Message::EchoReply {
+                    id: (header >> 16) as u16,
+                    sequence: header as u16,
+                }
+            }
+            _ => {
+                Icmpv6Message::Other {
+                    kind: kind,
+                    rest: header,
+                }
+            }
+        };
+
+        let packet = Icmpv6Packet {
+            code: code,
+            checksum: checksum,
+            checksum_valid: pseudo_header_sum(result, IpProtocol::Icmpv6, input.len())
+                .map(|sum| fold_checksum(ones_complement_sum(sum, input)) == 0),
+            message: message,
+        };
+        if packet.checksum_valid == Some(false) {
+            debug!("Invalid ICMPv6 checksum {:#06x}", packet.checksum);
+        }
+
+        IResult::Done(rest, Box::new(packet))
+    }
+}
@@ -0,0 +1,176 @@
+//! Definitions shared by the Internet Protocol versions
+use std::net::IpAddr;
+
+use packet::prelude::*;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// The protocol of the payload following an IP header
+pub enum IpProtocol {
+    /// IPv6 Hop-by-Hop options
+    HopByHop,
+
+    /// Internet Control Message Protocol
+    Icmp,
+
+    /// Internet Group Management Protocol
+    Igmp,
+
+    /// IPv4 encapsulation
+    IpInIp,
+
+    /// Transmission Control Protocol
+    Tcp,
+
+    /// User Datagram Protocol
+    Udp,
+
+    /// IPv6 encapsulation
+    Ipv6,
+
+    /// IPv6 routing header
+    Routing,
+
+    /// IPv6 fragment header
+    Fragment,
+
+    /// Generic Routing Encapsulation
+    Gre,
+
+    /// Encapsulating Security Payload
+    Esp,
+
+    /// Authentication Header
+    Ah,
+
+    /// Internet Control Message Protocol for IPv6
+    Icmpv6,
+
+    /// No next header for IPv6
+    NoNextHeader,
+
+    /// IPv6 destination options
+    DestinationOptions,
+
+    /// Stream Control Transmission Protocol
+    Sctp,
+
+    /// Any other protocol
+    Unknown(u8),
+}
+
+impl From<u8> for IpProtocol {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => IpProtocol::HopByHop,
+            1 => IpProtocol::Icmp,
+            2 => IpProtocol::Igmp,
+            4 => IpProtocol::IpInIp,
+            6 => IpProtocol::Tcp,
+            17 => IpProtocol::Udp,
+            41 => IpProtocol::Ipv6,
+            43 => IpProtocol::Routing,
+            44 => IpProtocol::Fragment,
+            47 => IpProtocol::Gre,
+            50 => IpProtocol::Esp,
+            51 => IpProtocol::Ah,
+            58 => IpProtocol::Icmpv6,
+            59 => IpProtocol::NoNextHeader,
+            60 => IpProtocol::DestinationOptions,
+            132 => IpProtocol::Sctp,
+            other => IpProtocol::Unknown(other),
+        }
+    }
+}
+
+impl From<IpProtocol> for u8 {
+    fn from(protocol: IpProtocol) -> Self {
+        match protocol {
+            IpProtocol::HopByHop => 0,
+            IpProtocol::Icmp => 1,
+            IpProtocol::Igmp => 2,
+            IpProtocol::IpInIp => 4,
+            IpProtocol::Tcp => 6,
+            IpProtocol::Udp => 17,
+            IpProtocol::Ipv6 => 41,
+            IpProtocol::Routing => 43,
+            IpProtocol::Fragment => 44,
+            IpProtocol::Gre => 47,
+            IpProtocol::Esp => 50,
+            IpProtocol::Ah => 51,
+            IpProtocol::Icmpv6 => 58,
+            IpProtocol::NoNextHeader => 59,
+            IpProtocol::DestinationOptions => 60,
+            IpProtocol::Sctp => 132,
+            IpProtocol::Unknown(other) => other,
+        }
+    }
+}
+
+/// Return the protocol of the payload announced by the last IP result. Non-first fragments do
+/// not start with the header of the announced protocol, which means that `None` will be returned
+/// for them.
+pub fn next_ip_protocol(result: Option<&ParserResultVec>) -> Option<IpProtocol> {
+    if let Some(ipv4) = last_result::<Ipv4Packet>(result) {
+        if ipv4.fragment_offset == 0 {
+            return Some(ipv4.protocol);
+        }
+    } else if let Some(ipv6) = last_result::<Ipv6Packet>(result) {
+        if ipv6.fragment().map_or(true, |f| f.offset == 0) {
+            return Some(ipv6.protocol);
+        }
+    }
+    None
+}
+
+/// Return the source and destination address of the last IP result
+pub fn ip_addresses(result: Option<&ParserResultVec>) -> Option<(IpAddr, IpAddr)> {
+    match last_result::<Ipv4Packet>(result) {
+        Some(ipv4) => Some((IpAddr::V4(ipv4.src), IpAddr::V4(ipv4.dst))),
+        None => last_result::<Ipv6Packet>(result).map(|ipv6| (IpAddr::V6(ipv6.src), IpAddr::V6(ipv6.dst))),
+    }
+}
+
+/// Calculate the one's complement sum of the pseudo header used by the transport layer checksums,
+/// which is available if the last result is an IP packet
+pub fn pseudo_header_sum(result: Option<&ParserResultVec>, protocol: IpProtocol, length: usize) -> Option<u32> {
+    if let Some(ipv4) = last_result::<Ipv4Packet>(result) {
+        let sum = ones_complement_sum(0, &ipv4.src.octets());
+        let sum = ones_complement_sum(sum, &ipv4.dst.octets());
+        Some(sum + u8::from(protocol) as u32 + length as u32)
+    } else if let Some(ipv6) = last_result::<Ipv6Packet>(result) {
+        let sum = ones_complement_sum(0, &ipv6.src.octets());
+        let sum = ones_complement_sum(sum, &ipv6.dst.octets());
+        Some(sum + (length as u32 >> 16) + (length as u32 & 0xffff) + u8::from(protocol) as u32)
+    } else {
+        None
+    }
+}
+
+/// Calculate the one's complement sum of the data in 16 bit words as used by the internet
+/// checksum, starting with an initial sum
+pub fn ones_complement_sum(initial: u32, data: &[u8]) -> u32 {
+    let mut sum = initial;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            (chunk[0] as u32) << 8 | chunk[1] as u32
+        } else {
+            (chunk[0] as u32) << 8
+        };
+        sum += word;
+    }
+    sum
+}
+
+/// Fold a one's complement sum into the final internet checksum
+pub fn fold_checksum(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Calculate the internet checksum of the data, which is zero if the data contains a valid
+/// checksum
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    fold_checksum(ones_complement_sum(0, data))
+}
@@ -0,0 +1,274 @@
+//! Internet Protocol version 4 related packets
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Instant;
+
+use nom::{IResult, ErrorKind, Needed, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The IPv4 parser
+#[derive(Debug)]
+pub struct Ipv4Parser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an IPv4 packet header
+pub struct Ipv4Packet {
+    /// The header length in bytes
+    pub header_length: u8,
+
+    /// Differentiated services code point
+    pub dscp: u8,
+
+    /// Explicit congestion notification
+    pub ecn: u8,
+
+    /// The length of the whole packet in bytes
+    pub total_length: u16,
+
+    /// The identification for fragment reassembly
+    pub id: u16,
+
+    /// The packet must not be fragmented
+    pub dont_fragment: bool,
+
+    /// Further fragments are following
+    pub more_fragments: bool,
+
+    /// The offset of the fragment payload in bytes
+    pub fragment_offset: u16,
+
+    /// Time to live
+    pub ttl: u8,
+
+    /// The protocol of the payload
+    pub protocol: IpProtocol,
+
+    /// The header checksum
+    pub checksum: u16,
+
+    /// Indicates if the header checksum is correct
+    pub checksum_valid: bool,
+
+    /// The source address
+    pub src: Ipv4Addr,
+
+    /// The destination address
+    pub dst: Ipv4Addr,
+
+    /// The header options
+    pub options: Vec<Ipv4Option>,
+}
+
+impl Ipv4Packet {
+    /// Returns true if the packet is a fragment of a larger datagram
+    pub fn is_fragment(&self) -> bool {
+        self.more_fragments || self.fragment_offset != 0
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An IPv4 header option
+pub enum Ipv4Option {
+    /// End of the option list
+    EndOfList,
+
+    /// Padding between options
+    NoOperation,
+
+    /// Record the route of the packet
+    RecordRoute {
+        /// Points to the next free entry, starting at one
+        pointer: u8,
+
+        /// The recorded addresses
+        route: Vec<Ipv4Addr>,
+    },
+
+    /// Record timestamps along the route
+    Timestamp {
+        /// Points to the next free entry, starting at one
+        pointer: u8,
+
+        /// Number of hops which could not record a timestamp
+        overflow: u8,
+
+        /// Indicates if addresses are recorded as well
+        flag: u8,
+
+        /// The recorded timestamps and addresses
+        entries: Vec<(Option<Ipv4Addr>, u32)>,
+    },
+
+    /// The packet should be examined by every router
+    RouterAlert(u16),
+
+    /// Any other option
+    Unknown {
+        /// The option type
+        kind: u8,
+
+        /// The option data without type and length
+        data: Vec<u8>,
+    },
+}
+
+impl Parsable<PacketData> for Ipv4Parser {
+    /// Parse an IPv4 packet from a byte slice. The left input will be the payload without the
+    /// link layer padding, which can be shorter than announced if the packet was truncated.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the link layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ethertype(result) != Some(EtherType::Ipv4) {
+            return reject(input);
+        }
+
+        let (_, (version, header_length)) = try_parse!(input,
+                                                       bits!(pair!(take_bits!(u8, 4), take_bits!(u8, 4))));
+        let header_length = header_length * 4;
+        if version != 4 || header_length < 20 {
+            return reject(input);
+        }
+        if input.len() < header_length as usize {
+            return IResult::Incomplete(Needed::Size(header_length as usize));
+        }
+
+        let header = &input[..header_length as usize];
+        let (options, packet) = try_parse!(header,
+            do_parse!(
+                take!(1) >>
+                tos: be_u8 >>
+                total_length: verify!(be_u16, |l: u16| l >= header_length as u16) >>
+                id: be_u16 >>
+                fragment: be_u16 >>
+                ttl: be_u8 >>
+                protocol: be_u8 >>
+                checksum: be_u16 >>
+                src: be_u32 >>
+                dst: be_u32 >>
+                (Ipv4Packet {
+                    header_length: header_length,
+                    dscp: tos >> 2,
+                    ecn: tos & 0x03,
+                    total_length: total_length,
+                    id: id,
+                    dont_fragment: fragment & 0x4000 != 0,
+                    more_fragments: fragment & 0x2000 != 0,
+                    fragment_offset: (fragment & 0x1fff) << 3,
+                    ttl: ttl,
+                    protocol: IpProtocol::from(protocol),
+                    checksum: checksum,
+                    checksum_valid: internet_checksum(header) == 0,
+                    src: Ipv4Addr::from(src),
+                    dst: Ipv4Addr::from(dst),
+                    options: vec![],
+                })
+            )
+        );
+
+        let mut packet = packet;
+        packet.options = try_parse!(options, complete!(ipv4_options)).1;
+        if !packet.checksum_valid {
+            debug!("Invalid IPv4 header checksum {:#06x}", packet.checksum);
+        }
+
+        let end = ::std::cmp::min(packet.total_length as usize, input.len());
+        let payload = &input[header_length as usize..end];
+
+        // Collect the fragments for reassembly
+        let fragment = packet.is_fragment() && !within_icmp_error(result);
+        if let (true, Some(cache)) = (fragment, data.and_then(|d| d.fragments.as_mut())) {
+            let key = FragmentKey {
+                src: IpAddr::V4(packet.src),
+                dst: IpAddr::V4(packet.dst),
+                id: packet.id as u32,
+                protocol: packet.protocol,
+            };
+            let first = if packet.fragment_offset == 0 {
+                Some(&input[..header_length as usize])
+            } else {
+                None
+            };
+            cache.insert(key,
+                         packet.fragment_offset as usize,
+                         packet.more_fragments,
+                         first,
+                         payload,
+                         Instant::now());
+        }
+
+        IResult::Done(payload, Box::new(packet))
+    }
+}
+
+/// Parse all IPv4 options
+fn ipv4_options(input: &[u8]) -> IResult<&[u8], Vec<Ipv4Option>> {
+    let mut options = vec![];
+    let mut input = input;
+    while !input.is_empty() {
+        let (rest, option) = try_parse!(input, ipv4_option);
+        input = rest;
+        let end = option == Ipv4Option::EndOfList;
+        options.push(option);
+        if end {
+            break;
+        }
+    }
+    IResult::Done(input, options)
+}
+
+/// Parse a single IPv4 option
+fn ipv4_option(input: &[u8]) -> IResult<&[u8], Ipv4Option> {
+    let (rest, kind) = try_parse!(input, be_u8);
+    match kind {
+        0 => return IResult::Done(rest, Ipv4Option::EndOfList),
+        1 => return IResult::Done(rest, Ipv4Option::NoOperation),
+        _ => {}
+    }
+
+    let (rest, data) = try_parse!(rest,
+                                  length_bytes!(map!(verify!(be_u8, |l: u8| l >= 2), |l: u8| l - 2)));
+    let option = match kind {
+        7 => {
+            let (_, (pointer, route)) = try_parse!(data, pair!(be_u8, many0!(complete!(be_u32))));
+            Ipv4Option::RecordRoute {
+                pointer: pointer,
+                route: route.into_iter().map(Ipv4Addr::from).collect(),
+            }
+        }
+        68 => {
+            let (data, (pointer, flags)) = try_parse!(data, pair!(be_u8, be_u8));
+            let flag = flags & 0x0f;
+            let entries = if flag == 0 {
+                try_parse!(data, many0!(complete!(be_u32))).1.into_iter().map(|t| (None, t)).collect()
+            } else {
+                try_parse!(data, many0!(complete!(pair!(be_u32, be_u32))))
+                    .1
+                    .into_iter()
+                    .map(|(a, t)| (Some(Ipv4Addr::from(a)), t))
+                    .collect()
+            };
+            Ipv4Option::Timestamp {
+                pointer: pointer,
+                overflow: flags >> 4,
+                flag: flag,
+                entries: entries,
+            }
+        }
+        148 => {
+            if data.len() != 2 {
+                return IResult::Error(error_position!(ErrorKind::Verify, input));
+            }
+            Ipv4Option::RouterAlert(try_parse!(data, be_u16).1)
+        }
+        _ => {
+            Ipv4Option::Unknown {
+                kind: kind,
+                data: data.to_vec(),
+            }
+        }
+    };
+    IResult::Done(rest, option)
+}
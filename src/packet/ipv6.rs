@@ -0,0 +1,327 @@
+//! Internet Protocol version 6 related packets
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Instant;
+
+use nom::{IResult, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The IPv6 parser
+#[derive(Debug)]
+pub struct Ipv6Parser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an IPv6 packet header including its extension headers
+pub struct Ipv6Packet {
+    /// The traffic class
+    pub traffic_class: u8,
+
+    /// The flow label
+    pub flow_label: u32,
+
+    /// The length of the payload including the extension headers in bytes
+    pub payload_length: u16,
+
+    /// The protocol following the fixed header
+    pub next_header: IpProtocol,
+
+    /// The hop limit
+    pub hop_limit: u8,
+
+    /// The source address
+    pub src: Ipv6Addr,
+
+    /// The destination address
+    pub dst: Ipv6Addr,
+
+    /// The chain of extension headers
+    pub extensions: Vec<Ipv6Extension>,
+
+    /// The protocol of the payload following the last extension header
+    pub protocol: IpProtocol,
+}
+
+impl Ipv6Packet {
+    /// Get the fragment header if available
+    pub fn fragment(&self) -> Option<&Ipv6Fragment> {
+        self.extensions
+            .iter()
+            .filter_map(|extension| match *extension {
+                Ipv6Extension::Fragment(ref fragment) => Some(fragment),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Returns true if the packet is a fragment of a larger datagram
+    pub fn is_fragment(&self) -> bool {
+        self.fragment().map_or(false, |f| f.more_fragments || f.offset != 0)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An IPv6 extension header
+pub enum Ipv6Extension {
+    /// Options which have to be examined by every node along the path
+    HopByHop(Vec<Ipv6Option>),
+
+    /// A list of intermediate nodes to be visited
+    Routing {
+        /// The variant of the routing header
+        routing_type: u8,
+
+        /// The number of remaining route segments
+        segments_left: u8,
+
+        /// The type specific data
+        data: Vec<u8>,
+    },
+
+    /// Fragmentation information
+    Fragment(Ipv6Fragment),
+
+    /// Options which have to be examined by the destination
+    DestinationOptions(Vec<Ipv6Option>),
+
+    /// IPsec authentication header
+    Authentication {
+        /// The security parameters index
+        spi: u32,
+
+        /// The sequence number
+        sequence: u32,
+
+        /// The integrity check value
+        icv: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The IPv6 fragment extension header
+pub struct Ipv6Fragment {
+    /// The offset of the fragment payload in bytes
+    pub offset: u16,
+
+    /// Further fragments are following
+    pub more_fragments: bool,
+
+    /// The identification for fragment reassembly
+    pub id: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An option of the Hop-by-Hop or destination options header
+pub struct Ipv6Option {
+    /// The option type
+    pub kind: u8,
+
+    /// The option data
+    pub data: Vec<u8>,
+}
+
+impl Parsable<PacketData> for Ipv6Parser {
+    /// Parse an IPv6 packet from a byte slice. The left input will be the payload following the
+    /// extension header chain. Parsing stops at an ESP header since everything after is
+    /// encrypted.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the link layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ethertype(result) != Some(EtherType::Ipv6) {
+            return reject(input);
+        }
+
+        let (rest, mut packet) = try_parse!(input,
+            do_parse!(
+                version: verify!(be_u32, |v: u32| v >> 28 == 6) >>
+                payload_length: be_u16 >>
+                next_header: be_u8 >>
+                hop_limit: be_u8 >>
+                src: take!(16) >>
+                dst: take!(16) >>
+                (Ipv6Packet {
+                    traffic_class: (version >> 20) as u8,
+                    flow_label: version & 0xfffff,
+                    payload_length: payload_length,
+                    next_header: IpProtocol::from(next_header),
+                    hop_limit: hop_limit,
+                    src: ipv6_address(src),
+                    dst: ipv6_address(dst),
+                    extensions: vec![],
+                    protocol: IpProtocol::from(next_header),
+                })
+            )
+        );
+
+        // Strip the link layer padding, a zero payload length indicates a jumbogram
+        let payload = if packet.payload_length == 0 {
+            rest
+        } else {
+            &rest[..::std::cmp::min(packet.payload_length as usize, rest.len())]
+        };
+
+        // Walk along the extension header chain, remembering the position of the next header
+        // field which points to the current extension header
+        let mut payload = payload;
+        let mut position = input.len() - rest.len();
+        let mut next_header_position = 6;
+        let mut fragmentable = None;
+        loop {
+            let (rest, (extension, next_header)) = match packet.protocol {
+                IpProtocol::HopByHop => {
+                    let (rest, (options, next_header)) = try_parse!(payload, options_header);
+                    (rest, (Ipv6Extension::HopByHop(options), next_header))
+                }
+                IpProtocol::DestinationOptions => {
+                    let (rest, (options, next_header)) = try_parse!(payload, options_header);
+                    (rest, (Ipv6Extension::DestinationOptions(options), next_header))
+                }
+                IpProtocol::Routing => try_parse!(payload, routing_header),
+                IpProtocol::Fragment => try_parse!(payload, fragment_header),
+                IpProtocol::Ah => try_parse!(payload, authentication_header),
+                _ => break,
+            };
+            // The data following the fragment header of a non-first fragment is payload only
+            let non_first_fragment = match extension {
+                Ipv6Extension::Fragment(ref fragment) => {
+                    fragmentable = Some((position, next_header_position, next_header, rest));
+                    fragment.offset != 0
+                }
+                _ => false,
+            };
+            next_header_position = position;
+            position += payload.len() - rest.len();
+            payload = rest;
+            packet.extensions.push(extension);
+            packet.protocol = IpProtocol::from(next_header);
+            if non_first_fragment {
+                break;
+            }
+        }
+
+        // Collect the fragments for reassembly
+        let data = if within_icmp_error(result) { None } else { data };
+        if let (Some(fragment), Some((position, next_header_position, next_header, fragment_payload)),
+                Some(cache)) = (packet.fragment(), fragmentable, data.and_then(|d| d.fragments.as_mut())) {
+            let key = FragmentKey {
+                src: IpAddr::V6(packet.src),
+                dst: IpAddr::V6(packet.dst),
+                id: fragment.id,
+                protocol: IpProtocol::from(next_header),
+            };
+
+            // The unfragmentable part must not contain the fragment header any more
+            let mut header = input[..position].to_vec();
+            header[next_header_position] = next_header;
+            let first = if fragment.offset == 0 {
+                Some(header.as_slice())
+            } else {
+                None
+            };
+            cache.insert(key,
+                         fragment.offset as usize,
+                         fragment.more_fragments,
+                         first,
+                         fragment_payload,
+                         Instant::now());
+        }
+
+        IResult::Done(payload, Box::new(packet))
+    }
+}
+
+/// Convert a sixteen byte slice into an `Ipv6Addr`
+pub fn ipv6_address(input: &[u8]) -> Ipv6Addr {
+    let mut address = [0; 16];
+    address.copy_from_slice(&input[..16]);
+    Ipv6Addr::from(address)
+}
+
+/// Parse the generic part of an extension header, returning the next header and the data
+/// following the first two bytes
+fn extension_header(input: &[u8]) -> IResult<&[u8], (u8, &[u8])> {
+    do_parse!(input,
+        next_header: be_u8 >>
+        length: be_u8 >>
+        data: take!(length as usize * 8 + 6) >>
+        (next_header, data)
+    )
+}
+
+/// Parse a Hop-by-Hop or destination options header
+fn options_header(input: &[u8]) -> IResult<&[u8], (Vec<Ipv6Option>, u8)> {
+    let (rest, (next_header, mut data)) = try_parse!(input, extension_header);
+    let mut options = vec![];
+    while !data.is_empty() {
+        // Pad1 has neither length nor data
+        if data[0] == 0 {
+            data = &data[1..];
+            options.push(Ipv6Option {
+                kind: 0,
+                data: vec![],
+            });
+            continue;
+        }
+        let (left, option) = try_parse!(data,
+            complete!(do_parse!(
+                kind: be_u8 >>
+                data: length_bytes!(be_u8) >>
+                (Ipv6Option {
+                    kind: kind,
+                    data: data.to_vec(),
+                })
+            ))
+        );
+        data = left;
+        options.push(option);
+    }
+    IResult::Done(rest, (options, next_header))
+}
+
+/// Parse a routing header
+fn routing_header(input: &[u8]) -> IResult<&[u8], (Ipv6Extension, u8)> {
+    let (rest, (next_header, data)) = try_parse!(input, extension_header);
+    IResult::Done(rest,
+                  (Ipv6Extension::Routing {
+                      routing_type: data[0],
+                      segments_left: data[1],
+                      data: data[2..].to_vec(),
+                  },
+                   next_header))
+}
+
+/// Parse a fragment header
+fn fragment_header(input: &[u8]) -> IResult<&[u8], (Ipv6Extension, u8)> {
+    do_parse!(input,
+        next_header: be_u8 >>
+        take!(1) >>
+        offset: be_u16 >>
+        id: be_u32 >>
+        ((Ipv6Extension::Fragment(Ipv6Fragment {
+            offset: offset & 0xfff8,
+            more_fragments: offset & 0x0001 != 0,
+            id: id,
+        }),
+          next_header))
+    )
+}
+
+/// Parse an authentication header, whose length is given in four byte units
+fn authentication_header(input: &[u8]) -> IResult<&[u8], (Ipv6Extension, u8)> {
+    do_parse!(input,
+        next_header: be_u8 >>
+        length: verify!(be_u8, |l: u8| l >= 1) >>
+        take!(2) >>
+        spi: be_u32 >>
+        sequence: be_u32 >>
+        icv: take!((length as usize - 1) * 4) >>
+        ((Ipv6Extension::Authentication {
+            spi: spi,
+            sequence: sequence,
+            icv: icv.to_vec(),
+        },
+          next_header))
+    )
+}
@@ -0,0 +1,305 @@
+//! Packet dissectors for common network protocols
+//!
+//! Every parser checks the results of its predecessors to decide whether the input belongs to it,
+//! for example by the `EtherType` announced by the link layer. This means that the parsers can
+//! be linked together in any tree structure, whereas `packet_peel` provides a complete one.
+//! Tunnels re-enter the tree at the link or network layer parsers via `LinkKind::Reenter` links.
+pub mod ethernet;
+pub mod vlan;
+pub mod mpls;
+pub mod ip;
+pub mod ipv4;
+pub mod ipv6;
+pub mod reassembly;
+pub mod tcp;
+pub mod stream;
+pub mod udp;
+pub mod sctp;
+pub mod icmp;
+pub mod arp;
+pub mod ndp;
+pub mod neighbor;
+pub mod dns;
+pub mod http;
+pub mod hpack;
+pub mod http2;
+pub mod tls;
+pub mod quic;
+pub mod dhcp;
+pub mod sip;
+pub mod rtp;
+pub mod tunnel;
+mod crypto;
+
+use nom::{IResult, ErrorKind};
+
+use self::prelude::*;
+use registry::{ParserRegistry, ParserInfo};
+
+pub mod prelude {
+    //! Sensible defaults for the packet parsers
+    pub use Peel;
+    pub use parser::{Parsable, ParserResult, ParserResultVec};
+    pub use super::{PacketData, packet_peel, packet_registry, last_result, next_ethertype, transport_ports, reject};
+
+    pub use packet::ethernet::*;
+    pub use packet::vlan::*;
+    pub use packet::mpls::*;
+    pub use packet::ip::*;
+    pub use packet::ipv4::*;
+    pub use packet::ipv6::*;
+    pub use packet::reassembly::*;
+    pub use packet::tcp::*;
+    pub use packet::stream::*;
+    pub use packet::udp::*;
+    pub use packet::sctp::*;
+    pub use packet::icmp::*;
+    pub use packet::arp::*;
+    pub use packet::ndp::*;
+    pub use packet::neighbor::*;
+    pub use packet::dns::*;
+    pub use packet::http::*;
+    pub use packet::hpack::*;
+    pub use packet::http2::*;
+    pub use packet::tls::*;
+    pub use packet::quic::*;
+    pub use packet::dhcp::*;
+    pub use packet::sip::*;
+    pub use packet::rtp::*;
+    pub use packet::tunnel::*;
+}
+
+#[derive(Debug, Default)]
+/// Data which will be shared across all packet parsers
+pub struct PacketData {
+    /// Reassembly of IP fragments, disabled if not available
+    pub fragments: Option<FragmentCache>,
+
+    /// Reassembly of TCP streams, disabled if not available
+    pub streams: Option<StreamCache>,
+
+    /// Tracking of IP to hardware address mappings, disabled if not available
+    pub neighbors: Option<NeighborCache>,
+
+    /// Decoding of HTTP/2 header blocks, disabled if not available
+    pub http2: Option<Http2Cache>,
+
+    /// Media ports announced via SIP, RTP and RTCP are not recognized if not available
+    pub rtp: Option<RtpCache>,
+}
+
+/// Return a `Peel` instance for the packet parsers, where the Ethernet parser is the root. All
+/// stateful components of the shared data are enabled.
+pub fn packet_peel() -> Peel<PacketData> {
+    // Create a tree
+    let mut p = Peel::new();
+    p.data = Some(PacketData {
+        fragments: Some(FragmentCache::default()),
+        streams: Some(StreamCache::default()),
+        neighbors: Some(NeighborCache::default()),
+        http2: Some(Http2Cache::default()),
+        rtp: Some(RtpCache::default()),
+    });
+
+    // Create the link layer parsers
+    let ethernet = p.new_root("ethernet", EthernetParser);
+    let vlan = p.new_parser(VlanParser);
+    let mpls = p.new_parser(MplsParser);
+    let arp = p.new_parser(ArpParser);
+
+    // Create the network layer parsers, which can be used as entry points as well
+    let ipv4 = p.new_root("ipv4", Ipv4Parser);
+    let ipv6 = p.new_root("ipv6", Ipv6Parser);
+
+    // Create the transport layer parsers, where the TCP root is the entry point for streams
+    let tcp = p.new_root("tcp", TcpParser);
+    let udp = p.new_parser(UdpParser);
+    let sctp = p.new_parser(SctpParser);
+    let icmpv4 = p.new_parser(Icmpv4Parser);
+    let icmpv6 = p.new_parser(Icmpv6Parser);
+    let ndp = p.new_parser(NdpParser);
+
+    // Create the application layer parsers
+    let dns = p.new_parser(DnsParser);
+    let http = p.new_parser(HttpParser);
+    let http2 = p.new_parser(Http2Parser);
+    let tls = p.new_parser(TlsParser);
+    let quic = p.new_parser(QuicParser);
+    let dhcpv4 = p.new_parser(Dhcpv4Parser);
+    let dhcpv6 = p.new_parser(Dhcpv6Parser);
+    let sip = p.new_parser(SipParser);
+    let rtp = p.new_parser(RtpParser);
+    let rtcp = p.new_parser(RtcpParser);
+
+    // Create the tunnel parsers, whose encapsulated packets re-enter the tree
+    let gre = p.new_parser(GreParser);
+    let vxlan = p.new_parser(VxlanParser);
+    let geneve = p.new_parser(GeneveParser);
+    let ip_in_ip = p.new_parser(IpInIpParser);
+
+    // Link the parsers together
+    p.link_nodes(&[(ethernet, arp),
+                   (ethernet, vlan),
+                   (ethernet, mpls),
+                   (ethernet, ipv4),
+                   (ethernet, ipv6),
+                   (vlan, arp),
+                   (vlan, vlan),
+                   (vlan, mpls),
+                   (vlan, ipv4),
+                   (vlan, ipv6),
+                   (mpls, ipv4),
+                   (mpls, ipv6),
+                   (ipv4, tcp),
+                   (ipv4, udp),
+                   (ipv4, sctp),
+                   (ipv4, icmpv4),
+                   (ipv4, gre),
+                   (ipv4, ip_in_ip),
+                   (ipv6, tcp),
+                   (ipv6, udp),
+                   (ipv6, sctp),
+                   (ipv6, icmpv6),
+                   (ipv6, gre),
+                   (ipv6, ip_in_ip),
+                   (icmpv4, ipv4),
+                   (icmpv6, ndp),
+                   (icmpv6, ipv6),
+                   (tcp, dns),
+                   (tcp, http),
+                   (tcp, http2),
+                   (tcp, tls),
+                   (tcp, sip),
+                   (udp, dns),
+                   (udp, quic),
+                   (udp, dhcpv4),
+                   (udp, dhcpv6),
+                   (udp, vxlan),
+                   (udp, geneve),
+                   (udp, sip),
+                   (udp, rtp),
+                   (udp, rtcp),
+                   (quic, quic)]);
+    for &(left, right) in &[(gre, ethernet),
+                            (gre, mpls),
+                            (gre, ipv4),
+                            (gre, ipv6),
+                            (vxlan, ethernet),
+                            (geneve, ethernet),
+                            (geneve, ipv4),
+                            (geneve, ipv6),
+                            (ip_in_ip, ipv4),
+                            (ip_in_ip, ipv6)] {
+        p.link_reenter(left, right);
+    }
+
+    p
+}
+
+/// Return a `ParserRegistry` containing all packet parsers
+pub fn packet_registry() -> ParserRegistry<PacketData> {
+    let mut r = ParserRegistry::new();
+    r.register(ParserInfo::new("EthernetParser", "1.0.0", "Ethernet II and IEEE 802.3 LLC/SNAP"),
+               |_| Ok(Box::new(EthernetParser)));
+    r.register(ParserInfo::new("VlanParser", "1.0.0", "IEEE 802.1Q and 802.1ad VLAN tags"),
+               |_| Ok(Box::new(VlanParser)));
+    r.register(ParserInfo::new("MplsParser", "1.0.0", "MPLS label stacks"),
+               |_| Ok(Box::new(MplsParser)));
+    r.register(ParserInfo::new("ArpParser", "1.0.0", "ARP for IPv4 over Ethernet"),
+               |_| Ok(Box::new(ArpParser)));
+    r.register(ParserInfo::new("Ipv4Parser", "1.0.0", "IPv4 including options"),
+               |_| Ok(Box::new(Ipv4Parser)));
+    r.register(ParserInfo::new("Ipv6Parser", "1.0.0", "IPv6 including the extension header chain"),
+               |_| Ok(Box::new(Ipv6Parser)));
+    r.register(ParserInfo::new("TcpParser", "1.0.0", "TCP including options"),
+               |_| Ok(Box::new(TcpParser)));
+    r.register(ParserInfo::new("UdpParser", "1.0.0", "UDP including checksum verification"),
+               |_| Ok(Box::new(UdpParser)));
+    r.register(ParserInfo::new("SctpParser", "1.0.0", "SCTP including checksum verification and all chunks"),
+               |_| Ok(Box::new(SctpParser)));
+    r.register(ParserInfo::new("Icmpv4Parser", "1.0.0", "ICMPv4 including error messages"),
+               |_| Ok(Box::new(Icmpv4Parser)));
+    r.register(ParserInfo::new("Icmpv6Parser", "1.0.0", "ICMPv6 including error messages"),
+               |_| Ok(Box::new(Icmpv6Parser)));
+    r.register(ParserInfo::new("NdpParser", "1.0.0", "IPv6 Neighbor Discovery"),
+               |_| Ok(Box::new(NdpParser)));
+    r.register(ParserInfo::new("DnsParser", "1.0.0", "DNS via UDP and TCP"),
+               |_| Ok(Box::new(DnsParser)));
+    r.register(ParserInfo::new("HttpParser", "1.0.0", "HTTP/1.0 and HTTP/1.1 messages"),
+               |_| Ok(Box::new(HttpParser)));
+    r.register(ParserInfo::new("Http2Parser", "1.0.0", "HTTP/2 frames including HPACK header blocks"),
+               |_| Ok(Box::new(Http2Parser)));
+    r.register(ParserInfo::new("TlsParser", "1.0.0", "TLS records and unencrypted handshake messages"),
+               |_| Ok(Box::new(TlsParser)));
+    r.register(ParserInfo::new("QuicParser", "1.0.0", "QUIC version 1 including client Initial decryption"),
+               |_| Ok(Box::new(QuicParser)));
+    r.register(ParserInfo::new("Dhcpv4Parser", "1.0.0", "DHCPv4 including BOOTP header and options"),
+               |_| Ok(Box::new(Dhcpv4Parser)));
+    r.register(ParserInfo::new("Dhcpv6Parser", "1.0.0", "DHCPv6 including nested relay messages"),
+               |_| Ok(Box::new(Dhcpv6Parser)));
+    r.register(ParserInfo::new("SipParser", "1.0.0", "SIP messages including SDP session descriptions"),
+               |_| Ok(Box::new(SipParser)));
+    r.register(ParserInfo::new("RtpParser", "1.0.0", "RTP on media ports announced via SIP"),
+               |_| Ok(Box::new(RtpParser)));
+    r.register(ParserInfo::new("RtcpParser", "1.0.0", "Compound RTCP packets on media ports announced via SIP"),
+               |_| Ok(Box::new(RtcpParser)));
+    r.register(ParserInfo::new("GreParser", "1.0.0", "GRE including checksum, key and sequence number"),
+               |_| Ok(Box::new(GreParser)));
+    r.register(ParserInfo::new("VxlanParser", "1.0.0", "VXLAN carrying Ethernet frames"),
+               |_| Ok(Box::new(VxlanParser)));
+    r.register(ParserInfo::new("GeneveParser", "1.0.0", "Geneve including options"),
+               |_| Ok(Box::new(GeneveParser)));
+    r.register(ParserInfo::new("IpInIpParser", "1.0.0", "IPv4 and IPv6 encapsulated directly by IP"),
+               |_| Ok(Box::new(IpInIpParser)));
+    r
+}
+
+/// Return the last parser result if it is of the given type
+pub fn last_result<T: 'static>(result: Option<&ParserResultVec>) -> Option<&T> {
+    result.and_then(|r| r.last()).and_then(|r| r.downcast_ref::<T>())
+}
+
+/// Return the protocol of the payload announced by the last link layer result. The original
+/// datagram of an ICMP error and the encapsulated packet of a tunnel are announced as well.
+pub fn next_ethertype(result: Option<&ParserResultVec>) -> Option<EtherType> {
+    if let Some(ethernet) = last_result::<EthernetPacket>(result) {
+        ethernet.ethertype
+    } else if let Some(vlan) = last_result::<VlanTag>(result) {
+        Some(vlan.ethertype)
+    } else if let Some(mpls) = last_result::<MplsPacket>(result) {
+        mpls.ethertype
+    } else if let Some(gre) = last_result::<GrePacket>(result) {
+        Some(gre.protocol)
+    } else if last_result::<VxlanPacket>(result).is_some() {
+        Some(EtherType::TransparentEthernetBridging)
+    } else if let Some(geneve) = last_result::<GenevePacket>(result) {
+        Some(geneve.protocol)
+    } else if let Some(tunnel) = last_result::<IpTunnel>(result) {
+        Some(tunnel.ethertype)
+    } else if last_result::<Icmpv4Packet>(result).map_or(false, |icmp| icmp.is_error()) {
+        Some(EtherType::Ipv4)
+    } else if last_result::<Icmpv6Packet>(result).map_or(false, |icmp| icmp.is_error()) {
+        Some(EtherType::Ipv6)
+    } else {
+        None
+    }
+}
+
+/// Return the source and destination port of the last transport layer result, or of the stream
+/// if the traversal was started by `Peel::traverse_streams`
+pub fn transport_ports(result: Option<&ParserResultVec>) -> Option<(u16, u16)> {
+    if let Some(udp) = last_result::<UdpPacket>(result) {
+        Some((udp.src_port, udp.dst_port))
+    } else if let Some(tcp) = last_result::<TcpPacket>(result) {
+        Some((tcp.src_port, tcp.dst_port))
+    } else if let Some(sctp) = last_result::<SctpPacket>(result) {
+        Some((sctp.src_port, sctp.dst_port))
+    } else {
+        last_result::<TcpStream>(result).map(|stream| (stream.key.src_port, stream.key.dst_port))
+    }
+}
+
+/// Reject the input as not belonging to the parser
+pub fn reject<O>(input: &[u8]) -> IResult<&[u8], O> {
+    IResult::Error(error_position!(ErrorKind::Tag, input))
+}
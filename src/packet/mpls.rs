@@ -0,0 +1,79 @@
+//! Multiprotocol Label Switching label stacks
+use nom::{IResult, be_u32};
+
+use packet::prelude::*;
+
+/// The MPLS parser, which parses the complete label stack
+#[derive(Debug)]
+pub struct MplsParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a MPLS label stack
+pub struct MplsPacket {
+    /// The labels from top to bottom of the stack
+    pub labels: Vec<MplsLabel>,
+
+    /// The protocol of the payload guessed by its first nibble, since MPLS does not carry it
+    pub ethertype: Option<EtherType>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a single MPLS label stack entry
+pub struct MplsLabel {
+    /// The label value
+    pub label: u32,
+
+    /// The traffic class
+    pub traffic_class: u8,
+
+    /// Indicates the last entry of the stack
+    pub bottom_of_stack: bool,
+
+    /// The time to live
+    pub ttl: u8,
+}
+
+impl Parsable<PacketData> for MplsParser {
+    /// Parse a MPLS label stack from a byte slice
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        match next_ethertype(result) {
+            Some(EtherType::Mpls) |
+            Some(EtherType::MplsMulticast) => {}
+            _ => return reject(input),
+        }
+
+        let mut labels = vec![];
+        let mut input = input;
+        loop {
+            let (rest, entry) = try_parse!(input, be_u32);
+            input = rest;
+            let label = MplsLabel {
+                label: entry >> 12,
+                traffic_class: ((entry >> 9) & 0x07) as u8,
+                bottom_of_stack: entry & 0x100 != 0,
+                ttl: entry as u8,
+            };
+            let bottom_of_stack = label.bottom_of_stack;
+            labels.push(label);
+            if bottom_of_stack {
+                break;
+            }
+        }
+
+        let ethertype = match input.first().map(|b| b >> 4) {
+            Some(4) => Some(EtherType::Ipv4),
+            Some(6) => Some(EtherType::Ipv6),
+            _ => None,
+        };
+
+        IResult::Done(input,
+                      Box::new(MplsPacket {
+                          labels: labels,
+                          ethertype: ethertype,
+                      }))
+    }
+}
@@ -0,0 +1,288 @@
+//! IPv6 Neighbor Discovery related packets
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Instant;
+
+use nom::{IResult, be_u8, be_u32};
+
+use packet::prelude::*;
+
+/// The Neighbor Discovery parser, which has to be linked as child of the ICMPv6 parser
+#[derive(Debug)]
+pub struct NdpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a Neighbor Discovery message
+pub struct NdpPacket {
+    /// The message
+    pub message: NdpMessage,
+
+    /// The options following the message
+    pub options: Vec<NdpOption>,
+
+    /// The announced address was known with another hardware address before
+    pub conflict: Option<NeighborConflict>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The Neighbor Discovery message types
+pub enum NdpMessage {
+    /// Ask routers to send advertisements
+    RouterSolicitation,
+
+    /// Announce a router and its configuration
+    RouterAdvertisement {
+        /// The hop limit to use for outgoing packets
+        hop_limit: u8,
+
+        /// Addresses are available via DHCPv6
+        managed: bool,
+
+        /// Other configuration is available via DHCPv6
+        other: bool,
+
+        /// The lifetime of the default router in seconds
+        lifetime: u16,
+
+        /// The time a neighbor is considered reachable in milliseconds
+        reachable_time: u32,
+
+        /// The time between retransmitted solicitations in milliseconds
+        retrans_timer: u32,
+    },
+
+    /// Ask for the hardware address of the target
+    NeighborSolicitation {
+        /// The address to resolve
+        target: Ipv6Addr,
+    },
+
+    /// Announce the hardware address of the target
+    NeighborAdvertisement {
+        /// The sender is a router
+        router: bool,
+
+        /// The advertisement answers a solicitation
+        solicited: bool,
+
+        /// The advertisement should override existing cache entries
+        override_entry: bool,
+
+        /// The resolved address
+        target: Ipv6Addr,
+    },
+
+    /// Inform about a better first hop for a destination
+    Redirect {
+        /// The better first hop
+        target: Ipv6Addr,
+
+        /// The redirected destination
+        destination: Ipv6Addr,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A Neighbor Discovery option
+pub enum NdpOption {
+    /// The hardware address of the sender
+    SourceLinkLayerAddress(MacAddress),
+
+    /// The hardware address of the target
+    TargetLinkLayerAddress(MacAddress),
+
+    /// A prefix for on-link determination and address configuration
+    PrefixInformation {
+        /// The number of valid leading bits
+        prefix_length: u8,
+
+        /// The prefix is on-link
+        on_link: bool,
+
+        /// The prefix can be used for stateless address configuration
+        autonomous: bool,
+
+        /// The valid lifetime in seconds
+        valid_lifetime: u32,
+
+        /// The preferred lifetime in seconds
+        preferred_lifetime: u32,
+
+        /// The prefix
+        prefix: Ipv6Addr,
+    },
+
+    /// The beginning of the redirected packet
+    RedirectedHeader(Vec<u8>),
+
+    /// The MTU of the link
+    Mtu(u32),
+
+    /// Any other option
+    Unknown {
+        /// The option type
+        kind: u8,
+
+        /// The option data without type and length
+        data: Vec<u8>,
+    },
+}
+
+impl Parsable<PacketData> for NdpParser {
+    /// Parse a Neighbor Discovery message from the body of an ICMPv6 message. The left input
+    /// will be empty.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // The type specific header is already parsed by the ICMPv6 parser
+        let (kind, header) = match last_result::<Icmpv6Packet>(result).map(|icmp| &icmp.message) {
+            Some(&Icmpv6Message::Other { kind, rest }) if (133..=137).contains(&kind) => (kind, rest),
+            _ => return reject(input),
+        };
+
+        let (options, message) = match kind {
+            133 => (input, NdpMessage::RouterSolicitation),
+            134 => {
+                let (options, (reachable_time, retrans_timer)) = try_parse!(input, pair!(be_u32, be_u32));
+                (options,
+                 NdpMessage::RouterAdvertisement {
+                     hop_limit: (header >> 24) as u8,
+                     managed: header & 0x0080_0000 != 0,
+                     other: header & 0x0040_0000 != 0,
+                     lifetime: header as u16,
+                     reachable_time: reachable_time,
+                     retrans_timer: retrans_timer,
+                 })
+            }
+            135 => {
+                let (options, target) = try_parse!(input, take!(16));
+                (options, NdpMessage::NeighborSolicitation { target: ipv6_address(target) })
+            }
+            136 => {
+                let (options, target) = try_parse!(input, take!(16));
+                (options,
+                 NdpMessage::NeighborAdvertisement {
+                     router: header & 0x8000_0000 != 0,
+                     solicited: header & 0x4000_0000 != 0,
+                     override_entry: header & 0x2000_0000 != 0,
+                     target: ipv6_address(target),
+                 })
+            }
+            _ => {
+                let (options, (target, destination)) = try_parse!(input, pair!(take!(16), take!(16)));
+                (options,
+                 NdpMessage::Redirect {
+                     target: ipv6_address(target),
+                     destination: ipv6_address(destination),
+                 })
+            }
+        };
+
+        let mut packet = NdpPacket {
+            message: message,
+            options: try_parse!(options, ndp_options).1,
+            conflict: None,
+        };
+
+        // Learn the announced mapping
+        let announced = match packet.message {
+            NdpMessage::NeighborAdvertisement { target, .. } => {
+                packet.target_link_layer_address().map(|mac| (target, mac))
+            }
+            NdpMessage::RouterSolicitation |
+            NdpMessage::RouterAdvertisement { .. } |
+            NdpMessage::NeighborSolicitation { .. } => {
+                let src = result.and_then(|r| r.iter().rev().filter_map(|r| r.downcast_ref::<Ipv6Packet>()).next())
+                    .map(|ipv6| ipv6.src);
+                match (src, packet.source_link_layer_address()) {
+                    (Some(src), Some(mac)) if !src.is_unspecified() => Some((src, mac)),
+                    _ => None,
+                }
+            }
+            NdpMessage::Redirect { .. } => None,
+        };
+        let data = if within_icmp_error(result) { None } else { data };
+        if let (Some((ip, mac)), Some(cache)) = (announced, data.and_then(|d| d.neighbors.as_mut())) {
+            packet.conflict = cache.learn(IpAddr::V6(ip), mac, Instant::now());
+        }
+
+        IResult::Done(&input[input.len()..], Box::new(packet))
+    }
+}
+
+impl NdpPacket {
+    /// Get the hardware address of the sender if available
+    pub fn source_link_layer_address(&self) -> Option<MacAddress> {
+        self.options
+            .iter()
+            .filter_map(|option| match *option {
+                NdpOption::SourceLinkLayerAddress(mac) => Some(mac),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Get the hardware address of the target if available
+    pub fn target_link_layer_address(&self) -> Option<MacAddress> {
+        self.options
+            .iter()
+            .filter_map(|option| match *option {
+                NdpOption::TargetLinkLayerAddress(mac) => Some(mac),
+                _ => None,
+            })
+            .next()
+    }
+}
+
+/// Parse all options
+fn ndp_options(input: &[u8]) -> IResult<&[u8], Vec<NdpOption>> {
+    let mut options = vec![];
+    let mut input = input;
+    while !input.is_empty() {
+        let (rest, option) = try_parse!(input, complete!(ndp_option));
+        input = rest;
+        options.push(option);
+    }
+    IResult::Done(input, options)
+}
+
+/// Parse a single option, whose length is given in eight byte units
+fn ndp_option(input: &[u8]) -> IResult<&[u8], NdpOption> {
+    let (rest, (kind, data)) = try_parse!(input,
+        pair!(be_u8, length_bytes!(map!(verify!(be_u8, |l: u8| l >= 1), |l: u8| l as usize * 8 - 2))));
+    let option = match (kind, data.len()) {
+        (1, 6) => NdpOption::SourceLinkLayerAddress(mac(data)),
+        (2, 6) => NdpOption::TargetLinkLayerAddress(mac(data)),
+        (3, 30) => {
+            let (_, option) = try_parse!(data,
+                do_parse!(
+                    prefix_length: be_u8 >>
+                    flags: be_u8 >>
+                    valid_lifetime: be_u32 >>
+                    preferred_lifetime: be_u32 >>
+                    take!(4) >>
+                    prefix: take!(16) >>
+                    (NdpOption::PrefixInformation {
+                        prefix_length: prefix_length,
+                        on_link: flags & 0x80 != 0,
+                        autonomous: flags & 0x40 != 0,
+                        valid_lifetime: valid_lifetime,
+                        preferred_lifetime: preferred_lifetime,
+                        prefix: ipv6_address(prefix),
+                    })
+                )
+            );
+            option
+        }
+        (4, _) => NdpOption::RedirectedHeader(data[6..].to_vec()),
+        (5, 6) => NdpOption::Mtu(try_parse!(&data[2..], be_u32).1),
+        _ => {
+            NdpOption::Unknown {
+                kind: kind,
+                data: data.to_vec(),
+            }
+        }
+    };
+    IResult::Done(rest, option)
+}
@@ -0,0 +1,122 @@
+//! Tracking of the mappings between IP and hardware addresses
+//!
+//! The ARP and NDP parsers store all announced mappings within the `NeighborCache` of the shared
+//! `PacketData` if available. If an address is announced with another hardware address than
+//! before, the parsers report a `NeighborConflict`, which can indicate spoofing.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use packet::prelude::*;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An address which was announced with a different hardware address
+pub struct NeighborConflict {
+    /// The IP address
+    pub ip: IpAddr,
+
+    /// The previously known hardware address
+    pub known: MacAddress,
+
+    /// The newly announced hardware address
+    pub announced: MacAddress,
+}
+
+#[derive(Debug)]
+/// A single mapping
+struct NeighborEntry {
+    mac: MacAddress,
+    last_seen: Instant,
+}
+
+#[derive(Debug)]
+/// A table of the mappings between IP and hardware addresses
+pub struct NeighborCache {
+    /// Mappings which were not announced for this duration may change without a conflict
+    pub timeout: Duration,
+
+    /// The maximum number of known mappings
+    pub max_entries: usize,
+
+    entries: HashMap<IpAddr, NeighborEntry>,
+}
+
+impl Default for NeighborCache {
+    fn default() -> Self {
+        NeighborCache::new(Duration::from_secs(3600), 65536)
+    }
+}
+
+impl NeighborCache {
+    /// Create a new `NeighborCache`
+    pub fn new(timeout: Duration, max_entries: usize) -> Self {
+        NeighborCache {
+            timeout: timeout,
+            max_entries: max_entries,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The number of known mappings
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no mappings are known
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the hardware address of an IP address
+    pub fn lookup(&self, ip: &IpAddr) -> Option<MacAddress> {
+        self.entries.get(ip).map(|e| e.mac)
+    }
+
+    /// Store the announced mapping and return a conflict if the address belonged to another
+    /// hardware address before. The new mapping replaces the old one in any case.
+    pub fn learn(&mut self, ip: IpAddr, mac: MacAddress, now: Instant) -> Option<NeighborConflict> {
+        self.expire(now);
+
+        // Make room for the new mapping
+        if !self.entries.contains_key(&ip) && self.entries.len() >= self.max_entries {
+            let oldest = self.entries.iter().min_by_key(|&(_, e)| e.last_seen).map(|(ip, _)| *ip);
+            if let Some(oldest) = oldest {
+                debug!("Neighbor cache full, dropping {}", oldest);
+                self.entries.remove(&oldest);
+            }
+        }
+
+        let timeout = self.timeout;
+        let previous = self.entries.insert(ip,
+                                           NeighborEntry {
+                                               mac: mac,
+                                               last_seen: now,
+                                           });
+        match previous {
+            Some(ref entry) if entry.mac != mac && now.duration_since(entry.last_seen) < timeout => {
+                warn!("Conflicting announcement of {}: {} was {}", ip, mac, entry.mac);
+                Some(NeighborConflict {
+                    ip: ip,
+                    known: entry.mac,
+                    announced: mac,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop all mappings which exceeded the timeout and return their number
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let timeout = self.timeout;
+        let expired: Vec<IpAddr> = self.entries
+            .iter()
+            .filter(|&(_, e)| now.duration_since(e.last_seen) >= timeout)
+            .map(|(ip, _)| *ip)
+            .collect();
+        for ip in &expired {
+            debug!("Neighbor {} timed out", ip);
+            self.entries.remove(ip);
+        }
+        expired.len()
+    }
+}
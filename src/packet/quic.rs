@@ -0,0 +1,435 @@
+//! QUIC related packets
+//!
+//! The parser reads the long and short headers of QUIC version 1. The protection of Initial
+//! packets sent by the client is removed, since their keys are derived from the destination
+//! connection ID, which exposes the CRYPTO frames carrying the TLS ClientHello. The Initial
+//! packets of the server use the keys of the original connection ID of the client, which are not
+//! known without tracking the connection, so they are not decrypted. Coalesced packets are left
+//! as input for a following QUIC parser.
+use nom::{IResult, ErrorKind, be_u8, be_u32};
+
+use packet::crypto::{Aes128, aes128_gcm_open, hkdf_expand_label, hkdf_extract};
+use packet::prelude::*;
+
+/// The well known QUIC port
+pub const QUIC_PORT: u16 = 443;
+
+/// QUIC version 1
+pub const QUIC_VERSION_1: u32 = 1;
+
+/// The salt for the Initial secrets of version 1
+const INITIAL_SALT: [u8; 20] = [0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8,
+                                0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a];
+
+/// The maximum length of a connection ID
+const MAX_CID_LENGTH: usize = 20;
+
+/// The length of the header protection sample
+const SAMPLE_LENGTH: usize = 16;
+
+/// The QUIC parser
+#[derive(Debug)]
+pub struct QuicParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a QUIC packet
+pub struct QuicPacket {
+    /// The packet type
+    pub kind: QuicPacketType,
+
+    /// The version, zero for version negotiation and short headers
+    pub version: u32,
+
+    /// The destination connection ID, empty for short headers since its length is only known to
+    /// the endpoints
+    pub dcid: Vec<u8>,
+
+    /// The source connection ID
+    pub scid: Vec<u8>,
+
+    /// The address validation token of Initial and Retry packets
+    pub token: Vec<u8>,
+
+    /// The versions offered by a version negotiation
+    pub supported_versions: Vec<u32>,
+
+    /// The packet number if the protection was removed
+    pub packet_number: Option<u64>,
+
+    /// The frames if the protection was removed
+    pub frames: Vec<QuicFrame>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The packet types
+pub enum QuicPacketType {
+    /// The first packets of the handshake
+    Initial,
+
+    /// Early data of the client
+    ZeroRtt,
+
+    /// Packets of the handshake
+    Handshake,
+
+    /// The server requests another Initial with the given token
+    Retry,
+
+    /// The server does not support the requested version
+    VersionNegotiation,
+
+    /// Packets after the handshake
+    Short {
+        /// The latency spin bit
+        spin: bool,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The frames which are allowed within Initial packets
+pub enum QuicFrame {
+    /// A number of padding bytes
+    Padding(usize),
+
+    /// Requests an acknowledgement
+    Ping,
+
+    /// Acknowledges received packets
+    Ack {
+        /// The largest acknowledged packet number
+        largest: u64,
+
+        /// The encoded time since the largest packet was received
+        delay: u64,
+
+        /// The number of acknowledged packets before the largest one
+        first_range: u64,
+    },
+
+    /// Handshake data
+    Crypto {
+        /// The position within the handshake data
+        offset: u64,
+
+        /// The data
+        data: Vec<u8>,
+    },
+
+    /// Closes the connection
+    ConnectionClose {
+        /// The reason
+        error_code: u64,
+
+        /// The frame type causing the error, if reported by the transport
+        frame_type: Option<u64>,
+
+        /// The reason phrase
+        reason: String,
+    },
+}
+
+impl QuicPacket {
+    /// The handshake data from the start of the CRYPTO stream as far as contiguous
+    pub fn crypto_data(&self) -> Vec<u8> {
+        let mut frames: Vec<(u64, &[u8])> = self.frames
+            .iter()
+            .filter_map(|f| match *f {
+                QuicFrame::Crypto { offset, ref data } => Some((offset, data.as_slice())),
+                _ => None,
+            })
+            .collect();
+        frames.sort_by_key(|&(offset, _)| offset);
+
+        let mut data = vec![];
+        for (offset, frame) in frames {
+            if offset > data.len() as u64 {
+                break;
+            }
+            let skip = (data.len() as u64 - offset) as usize;
+            if skip < frame.len() {
+                data.extend_from_slice(&frame[skip..]);
+            }
+        }
+        data
+    }
+
+    /// The client hello if it is completely contained within the handshake data
+    pub fn client_hello(&self) -> Option<TlsClientHello> {
+        match tls_handshakes(&self.crypto_data()) {
+            IResult::Done(_, messages) => {
+                messages.into_iter()
+                    .filter_map(|m| match m {
+                        TlsHandshake::ClientHello(hello) => Some(hello),
+                        _ => None,
+                    })
+                    .next()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Parsable<PacketData> for QuicParser {
+    /// Parse a QUIC packet from a byte slice. The left input will be the following coalesced
+    /// packets.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser, carried by UDP or coalesced with another packet
+        let well_known = transport_ports(result).map_or(false, |(s, d)| s == QUIC_PORT || d == QUIC_PORT);
+        let coalesced = last_result::<QuicPacket>(result).is_some();
+        if input.is_empty() ||
+           result.map_or(false, |r| !r.is_empty()) && last_result::<UdpPacket>(result).is_none() && !coalesced {
+            return reject(input);
+        }
+        let first = input[0];
+
+        // Short headers can only be recognized by the port
+        if first & 0x80 == 0 {
+            if first & 0x40 == 0 || !well_known || coalesced {
+                return reject(input);
+            }
+            let packet = QuicPacket {
+                kind: QuicPacketType::Short { spin: first & 0x20 != 0 },
+                version: 0,
+                dcid: vec![],
+                scid: vec![],
+                token: vec![],
+                supported_versions: vec![],
+                packet_number: None,
+                frames: vec![],
+            };
+            return IResult::Done(&input[input.len()..], Box::new(packet));
+        }
+
+        let (rest, (version, dcid, scid)) = try_parse!(input,
+            do_parse!(
+                be_u8 >>
+                version: be_u32 >>
+                dcid: length_bytes!(be_u8) >>
+                scid: length_bytes!(be_u8) >>
+                (version, dcid, scid)
+            )
+        );
+        if dcid.len() > MAX_CID_LENGTH || scid.len() > MAX_CID_LENGTH ||
+           version != 0 && (version != QUIC_VERSION_1 && !well_known || first & 0x40 == 0) {
+            return reject(input);
+        }
+        let mut packet = QuicPacket {
+            kind: QuicPacketType::VersionNegotiation,
+            version: version,
+            dcid: dcid.to_vec(),
+            scid: scid.to_vec(),
+            token: vec![],
+            supported_versions: vec![],
+            packet_number: None,
+            frames: vec![],
+        };
+
+        // The versions of a negotiation fill the whole datagram
+        if version == 0 {
+            if rest.is_empty() || rest.len() % 4 != 0 {
+                return reject(input);
+            }
+            packet.supported_versions = rest.chunks(4)
+                .map(|v| u32::from(v[0]) << 24 | u32::from(v[1]) << 16 | u32::from(v[2]) << 8 | u32::from(v[3]))
+                .collect();
+            return IResult::Done(&rest[rest.len()..], Box::new(packet));
+        }
+
+        packet.kind = match first >> 4 & 0x03 {
+            0 => QuicPacketType::Initial,
+            1 => QuicPacketType::ZeroRtt,
+            2 => QuicPacketType::Handshake,
+            _ => QuicPacketType::Retry,
+        };
+
+        // A retry contains a token and the integrity tag
+        if packet.kind == QuicPacketType::Retry {
+            if rest.len() < 16 {
+                return reject(input);
+            }
+            packet.token = rest[..rest.len() - 16].to_vec();
+            return IResult::Done(&rest[rest.len()..], Box::new(packet));
+        }
+
+        let rest = if packet.kind == QuicPacketType::Initial {
+            let (rest, token) = try_parse!(rest, length_bytes!(varint));
+            packet.token = token.to_vec();
+            rest
+        } else {
+            rest
+        };
+        let (rest, length) = try_parse!(rest, varint);
+        if rest.len() < length as usize {
+            return reject(input);
+        }
+        let header_length = input.len() - rest.len();
+        let end = header_length + length as usize;
+
+        // Remove the protection of the client's Initial packets
+        if packet.kind == QuicPacketType::Initial && version == QUIC_VERSION_1 && !well_known_server(result) {
+            if let Some((packet_number, payload)) = unprotect(&input[..end], header_length, dcid) {
+                packet.packet_number = Some(packet_number);
+                if let IResult::Done(_, frames) = frames(&payload) {
+                    packet.frames = frames;
+                }
+            }
+        }
+
+        IResult::Done(&input[end..], Box::new(packet))
+    }
+}
+
+/// Returns true if the datagram was sent from the well known port, which means by the server
+fn well_known_server(result: Option<&ParserResultVec>) -> bool {
+    result.map_or(false, |r| {
+        r.iter()
+            .rev()
+            .filter_map(|r| r.downcast_ref::<UdpPacket>())
+            .next()
+            .map_or(false, |udp| udp.src_port == QUIC_PORT && udp.dst_port != QUIC_PORT)
+    })
+}
+
+/// Parse a variable length integer
+pub fn varint(input: &[u8]) -> IResult<&[u8], u64> {
+    let (_, first) = try_parse!(input, be_u8);
+    let length = 1 << (first >> 6);
+    if input.len() < length {
+        return IResult::Incomplete(::nom::Needed::Size(length));
+    }
+    let value = input[1..length].iter().fold(u64::from(first & 0x3f), |v, b| v << 8 | u64::from(*b));
+    IResult::Done(&input[length..], value)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The keys protecting the packets of one endpoint
+pub struct QuicKeys {
+    /// The AEAD key of the packet protection
+    pub key: [u8; 16],
+
+    /// The IV of the packet protection
+    pub iv: [u8; 12],
+
+    /// The key of the header protection
+    pub hp: [u8; 16],
+}
+
+impl QuicKeys {
+    /// Derive the keys of the client's Initial packets from the destination connection ID
+    pub fn client_initial(dcid: &[u8]) -> Self {
+        let initial_secret = hkdf_extract(&INITIAL_SALT, dcid);
+        let secret = hkdf_expand_label(&initial_secret, "client in", 32);
+        let mut keys = QuicKeys {
+            key: [0; 16],
+            iv: [0; 12],
+            hp: [0; 16],
+        };
+        keys.key.copy_from_slice(&hkdf_expand_label(&secret, "quic key", 16));
+        keys.iv.copy_from_slice(&hkdf_expand_label(&secret, "quic iv", 12));
+        keys.hp.copy_from_slice(&hkdf_expand_label(&secret, "quic hp", 16));
+        keys
+    }
+}
+
+/// Remove the header and packet protection of a long header packet, returning the packet number
+/// and the decrypted payload
+fn unprotect(packet: &[u8], header_length: usize, dcid: &[u8]) -> Option<(u64, Vec<u8>)> {
+    // The sample starts four bytes after the packet number
+    let sample_start = header_length + 4;
+    if packet.len() < sample_start + SAMPLE_LENGTH {
+        return None;
+    }
+    let keys = QuicKeys::client_initial(dcid);
+    let mut sample = [0u8; SAMPLE_LENGTH];
+    sample.copy_from_slice(&packet[sample_start..sample_start + SAMPLE_LENGTH]);
+    let mask = Aes128::new(&keys.hp).encrypt(&sample);
+
+    let mut header = packet[..header_length].to_vec();
+    header[0] ^= mask[0] & 0x0f;
+    let packet_number_length = (header[0] & 0x03) as usize + 1;
+    let mut packet_number = 0u64;
+    for i in 0..packet_number_length {
+        let byte = packet[header_length + i] ^ mask[1 + i];
+        header.push(byte);
+        packet_number = packet_number << 8 | u64::from(byte);
+    }
+
+    // The nonce is the IV combined with the packet number
+    let mut nonce = keys.iv;
+    for i in 0..8 {
+        nonce[11 - i] ^= (packet_number >> (8 * i)) as u8;
+    }
+    let payload = aes128_gcm_open(&keys.key, &nonce, &header, &packet[header.len()..]);
+    if payload.is_none() {
+        debug!("Unable to decrypt QUIC Initial packet {}", packet_number);
+    }
+    payload.map(|p| (packet_number, p))
+}
+
+/// Parse all frames of a decrypted Initial packet
+fn frames(input: &[u8]) -> IResult<&[u8], Vec<QuicFrame>> {
+    let mut frames = vec![];
+    let mut input = input;
+    while !input.is_empty() {
+        let (rest, kind) = try_parse!(input, varint);
+        let (rest, frame) = match kind {
+            0x00 => {
+                let padding = rest.iter().take_while(|b| **b == 0).count();
+                (&rest[padding..], QuicFrame::Padding(padding + 1))
+            }
+            0x01 => (rest, QuicFrame::Ping),
+            0x02 | 0x03 => try_parse!(rest, apply!(ack, kind == 0x03)),
+            0x06 => {
+                try_parse!(rest,
+                    do_parse!(
+                        offset: varint >>
+                        data: length_bytes!(varint) >>
+                        (QuicFrame::Crypto {
+                            offset: offset,
+                            data: data.to_vec(),
+                        })
+                    )
+                )
+            }
+            0x1c | 0x1d => {
+                try_parse!(rest,
+                    do_parse!(
+                        error_code: varint >>
+                        frame_type: cond!(kind == 0x1c, varint) >>
+                        reason: length_bytes!(varint) >>
+                        (QuicFrame::ConnectionClose {
+                            error_code: error_code,
+                            frame_type: frame_type,
+                            reason: String::from_utf8_lossy(reason).into_owned(),
+                        })
+                    )
+                )
+            }
+            _ => return IResult::Error(error_position!(ErrorKind::Switch, input)),
+        };
+        frames.push(frame);
+        input = rest;
+    }
+    IResult::Done(input, frames)
+}
+
+/// Parse an acknowledgement, where only the first range is kept
+fn ack(input: &[u8], ecn: bool) -> IResult<&[u8], QuicFrame> {
+    let (mut rest, (largest, delay, count, first_range)) = try_parse!(input, tuple!(varint, varint, varint, varint));
+    for _ in 0..count {
+        rest = try_parse!(rest, tuple!(varint, varint)).0;
+    }
+    if ecn {
+        rest = try_parse!(rest, tuple!(varint, varint, varint)).0;
+    }
+    IResult::Done(rest,
+                  QuicFrame::Ack {
+                      largest: largest,
+                      delay: delay,
+                      first_range: first_range,
+                  })
+}
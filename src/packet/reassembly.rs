@@ -0,0 +1,297 @@
+//! Reassembly of fragmented IP datagrams
+//!
+//! The IPv4 and IPv6 parsers store all fragments within the `FragmentCache` of the shared
+//! `PacketData` if available. Complete datagrams are rebuilt as unfragmented packets, which can
+//! be dissected afterwards via `Peel::traverse_reassembled`. Until then they count into the
+//! memory limit of the cache, which drops the oldest of them if more room is needed.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::btree_map::Entry;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use packet::prelude::*;
+use PeelResult;
+
+/// The largest possible IP datagram without jumbograms
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// Identifies all fragments belonging to the same datagram
+pub struct FragmentKey {
+    /// The source address
+    pub src: IpAddr,
+
+    /// The destination address
+    pub dst: IpAddr,
+
+    /// The identification of the datagram
+    pub id: u32,
+
+    /// The protocol of the datagram payload
+    pub protocol: IpProtocol,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A reassembled datagram
+pub struct Datagram {
+    /// The key of the fragments
+    pub key: FragmentKey,
+
+    /// The complete packet including an IP header without fragmentation information
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+/// The collected fragments of a single datagram
+struct FragmentBuffer {
+    /// The unfragmentable part of the first fragment
+    header: Option<Vec<u8>>,
+
+    /// The fragment payloads by their offsets
+    pieces: BTreeMap<usize, Vec<u8>>,
+
+    /// The length of the payload, known after the last fragment arrived
+    length: Option<usize>,
+
+    /// The number of buffered bytes
+    size: usize,
+
+    /// The arrival of the first fragment
+    created: Instant,
+}
+
+#[derive(Debug)]
+/// A cache for IP fragments with timeout and memory limits
+pub struct FragmentCache {
+    /// Incomplete datagrams will be dropped after this duration
+    pub timeout: Duration,
+
+    /// The maximum number of buffered bytes over all incomplete and reassembled datagrams
+    pub max_bytes: usize,
+
+    /// The maximum number of incomplete datagrams
+    pub max_datagrams: usize,
+
+    buffers: HashMap<FragmentKey, FragmentBuffer>,
+    completed: VecDeque<Datagram>,
+    size: usize,
+}
+
+impl Default for FragmentCache {
+    fn default() -> Self {
+        FragmentCache::new(Duration::from_secs(30), 4 * 1024 * 1024, 1024)
+    }
+}
+
+impl FragmentCache {
+    /// Create a new `FragmentCache`
+    pub fn new(timeout: Duration, max_bytes: usize, max_datagrams: usize) -> Self {
+        FragmentCache {
+            timeout: timeout,
+            max_bytes: max_bytes,
+            max_datagrams: max_datagrams,
+            buffers: HashMap::new(),
+            completed: VecDeque::new(),
+            size: 0,
+        }
+    }
+
+    /// The number of incomplete datagrams
+    pub fn pending(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// The number of currently buffered bytes, including the reassembled datagrams
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Take the next reassembled datagram
+    pub fn pop(&mut self) -> Option<Datagram> {
+        let datagram = self.completed.pop_front();
+        if let Some(ref datagram) = datagram {
+            self.size -= datagram.data.len();
+        }
+        datagram
+    }
+
+    /// Insert a fragment at the given payload offset. The unfragmentable `header` has to be
+    /// provided for the first fragment, where the next header of IPv6 packets already points to
+    /// the fragmented protocol. Returns true if the datagram is complete afterwards.
+    pub fn insert(&mut self,
+                  key: FragmentKey,
+                  offset: usize,
+                  more_fragments: bool,
+                  header: Option<&[u8]>,
+                  payload: &[u8],
+                  now: Instant)
+                  -> bool {
+        self.expire(now);
+
+        let end = offset + payload.len();
+        if end > MAX_DATAGRAM_SIZE {
+            warn!("Dropping oversized fragment of {:?}", key);
+            self.drop_buffer(&key);
+            return false;
+        }
+
+        // Make room for the new fragment, where reassembled datagrams which were not taken yet
+        // are dropped first
+        let new_bytes = payload.len() + header.map_or(0, |h| h.len());
+        while self.size + new_bytes > self.max_bytes {
+            match self.pop() {
+                Some(datagram) => debug!("Fragment cache full, dropping reassembled {:?}", datagram.key),
+                None => break,
+            }
+        }
+        while !self.buffers.contains_key(&key) && self.buffers.len() >= self.max_datagrams ||
+              self.size + new_bytes > self.max_bytes {
+            let oldest = match self.buffers.iter().filter(|&(k, _)| *k != key).min_by_key(|&(_, b)| b.created) {
+                Some((oldest, _)) => *oldest,
+                None => break,
+            };
+            debug!("Fragment cache full, dropping {:?}", oldest);
+            self.drop_buffer(&oldest);
+        }
+        if self.size + new_bytes > self.max_bytes {
+            warn!("Fragment of {:?} exceeds the memory limit", key);
+            self.drop_buffer(&key);
+            return false;
+        }
+
+        // Store the fragment, duplicates are ignored
+        {
+            let buffer = self.buffers.entry(key).or_insert_with(|| {
+                FragmentBuffer {
+                    header: None,
+                    pieces: BTreeMap::new(),
+                    length: None,
+                    size: 0,
+                    created: now,
+                }
+            });
+            if let (None, Some(header)) = (buffer.header.as_ref(), header) {
+                buffer.header = Some(header.to_vec());
+                buffer.size += header.len();
+                self.size += header.len();
+            }
+            if !more_fragments {
+                buffer.length = Some(end);
+            }
+            if let Entry::Vacant(piece) = buffer.pieces.entry(offset) {
+                piece.insert(payload.to_vec());
+                buffer.size += payload.len();
+                self.size += payload.len();
+            }
+        }
+
+        match self.assemble(&key) {
+            Some(datagram) => {
+                self.drop_buffer(&key);
+                debug!("Reassembled datagram {:?} with {} bytes", key, datagram.data.len());
+                self.size += datagram.data.len();
+                self.completed.push_back(datagram);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop all incomplete datagrams which exceeded the timeout and return their number
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let timeout = self.timeout;
+        let expired: Vec<FragmentKey> = self.buffers
+            .iter()
+            .filter(|&(_, b)| now.duration_since(b.created) >= timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &expired {
+            debug!("Fragments of {:?} timed out", key);
+            self.drop_buffer(key);
+        }
+        expired.len()
+    }
+
+    fn drop_buffer(&mut self, key: &FragmentKey) {
+        if let Some(buffer) = self.buffers.remove(key) {
+            self.size -= buffer.size;
+        }
+    }
+
+    /// Rebuild the datagram if all fragments are available
+    fn assemble(&self, key: &FragmentKey) -> Option<Datagram> {
+        let buffer = &self.buffers[key];
+        let (header, length) = match (buffer.header.as_ref(), buffer.length) {
+            (Some(header), Some(length)) => (header, length),
+            _ => return None,
+        };
+
+        // Check for gaps, overlapping data will be taken from the first fragment
+        let mut data = header.clone();
+        let mut position = 0;
+        for (offset, piece) in &buffer.pieces {
+            if *offset > position {
+                return None;
+            }
+            let end = ::std::cmp::min(offset + piece.len(), length);
+            if end > position {
+                data.extend_from_slice(&piece[position - offset..end - offset]);
+                position = end;
+            }
+        }
+        if position < length {
+            return None;
+        }
+
+        // Remove the fragmentation information from the header
+        let header_length = header.len();
+        match key.src {
+            IpAddr::V4(_) => {
+                if header_length < 20 || data.len() > MAX_DATAGRAM_SIZE {
+                    return None;
+                }
+                let total_length = data.len();
+                data[2] = (total_length >> 8) as u8;
+                data[3] = total_length as u8;
+                data[6] &= 0x40;
+                data[7] = 0;
+                data[10] = 0;
+                data[11] = 0;
+                let checksum = internet_checksum(&data[..header_length]);
+                data[10] = (checksum >> 8) as u8;
+                data[11] = checksum as u8;
+            }
+            IpAddr::V6(_) => {
+                if header_length < 40 || data.len() - 40 > MAX_DATAGRAM_SIZE {
+                    return None;
+                }
+                let payload_length = data.len() - 40;
+                data[4] = (payload_length >> 8) as u8;
+                data[5] = payload_length as u8;
+            }
+        }
+
+        Some(Datagram {
+            key: *key,
+            data: data,
+        })
+    }
+}
+
+impl Peel<PacketData> {
+    /// Traverse all reassembled datagrams starting at the roots named `ipv4` or `ipv6` and pass
+    /// the results to the given closure. The tree has to provide these roots, like the one built
+    /// by `packet_peel`, otherwise the closure gets the datagram with a `NoTreeRoot` error.
+    pub fn traverse_reassembled<F>(&mut self, mut f: F)
+        where F: FnMut(&Datagram, PeelResult)
+    {
+        while let Some(datagram) = self.data.as_mut().and_then(|d| d.fragments.as_mut()).and_then(|c| c.pop()) {
+            let root = match datagram.key.src {
+                IpAddr::V4(_) => "ipv4",
+                IpAddr::V6(_) => "ipv6",
+            };
+            let result = self.traverse_root(root, &datagram.data, vec![]);
+            f(&datagram, result);
+        }
+    }
+}
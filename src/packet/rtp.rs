@@ -0,0 +1,548 @@
+//! Real-time Transport Protocol related packets
+//!
+//! RTP and RTCP use dynamic ports, which are negotiated via SDP. The SIP parser stores the
+//! announced media ports within the `RtpCache` of the shared `PacketData` if available, the RTP
+//! and RTCP parsers only accept UDP datagrams from or to these ports. The RTCP parser accepts
+//! multiplexed RTCP on the RTP port as well, which is distinguished by its packet type.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use nom::{IResult, ErrorKind, be_u8, be_u16, be_u32, be_u64};
+
+use packet::prelude::*;
+
+/// The RTP parser
+#[derive(Debug)]
+pub struct RtpParser;
+
+/// The RTCP parser
+#[derive(Debug)]
+pub struct RtcpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an RTP header
+pub struct RtpPacket {
+    /// The marker bit, whose meaning is defined by the profile
+    pub marker: bool,
+
+    /// The format of the payload
+    pub payload_type: u8,
+
+    /// The sequence number
+    pub sequence: u16,
+
+    /// The sampling instant of the first payload byte
+    pub timestamp: u32,
+
+    /// The synchronization source
+    pub ssrc: u32,
+
+    /// The contributing sources
+    pub csrc: Vec<u32>,
+
+    /// The header extension
+    pub extension: Option<RtpExtension>,
+
+    /// The number of padding bytes after the payload
+    pub padding: u8,
+
+    /// The SIP call which announced the media session
+    pub call_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An RTP header extension
+pub struct RtpExtension {
+    /// The profile specific identifier
+    pub profile: u16,
+
+    /// The extension data
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a compound RTCP packet
+pub struct RtcpPacket {
+    /// The contained messages in their original order
+    pub messages: Vec<RtcpMessage>,
+
+    /// The SIP call which announced the media session
+    pub call_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A single RTCP message
+pub enum RtcpMessage {
+    /// Transmission and reception statistics of an active sender
+    SenderReport {
+        /// The synchronization source of the sender
+        ssrc: u32,
+
+        /// The wallclock time in NTP format
+        ntp_timestamp: u64,
+
+        /// The corresponding RTP timestamp
+        rtp_timestamp: u32,
+
+        /// The number of sent packets
+        packet_count: u32,
+
+        /// The number of sent payload bytes
+        octet_count: u32,
+
+        /// The reception statistics
+        reports: Vec<RtcpReportBlock>,
+    },
+
+    /// Reception statistics of a participant which is not sending
+    ReceiverReport {
+        /// The synchronization source of the receiver
+        ssrc: u32,
+
+        /// The reception statistics
+        reports: Vec<RtcpReportBlock>,
+    },
+
+    /// Descriptions of the sources like their canonical names
+    SourceDescription(Vec<RtcpSourceDescription>),
+
+    /// The sources are leaving the session
+    Goodbye {
+        /// The leaving sources
+        sources: Vec<u32>,
+
+        /// The reason for leaving
+        reason: Option<String>,
+    },
+
+    /// Application specific data
+    App {
+        /// The application specific subtype
+        subtype: u8,
+
+        /// The synchronization source
+        ssrc: u32,
+
+        /// The name of the application
+        name: String,
+
+        /// The application data
+        data: Vec<u8>,
+    },
+
+    /// An unsupported message
+    Unknown {
+        /// The packet type
+        kind: u8,
+
+        /// The type specific count field
+        count: u8,
+
+        /// The message data
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The reception statistics of a single source
+pub struct RtcpReportBlock {
+    /// The source the statistics refer to
+    pub ssrc: u32,
+
+    /// The fraction of packets lost since the last report in units of 1/256
+    pub fraction_lost: u8,
+
+    /// The total number of lost packets, negative if duplicates arrived
+    pub cumulative_lost: i32,
+
+    /// The highest received sequence number extended by the number of cycles
+    pub highest_sequence: u32,
+
+    /// The interarrival jitter in timestamp units
+    pub jitter: u32,
+
+    /// The middle bits of the NTP timestamp of the last sender report
+    pub last_sr: u32,
+
+    /// The delay since the last sender report in units of 1/65536 seconds
+    pub delay_since_last_sr: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The description items of a single source
+pub struct RtcpSourceDescription {
+    /// The described source
+    pub ssrc: u32,
+
+    /// The item types and their text
+    pub items: Vec<(u8, String)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A media port announced via SDP
+pub struct RtpExpectation {
+    /// The SIP call which announced the port
+    pub call_id: String,
+
+    /// The media type like `audio` or `video`
+    pub media: String,
+
+    /// The port is used for RTCP
+    pub rtcp: bool,
+}
+
+#[derive(Debug)]
+/// A single expected port
+struct RtpEntry {
+    expectation: RtpExpectation,
+    last_seen: Instant,
+}
+
+#[derive(Debug)]
+/// A table of the media ports announced via SDP
+pub struct RtpCache {
+    /// Ports without any traffic for this duration will be dropped
+    pub timeout: Duration,
+
+    /// The maximum number of expected ports
+    pub max_entries: usize,
+
+    entries: HashMap<(IpAddr, u16), RtpEntry>,
+}
+
+impl Default for RtpCache {
+    fn default() -> Self {
+        RtpCache::new(Duration::from_secs(300), 4096)
+    }
+}
+
+impl RtpCache {
+    /// Create a new `RtpCache`
+    pub fn new(timeout: Duration, max_entries: usize) -> Self {
+        RtpCache {
+            timeout: timeout,
+            max_entries: max_entries,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The number of expected ports
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no ports are expected
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Expect RTP and RTCP at the given address, replacing earlier announcements of the ports.
+    /// Multiplexed RTCP is expected if both ports are equal. If the cache is full, the ports with
+    /// the oldest traffic are dropped.
+    pub fn expect(&mut self, address: IpAddr, rtp_port: u16, rtcp_port: u16, call_id: &str, media: &str, now: Instant) {
+        self.expire(now);
+        for &(port, rtcp) in &[(rtcp_port, true), (rtp_port, false)] {
+            // Make room for the new port
+            if !self.entries.contains_key(&(address, port)) && self.entries.len() >= self.max_entries {
+                let oldest = self.entries
+                    .iter()
+                    .filter(|&(key, _)| *key != (address, rtcp_port))
+                    .min_by_key(|&(_, e)| e.last_seen)
+                    .map(|(key, _)| *key);
+                if let Some((oldest_address, oldest_port)) = oldest {
+                    debug!("RTP cache full, dropping {}:{}", oldest_address, oldest_port);
+                    self.entries.remove(&(oldest_address, oldest_port));
+                }
+            }
+            debug!("Expecting {} {} at {}:{}", media, if rtcp { "RTCP" } else { "RTP" }, address, port);
+            self.entries.insert((address, port),
+                                RtpEntry {
+                                    expectation: RtpExpectation {
+                                        call_id: call_id.to_owned(),
+                                        media: media.to_owned(),
+                                        rtcp: rtcp,
+                                    },
+                                    last_seen: now,
+                                });
+        }
+    }
+
+    /// Get the expectation of a port, which keeps it alive
+    pub fn lookup(&mut self, address: IpAddr, port: u16, now: Instant) -> Option<&RtpExpectation> {
+        let timeout = self.timeout;
+        match self.entries.get_mut(&(address, port)) {
+            Some(entry) if now.duration_since(entry.last_seen) < timeout => {
+                entry.last_seen = now;
+                Some(&entry.expectation)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop all ports of a call and return their number
+    pub fn forget(&mut self, call_id: &str) -> usize {
+        let count = self.entries.len();
+        self.entries.retain(|_, e| e.expectation.call_id != call_id);
+        count - self.entries.len()
+    }
+
+    /// Drop all idle ports which exceeded the timeout and return their number
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let timeout = self.timeout;
+        let count = self.entries.len();
+        self.entries.retain(|_, e| now.duration_since(e.last_seen) < timeout);
+        count - self.entries.len()
+    }
+}
+
+impl Parsable<PacketData> for RtpParser {
+    /// Parse an RTP header from a byte slice. The left input will be the payload without padding.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or sent from or to an expected RTP port
+        let expectation = expected_port(result, data);
+        if result.map_or(false, |r| !r.is_empty()) && expectation.as_ref().map_or(true, |e| e.rtcp) {
+            return reject(input);
+        }
+
+        // Payload types conflicting with RTCP packet types are not allowed
+        let (rest, mut packet) = try_parse!(input,
+            do_parse!(
+                first: verify!(be_u8, |f: u8| f >> 6 == 2) >>
+                second: verify!(be_u8, |s: u8| !(64..=95).contains(&(s & 0x7f))) >>
+                sequence: be_u16 >>
+                timestamp: be_u32 >>
+                ssrc: be_u32 >>
+                csrc: count!(be_u32, (first & 0x0f) as usize) >>
+                extension: cond!(first & 0x10 != 0,
+                    do_parse!(
+                        profile: be_u16 >>
+                        data: length_bytes!(map!(be_u16, |l: u16| l as usize * 4)) >>
+                        (RtpExtension {
+                            profile: profile,
+                            data: data.to_vec(),
+                        })
+                    )
+                ) >>
+                (RtpPacket {
+                    marker: second & 0x80 != 0,
+                    payload_type: second & 0x7f,
+                    sequence: sequence,
+                    timestamp: timestamp,
+                    ssrc: ssrc,
+                    csrc: csrc,
+                    extension: extension,
+                    padding: if first & 0x20 != 0 { 1 } else { 0 },
+                    call_id: expectation.map(|e| e.call_id),
+                })
+            )
+        );
+
+        // The last byte contains the number of padding bytes including itself
+        if packet.padding != 0 {
+            packet.padding = match rest.last() {
+                Some(&padding) if padding != 0 && padding as usize <= rest.len() => padding,
+                _ => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+            };
+        }
+
+        let end = rest.len() - packet.padding as usize;
+        IResult::Done(&rest[..end], Box::new(packet))
+    }
+}
+
+impl Parsable<PacketData> for RtcpParser {
+    /// Parse a compound RTCP packet from a byte slice
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or sent from or to an expected RTP or RTCP port
+        let expectation = expected_port(result, data);
+        if result.map_or(false, |r| !r.is_empty()) && expectation.is_none() {
+            return reject(input);
+        }
+
+        // Every message starts with the version and a packet type within the reserved range
+        let mut messages = vec![];
+        let mut rest = input;
+        while !rest.is_empty() {
+            let (left, (first, kind, body)) = try_parse!(rest,
+                do_parse!(
+                    first: verify!(be_u8, |f: u8| f >> 6 == 2) >>
+                    kind: verify!(be_u8, |k: u8| (192..=223).contains(&k)) >>
+                    body: length_bytes!(map!(be_u16, |l: u16| l as usize * 4)) >>
+                    (first, kind, body)
+                )
+            );
+
+            // Only the last message may be padded
+            let body = if first & 0x20 != 0 {
+                match body.last() {
+                    Some(&padding) if left.is_empty() && padding != 0 && padding as usize <= body.len() => {
+                        &body[..body.len() - padding as usize]
+                    }
+                    _ => return IResult::Error(error_position!(ErrorKind::Verify, rest)),
+                }
+            } else {
+                body
+            };
+
+            let (_, message) = try_parse!(body, apply!(rtcp_message, kind, first & 0x1f));
+            messages.push(message);
+            rest = left;
+        }
+        if messages.is_empty() {
+            return reject(input);
+        }
+
+        IResult::Done(rest,
+                      Box::new(RtcpPacket {
+                          messages: messages,
+                          call_id: expectation.map(|e| e.call_id),
+                      }))
+    }
+}
+
+/// Lookup the ports of the UDP datagram within the `RtpCache`, where the destination is preferred
+fn expected_port(result: Option<&ParserResultVec>, data: Option<&mut PacketData>) -> Option<RtpExpectation> {
+    let (src_port, dst_port) = match last_result::<UdpPacket>(result) {
+        Some(udp) => (udp.src_port, udp.dst_port),
+        None => return None,
+    };
+
+    // The IP header precedes the UDP header
+    let addresses = result.and_then(|r| {
+        r.iter().rev().nth(1).and_then(|ip| match ip.downcast_ref::<Ipv4Packet>() {
+            Some(ipv4) => Some((IpAddr::V4(ipv4.src), IpAddr::V4(ipv4.dst))),
+            None => ip.downcast_ref::<Ipv6Packet>().map(|ipv6| (IpAddr::V6(ipv6.src), IpAddr::V6(ipv6.dst))),
+        })
+    });
+
+    match (addresses, data.and_then(|d| d.rtp.as_mut())) {
+        (Some((src, dst)), Some(cache)) => {
+            let now = Instant::now();
+            if let Some(expectation) = cache.lookup(dst, dst_port, now) {
+                return Some(expectation.clone());
+            }
+            cache.lookup(src, src_port, now).cloned()
+        }
+        _ => None,
+    }
+}
+
+/// Parse the body of an RTCP message with the given packet type and count field
+fn rtcp_message(input: &[u8], kind: u8, count: u8) -> IResult<&[u8], RtcpMessage> {
+    let count = count as usize;
+    match kind {
+        200 => {
+            do_parse!(input,
+                ssrc: be_u32 >>
+                ntp_timestamp: be_u64 >>
+                rtp_timestamp: be_u32 >>
+                packet_count: be_u32 >>
+                octet_count: be_u32 >>
+                reports: count!(rtcp_report_block, count) >>
+                (RtcpMessage::SenderReport {
+                    ssrc: ssrc,
+                    ntp_timestamp: ntp_timestamp,
+                    rtp_timestamp: rtp_timestamp,
+                    packet_count: packet_count,
+                    octet_count: octet_count,
+                    reports: reports,
+                })
+            )
+        }
+        201 => {
+            do_parse!(input,
+                ssrc: be_u32 >>
+                reports: count!(rtcp_report_block, count) >>
+                (RtcpMessage::ReceiverReport {
+                    ssrc: ssrc,
+                    reports: reports,
+                })
+            )
+        }
+        202 => map!(input, count!(rtcp_source_description, count), RtcpMessage::SourceDescription),
+        203 => {
+            do_parse!(input,
+                sources: count!(be_u32, count) >>
+                reason: opt!(complete!(length_bytes!(be_u8))) >>
+                (RtcpMessage::Goodbye {
+                    sources: sources,
+                    reason: reason.map(|r| String::from_utf8_lossy(r).into_owned()),
+                })
+            )
+        }
+        204 => {
+            do_parse!(input,
+                ssrc: be_u32 >>
+                name: take!(4) >>
+                (RtcpMessage::App {
+                    subtype: count as u8,
+                    ssrc: ssrc,
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    data: input[8..].to_vec(),
+                })
+            )
+        }
+        _ => {
+            IResult::Done(&input[input.len()..],
+                          RtcpMessage::Unknown {
+                              kind: kind,
+                              count: count as u8,
+                              data: input.to_vec(),
+                          })
+        }
+    }
+}
+
+/// Parse the reception statistics of a single source
+fn rtcp_report_block(input: &[u8]) -> IResult<&[u8], RtcpReportBlock> {
+    do_parse!(input,
+        ssrc: be_u32 >>
+        lost: be_u32 >>
+        highest_sequence: be_u32 >>
+        jitter: be_u32 >>
+        last_sr: be_u32 >>
+        delay_since_last_sr: be_u32 >>
+        (RtcpReportBlock {
+            ssrc: ssrc,
+            fraction_lost: (lost >> 24) as u8,
+            cumulative_lost: ((lost << 8) as i32) >> 8,
+            highest_sequence: highest_sequence,
+            jitter: jitter,
+            last_sr: last_sr,
+            delay_since_last_sr: delay_since_last_sr,
+        })
+    )
+}
+
+/// Parse the items of a single source, which are terminated by a zero type and padded to the next
+/// multiple of four bytes
+fn rtcp_source_description(input: &[u8]) -> IResult<&[u8], RtcpSourceDescription> {
+    let (mut rest, ssrc) = try_parse!(input, be_u32);
+    let mut items = vec![];
+    loop {
+        let (left, kind) = try_parse!(rest, be_u8);
+        if kind == 0 {
+            rest = left;
+            break;
+        }
+        let (left, text) = try_parse!(left, length_bytes!(be_u8));
+        items.push((kind, String::from_utf8_lossy(text).into_owned()));
+        rest = left;
+    }
+
+    let padding = ::std::cmp::min((4 - (input.len() - rest.len()) % 4) % 4, rest.len());
+    IResult::Done(&rest[padding..],
+                  RtcpSourceDescription {
+                      ssrc: ssrc,
+                      items: items,
+                  })
+}
@@ -0,0 +1,421 @@
+//! Stream Control Transmission Protocol related packets
+//!
+//! All chunks of a packet will be parsed, whereas the left input will be the payload of the first
+//! DATA chunk. Fragmented user messages are not reassembled.
+use nom::{IResult, be_u8, be_u16, be_u32, le_u32};
+
+use packet::prelude::*;
+
+/// The payload protocol identifier of S1AP
+pub const SCTP_PPID_S1AP: u32 = 18;
+
+/// The payload protocol identifier of Diameter
+pub const SCTP_PPID_DIAMETER: u32 = 46;
+
+/// The lookup table of the CRC32c polynomial in reversed representation
+const CRC32C_TABLE: [u32; 256] = [
+    0x00000000, 0xf26b8303, 0xe13b70f7, 0x1350f3f4, 0xc79a971f, 0x35f1141c,
+    0x26a1e7e8, 0xd4ca64eb, 0x8ad958cf, 0x78b2dbcc, 0x6be22838, 0x9989ab3b,
+    0x4d43cfd0, 0xbf284cd3, 0xac78bf27, 0x5e133c24, 0x105ec76f, 0xe235446c,
+    0xf165b798, 0x030e349b, 0xd7c45070, 0x25afd373, 0x36ff2087, 0xc494a384,
+    0x9a879fa0, 0x68ec1ca3, 0x7bbcef57, 0x89d76c54, 0x5d1d08bf, 0xaf768bbc,
+    0xbc267848, 0x4e4dfb4b, 0x20bd8ede, 0xd2d60ddd, 0xc186fe29, 0x33ed7d2a,
+    0xe72719c1, 0x154c9ac2, 0x061c6936, 0xf477ea35, 0xaa64d611, 0x580f5512,
+    0x4b5fa6e6, 0xb93425e5, 0x6dfe410e, 0x9f95c20d, 0x8cc531f9, 0x7eaeb2fa,
+    0x30e349b1, 0xc288cab2, 0xd1d83946, 0x23b3ba45, 0xf779deae, 0x05125dad,
+    0x1642ae59, 0xe4292d5a, 0xba3a117e, 0x4851927d, 0x5b016189, 0xa96ae28a,
+    0x7da08661, 0x8fcb0562, 0x9c9bf696, 0x6ef07595, 0x417b1dbc, 0xb3109ebf,
+    0xa0406d4b, 0x522bee48, 0x86e18aa3, 0x748a09a0, 0x67dafa54, 0x95b17957,
+    0xcba24573, 0x39c9c670, 0x2a993584, 0xd8f2b687, 0x0c38d26c, 0xfe53516f,
+    0xed03a29b, 0x1f682198, 0x5125dad3, 0xa34e59d0, 0xb01eaa24, 0x42752927,
+    0x96bf4dcc, 0x64d4cecf, 0x77843d3b, 0x85efbe38, 0xdbfc821c, 0x2997011f,
+    0x3ac7f2eb, 0xc8ac71e8, 0x1c661503, 0xee0d9600, 0xfd5d65f4, 0x0f36e6f7,
+    0x61c69362, 0x93ad1061, 0x80fde395, 0x72966096, 0xa65c047d, 0x5437877e,
+    0x4767748a, 0xb50cf789, 0xeb1fcbad, 0x197448ae, 0x0a24bb5a, 0xf84f3859,
+    0x2c855cb2, 0xdeeedfb1, 0xcdbe2c45, 0x3fd5af46, 0x7198540d, 0x83f3d70e,
+    0x90a324fa, 0x62c8a7f9, 0xb602c312, 0x44694011, 0x5739b3e5, 0xa55230e6,
+    0xfb410cc2, 0x092a8fc1, 0x1a7a7c35, 0xe811ff36, 0x3cdb9bdd, 0xceb018de,
+    0xdde0eb2a, 0x2f8b6829, 0x82f63b78, 0x709db87b, 0x63cd4b8f, 0x91a6c88c,
+    0x456cac67, 0xb7072f64, 0xa457dc90, 0x563c5f93, 0x082f63b7, 0xfa44e0b4,
+    0xe9141340, 0x1b7f9043, 0xcfb5f4a8, 0x3dde77ab, 0x2e8e845f, 0xdce5075c,
+    0x92a8fc17, 0x60c37f14, 0x73938ce0, 0x81f80fe3, 0x55326b08, 0xa759e80b,
+    0xb4091bff, 0x466298fc, 0x1871a4d8, 0xea1a27db, 0xf94ad42f, 0x0b21572c,
+    0xdfeb33c7, 0x2d80b0c4, 0x3ed04330, 0xccbbc033, 0xa24bb5a6, 0x502036a5,
+    0x4370c551, 0xb11b4652, 0x65d122b9, 0x97baa1ba, 0x84ea524e, 0x7681d14d,
+    0x2892ed69, 0xdaf96e6a, 0xc9a99d9e, 0x3bc21e9d, 0xef087a76, 0x1d63f975,
+    0x0e330a81, 0xfc588982, 0xb21572c9, 0x407ef1ca, 0x532e023e, 0xa145813d,
+    0x758fe5d6, 0x87e466d5, 0x94b49521, 0x66df1622, 0x38cc2a06, 0xcaa7a905,
+    0xd9f75af1, 0x2b9cd9f2, 0xff56bd19, 0x0d3d3e1a, 0x1e6dcdee, 0xec064eed,
+    0xc38d26c4, 0x31e6a5c7, 0x22b65633, 0xd0ddd530, 0x0417b1db, 0xf67c32d8,
+    0xe52cc12c, 0x1747422f, 0x49547e0b, 0xbb3ffd08, 0xa86f0efc, 0x5a048dff,
+    0x8ecee914, 0x7ca56a17, 0x6ff599e3, 0x9d9e1ae0, 0xd3d3e1ab, 0x21b862a8,
+    0x32e8915c, 0xc083125f, 0x144976b4, 0xe622f5b7, 0xf5720643, 0x07198540,
+    0x590ab964, 0xab613a67, 0xb831c993, 0x4a5a4a90, 0x9e902e7b, 0x6cfbad78,
+    0x7fab5e8c, 0x8dc0dd8f, 0xe330a81a, 0x115b2b19, 0x020bd8ed, 0xf0605bee,
+    0x24aa3f05, 0xd6c1bc06, 0xc5914ff2, 0x37faccf1, 0x69e9f0d5, 0x9b8273d6,
+    0x88d28022, 0x7ab90321, 0xae7367ca, 0x5c18e4c9, 0x4f48173d, 0xbd23943e,
+    0xf36e6f75, 0x0105ec76, 0x12551f82, 0xe03e9c81, 0x34f4f86a, 0xc69f7b69,
+    0xd5cf889d, 0x27a40b9e, 0x79b737ba, 0x8bdcb4b9, 0x988c474d, 0x6ae7c44e,
+    0xbe2da0a5, 0x4c4623a6, 0x5f16d052, 0xad7d5351,
+];
+
+/// The SCTP parser
+#[derive(Debug)]
+pub struct SctpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an SCTP packet
+pub struct SctpPacket {
+    /// The source port
+    pub src_port: u16,
+
+    /// The destination port
+    pub dst_port: u16,
+
+    /// The tag identifying the association at the receiver
+    pub verification_tag: u32,
+
+    /// The CRC32c checksum over the whole packet
+    pub checksum: u32,
+
+    /// Indicates if the checksum is correct
+    pub checksum_valid: bool,
+
+    /// All chunks of the packet
+    pub chunks: Vec<SctpChunk>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The chunks of an SCTP packet
+pub enum SctpChunk {
+    /// User data
+    Data(SctpData),
+
+    /// Initiation of an association
+    Init(SctpInit),
+
+    /// Acknowledgement of the initiation
+    InitAck(SctpInit),
+
+    /// Selective acknowledgement of received data
+    Sack {
+        /// The last sequence number received in order
+        cumulative_tsn: u32,
+
+        /// The advertised receiver window credit
+        a_rwnd: u32,
+
+        /// The start and end offsets of received blocks relative to the cumulative TSN
+        gap_blocks: Vec<(u16, u16)>,
+
+        /// The sequence numbers received more than once
+        duplicates: Vec<u32>,
+    },
+
+    /// Probe of the reachability of a destination address
+    Heartbeat(Vec<u8>),
+
+    /// Reply to a heartbeat containing its information
+    HeartbeatAck(Vec<u8>),
+
+    /// Abortion of the association
+    Abort {
+        /// The verification tag of the sender was reflected
+        reflected: bool,
+
+        /// The reasons of the abortion
+        causes: Vec<SctpParameter>,
+    },
+
+    /// Graceful close of the association
+    Shutdown {
+        /// The last sequence number received in order
+        cumulative_tsn: u32,
+    },
+
+    /// Acknowledgement of the shutdown
+    ShutdownAck,
+
+    /// Report of errors which do not abort the association
+    Error(Vec<SctpParameter>),
+
+    /// The state cookie of the initiation acknowledgement
+    CookieEcho(Vec<u8>),
+
+    /// Acknowledgement of the cookie
+    CookieAck,
+
+    /// Completion of the shutdown
+    ShutdownComplete {
+        /// The verification tag of the sender was reflected
+        reflected: bool,
+    },
+
+    /// Any other chunk
+    Unknown {
+        /// The chunk type
+        kind: u8,
+
+        /// The chunk flags
+        flags: u8,
+
+        /// The raw chunk value
+        value: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A DATA chunk
+pub struct SctpData {
+    /// The data may be delivered out of order
+    pub unordered: bool,
+
+    /// The first fragment of a user message
+    pub beginning: bool,
+
+    /// The last fragment of a user message
+    pub ending: bool,
+
+    /// The transmission sequence number
+    pub tsn: u32,
+
+    /// The stream the data belongs to
+    pub stream_id: u16,
+
+    /// The sequence number of the user message within the stream
+    pub stream_sequence: u16,
+
+    /// The payload protocol identifier
+    pub ppid: u32,
+
+    /// The user data
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An INIT or INIT ACK chunk
+pub struct SctpInit {
+    /// The verification tag to use by the receiver
+    pub initiate_tag: u32,
+
+    /// The advertised receiver window credit
+    pub a_rwnd: u32,
+
+    /// The number of outbound streams
+    pub outbound_streams: u16,
+
+    /// The maximum number of inbound streams
+    pub inbound_streams: u16,
+
+    /// The initial transmission sequence number
+    pub initial_tsn: u32,
+
+    /// The optional parameters, like addresses or the state cookie
+    pub parameters: Vec<SctpParameter>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A parameter of an initiation or a cause of an error
+pub struct SctpParameter {
+    /// The type of the parameter or the cause code
+    pub kind: u16,
+
+    /// The raw value
+    pub value: Vec<u8>,
+}
+
+impl SctpPacket {
+    /// Get all DATA chunks
+    pub fn data(&self) -> Vec<&SctpData> {
+        self.chunks
+            .iter()
+            .filter_map(|c| match *c {
+                SctpChunk::Data(ref data) => Some(data),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the first DATA chunk, whose payload will be the left input of the parser
+    pub fn first_data(&self) -> Option<&SctpData> {
+        self.data().into_iter().next()
+    }
+}
+
+impl Parsable<PacketData> for SctpParser {
+    /// Parse an SCTP packet including all chunks from a byte slice. The left input will be the
+    /// payload of the first DATA chunk, or empty if there is none.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the network layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ip_protocol(result) != Some(IpProtocol::Sctp) {
+            return reject(input);
+        }
+
+        let (mut rest, (src_port, dst_port, verification_tag, checksum)) = try_parse!(input,
+            tuple!(be_u16, be_u16, be_u32, le_u32));
+        if rest.is_empty() {
+            return reject(input);
+        }
+
+        // Parse all chunks, which are padded to a multiple of four bytes
+        let mut chunks = vec![];
+        let mut payload = None;
+        while !rest.is_empty() {
+            let (left, (kind, flags, value)) = try_parse!(rest,
+                tuple!(be_u8, be_u8, length_bytes!(map!(verify!(be_u16, |l: u16| l >= 4), |l: u16| l - 4))));
+            let (_, chunk) = try_parse!(value, apply!(sctp_chunk, kind, flags));
+            if let SctpChunk::Data(_) = chunk {
+                if payload.is_none() {
+                    payload = Some(&value[12..]);
+                }
+            }
+            chunks.push(chunk);
+
+            // The padding of the last chunk may be missing
+            let padding = ::std::cmp::min((4 - value.len() % 4) % 4, left.len());
+            rest = &left[padding..];
+        }
+
+        // The checksum field itself is zero for the calculation
+        let crc = crc32c_update(crc32c_update(crc32c_update(!0, &input[..8]), &[0; 4]), &input[12..]);
+        let checksum_valid = !crc == checksum;
+        if !checksum_valid {
+            debug!("Invalid SCTP checksum {:#010x}", checksum);
+        }
+
+        IResult::Done(payload.unwrap_or(rest),
+                      Box::new(SctpPacket {
+                          src_port: src_port,
+                          dst_port: dst_port,
+                          verification_tag: verification_tag,
+                          checksum: checksum,
+                          checksum_valid: checksum_valid,
+                          chunks: chunks,
+                      }))
+    }
+}
+
+/// Return the payload protocol identifier of the first DATA chunk of the last SCTP result
+pub fn next_sctp_ppid(result: Option<&ParserResultVec>) -> Option<u32> {
+    last_result::<SctpPacket>(result).and_then(|p| p.first_data()).map(|d| d.ppid)
+}
+
+/// Calculate the CRC32c checksum of the data
+pub fn crc32c(data: &[u8]) -> u32 {
+    !crc32c_update(!0, data)
+}
+
+/// Continue a CRC32c calculation without the final inversion
+fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, b| CRC32C_TABLE[((crc ^ u32::from(*b)) & 0xff) as usize] ^ crc >> 8)
+}
+
+/// Parse the value of a chunk
+fn sctp_chunk(input: &[u8], kind: u8, flags: u8) -> IResult<&[u8], SctpChunk> {
+    match kind {
+        0 => {
+            do_parse!(input,
+                tsn: be_u32 >>
+                stream_id: be_u16 >>
+                stream_sequence: be_u16 >>
+                ppid: be_u32 >>
+                (SctpChunk::Data(SctpData {
+                    unordered: flags & 0x04 != 0,
+                    beginning: flags & 0x02 != 0,
+                    ending: flags & 0x01 != 0,
+                    tsn: tsn,
+                    stream_id: stream_id,
+                    stream_sequence: stream_sequence,
+                    ppid: ppid,
+                    payload: input[12..].to_vec(),
+                }))
+            )
+        }
+        1 | 2 => {
+            do_parse!(input,
+                initiate_tag: be_u32 >>
+                a_rwnd: be_u32 >>
+                outbound_streams: be_u16 >>
+                inbound_streams: be_u16 >>
+                initial_tsn: be_u32 >>
+                parameters: sctp_parameters >>
+                ({
+                    let init = SctpInit {
+                        initiate_tag: initiate_tag,
+                        a_rwnd: a_rwnd,
+                        outbound_streams: outbound_streams,
+                        inbound_streams: inbound_streams,
+                        initial_tsn: initial_tsn,
+                        parameters: parameters,
+                    };
+                    if kind == 1 {
+                        SctpChunk::Init(init)
+                    } else {
+                        SctpChunk::InitAck(init)
+                    }
+                })
+            )
+        }
+        3 => {
+            do_parse!(input,
+                cumulative_tsn: be_u32 >>
+                a_rwnd: be_u32 >>
+                gap_count: be_u16 >>
+                duplicate_count: be_u16 >>
+                gap_blocks: count!(pair!(be_u16, be_u16), gap_count as usize) >>
+                duplicates: count!(be_u32, duplicate_count as usize) >>
+                (SctpChunk::Sack {
+                    cumulative_tsn: cumulative_tsn,
+                    a_rwnd: a_rwnd,
+                    gap_blocks: gap_blocks,
+                    duplicates: duplicates,
+                })
+            )
+        }
+        4 => IResult::Done(&input[input.len()..], SctpChunk::Heartbeat(input.to_vec())),
+        5 => IResult::Done(&input[input.len()..], SctpChunk::HeartbeatAck(input.to_vec())),
+        6 => {
+            map!(input, sctp_parameters, |causes| {
+                SctpChunk::Abort {
+                    reflected: flags & 0x01 != 0,
+                    causes: causes,
+                }
+            })
+        }
+        7 => map!(input, be_u32, |tsn| SctpChunk::Shutdown { cumulative_tsn: tsn }),
+        8 => IResult::Done(input, SctpChunk::ShutdownAck),
+        9 => map!(input, sctp_parameters, SctpChunk::Error),
+        10 => IResult::Done(&input[input.len()..], SctpChunk::CookieEcho(input.to_vec())),
+        11 => IResult::Done(input, SctpChunk::CookieAck),
+        14 => IResult::Done(input, SctpChunk::ShutdownComplete { reflected: flags & 0x01 != 0 }),
+        _ => {
+            IResult::Done(&input[input.len()..],
+                          SctpChunk::Unknown {
+                              kind: kind,
+                              flags: flags,
+                              value: input.to_vec(),
+                          })
+        }
+    }
+}
+
+/// Parse parameters or error causes, which fill the whole input and are padded to a multiple of
+/// four bytes
+fn sctp_parameters(mut input: &[u8]) -> IResult<&[u8], Vec<SctpParameter>> {
+    let mut parameters = vec![];
+    while !input.is_empty() {
+        let (rest, (kind, value)) = try_parse!(input,
+            pair!(be_u16, length_bytes!(map!(verify!(be_u16, |l: u16| l >= 4), |l: u16| l - 4))));
+        parameters.push(SctpParameter {
+            kind: kind,
+            value: value.to_vec(),
+        });
+        let padding = ::std::cmp::min((4 - value.len() % 4) % 4, rest.len());
+        input = &rest[padding..];
+    }
+    IResult::Done(input, parameters)
+}
@@ -0,0 +1,392 @@
+//! Session Initiation Protocol related packets
+//!
+//! The parser detects messages by their start line, independently from the used ports. Bodies
+//! of the type `application/sdp` are parsed as session descriptions, whose media ports are
+//! stored within the `RtpCache` of the shared `PacketData` if available. This allows the RTP and
+//! RTCP parsers to recognize the media streams of a call, which are forgotten on `BYE`.
+use std::net::IpAddr;
+use std::str;
+use std::time::Instant;
+
+use nom::{IResult, ErrorKind, Needed};
+
+use packet::http::{line, header_fields};
+use packet::prelude::*;
+
+/// The well known SIP port
+pub const SIP_PORT: u16 = 5060;
+
+/// The only supported protocol version
+const SIP_VERSION: &str = "SIP/2.0";
+
+/// The full names of the compact header forms
+const COMPACT_HEADERS: &[(&str, &str)] = &[("c", "Content-Type"),
+                                           ("e", "Content-Encoding"),
+                                           ("f", "From"),
+                                           ("i", "Call-ID"),
+                                           ("k", "Supported"),
+                                           ("l", "Content-Length"),
+                                           ("m", "Contact"),
+                                           ("s", "Subject"),
+                                           ("t", "To"),
+                                           ("v", "Via")];
+
+/// The SIP parser
+#[derive(Debug)]
+pub struct SipParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a SIP message
+pub struct SipPacket {
+    /// The request or status line
+    pub start: SipStart,
+
+    /// The headers, where compact names are replaced by their full names
+    pub headers: HttpHeaders,
+
+    /// The body
+    pub body: Vec<u8>,
+
+    /// The session description carried by the body
+    pub sdp: Option<SdpSession>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The first line of a message
+pub enum SipStart {
+    /// A request line
+    Request {
+        /// The method
+        method: String,
+
+        /// The request URI
+        uri: String,
+    },
+
+    /// A status line
+    Response {
+        /// The status code
+        status: u16,
+
+        /// The reason phrase
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an SDP session description
+pub struct SdpSession {
+    /// The originator and session identifier
+    pub origin: String,
+
+    /// The session name
+    pub name: String,
+
+    /// The connection address of all media without an own one
+    pub connection: Option<IpAddr>,
+
+    /// The session attributes
+    pub attributes: Vec<SdpAttribute>,
+
+    /// The media descriptions
+    pub media: Vec<SdpMedia>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An SDP media description
+pub struct SdpMedia {
+    /// The media type like `audio` or `video`
+    pub kind: String,
+
+    /// The transport port, zero if the media stream is rejected
+    pub port: u16,
+
+    /// The number of consecutive ports
+    pub port_count: u16,
+
+    /// The transport protocol like `RTP/AVP`
+    pub protocol: String,
+
+    /// The media formats, which are RTP payload types for RTP based protocols
+    pub formats: Vec<String>,
+
+    /// The connection address of the media
+    pub connection: Option<IpAddr>,
+
+    /// The media attributes
+    pub attributes: Vec<SdpAttribute>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An SDP attribute
+pub struct SdpAttribute {
+    /// The name
+    pub name: String,
+
+    /// The value, which is missing for flags
+    pub value: Option<String>,
+}
+
+impl SipPacket {
+    /// The method of a request
+    pub fn method(&self) -> Option<&str> {
+        match self.start {
+            SipStart::Request { ref method, .. } => Some(method),
+            SipStart::Response { .. } => None,
+        }
+    }
+
+    /// The value of the `Call-ID` field
+    pub fn call_id(&self) -> Option<&str> {
+        self.headers.get("Call-ID")
+    }
+
+    /// The sequence number and method of the `CSeq` field
+    pub fn cseq(&self) -> Option<(u32, &str)> {
+        self.headers.get("CSeq").and_then(|v| {
+            let mut parts = v.split_whitespace();
+            match (parts.next().and_then(|s| s.parse().ok()), parts.next()) {
+                (Some(sequence), Some(method)) => Some((sequence, method)),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl SdpMedia {
+    /// The connection address of the media, falling back to the one of the session
+    pub fn address(&self, session: &SdpSession) -> Option<IpAddr> {
+        self.connection.or(session.connection)
+    }
+
+    /// The RTCP port, which follows the RTP port if not given by the `rtcp` attribute or
+    /// multiplexed via `rtcp-mux`
+    pub fn rtcp_port(&self) -> u16 {
+        if self.attributes.iter().any(|a| a.name == "rtcp-mux") {
+            return self.port;
+        }
+        self.attribute("rtcp")
+            .and_then(|v| v.split_whitespace().next())
+            .and_then(|p| p.parse().ok())
+            .unwrap_or_else(|| self.port.wrapping_add(1))
+    }
+
+    /// Get the value of the first attribute with the given name
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|a| a.name == name).and_then(|a| a.value.as_ref()).map(|v| v.as_str())
+    }
+}
+
+impl Parsable<PacketData> for SipParser {
+    /// Parse a SIP message from a byte slice. The left input will be the data following the
+    /// message. Messages carried by TCP need a `Content-Length`, whereas the body of a datagram
+    /// extends to its end if the length is missing.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or carried by UDP or TCP
+        let datagram = last_result::<UdpPacket>(result).is_some();
+        let stream = last_result::<TcpPacket>(result).is_some() || tcp_stream(result).is_some();
+        if input.is_empty() || result.map_or(false, |r| !r.is_empty()) && !datagram && !stream {
+            return reject(input);
+        }
+
+        // Datagrams are always complete
+        let (rest, (start, headers)) = match pair!(input, start_line, header_fields) {
+            IResult::Done(rest, message) => (rest, message),
+            IResult::Incomplete(_) if datagram => return reject(input),
+            IResult::Incomplete(needed) => return IResult::Incomplete(needed),
+            IResult::Error(error) => return IResult::Error(error),
+        };
+        let headers = expand_headers(&headers);
+
+        // Determine the length of the body
+        let (rest, body) = match headers.content_length() {
+            Some(length) if rest.len() >= length => (&rest[length..], &rest[..length]),
+            Some(_) if datagram => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+            Some(length) => {
+                return match (input.len() - rest.len()).checked_add(length) {
+                    Some(size) => IResult::Incomplete(Needed::Size(size)),
+                    None => IResult::Error(error_position!(ErrorKind::Verify, input)),
+                };
+            }
+            None if headers.contains("Content-Length") => {
+                return IResult::Error(error_position!(ErrorKind::Verify, input));
+            }
+            None if datagram || result.map_or(true, |r| r.is_empty()) => (&rest[rest.len()..], rest),
+            None => (rest, &rest[..0]),
+        };
+
+        // Parse the session description
+        let is_sdp = headers.content_type()
+            .map_or(false, |t| t.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("application/sdp"));
+        let sdp = if is_sdp && !body.is_empty() {
+            let sdp = session_description(body);
+            if sdp.is_none() {
+                debug!("Invalid session description within SIP message");
+            }
+            sdp
+        } else {
+            None
+        };
+
+        let packet = SipPacket {
+            start: start,
+            headers: headers,
+            body: body.to_vec(),
+            sdp: sdp,
+        };
+
+        // Announce the media ports of the call
+        if let (Some(call_id), Some(cache)) = (packet.call_id(), data.and_then(|d| d.rtp.as_mut())) {
+            if packet.method() == Some("BYE") {
+                cache.forget(call_id);
+            } else if let Some(ref sdp) = packet.sdp {
+                let now = Instant::now();
+                for media in sdp.media.iter().filter(|m| m.port != 0) {
+                    match media.address(sdp) {
+                        Some(address) if !address.is_unspecified() => {
+                            cache.expect(address, media.port, media.rtcp_port(), call_id, &media.kind, now)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        IResult::Done(rest, Box::new(packet))
+    }
+}
+
+/// Parse a request or status line
+fn start_line(input: &[u8]) -> IResult<&[u8], SipStart> {
+    // Reject anything which can not become a start line as early as possible
+    let method_length = input.iter().take_while(|b| b.is_ascii_uppercase()).count();
+    let status_line = input.starts_with(b"SIP/2.0 ") ||
+                      b"SIP/2.0 ".starts_with(&input[..::std::cmp::min(input.len(), 8)]);
+    if !status_line && (method_length == 0 || method_length < input.len() && input[method_length] != b' ') {
+        return IResult::Error(error_position!(ErrorKind::Tag, input));
+    }
+
+    let (rest, line) = try_parse!(input, line);
+    let line = match str::from_utf8(line) {
+        Ok(line) => line,
+        Err(_) => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+    };
+    let mut parts = line.splitn(3, ' ');
+    let start = match (parts.next(), parts.next(), parts.next()) {
+        (Some(SIP_VERSION), Some(status), reason) if status_line => {
+            match status.parse() {
+                Ok(code) if status.len() == 3 && code >= 100 => {
+                    SipStart::Response {
+                        status: code,
+                        reason: reason.unwrap_or("").to_owned(),
+                    }
+                }
+                _ => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+            }
+        }
+        (Some(method), Some(uri), Some(SIP_VERSION)) if !uri.is_empty() && !uri.contains(' ') => {
+            SipStart::Request {
+                method: method.to_owned(),
+                uri: uri.to_owned(),
+            }
+        }
+        _ => return IResult::Error(error_position!(ErrorKind::Tag, input)),
+    };
+    IResult::Done(rest, start)
+}
+
+/// Replace the compact header names by their full names
+fn expand_headers(headers: &HttpHeaders) -> HttpHeaders {
+    let mut expanded = HttpHeaders::default();
+    for (name, value) in headers.iter() {
+        let name = COMPACT_HEADERS.iter()
+            .find(|&&(compact, _)| name.eq_ignore_ascii_case(compact))
+            .map_or(name.as_str(), |&(_, full)| full);
+        expanded.insert(name, value);
+    }
+    expanded
+}
+
+/// Parse a session description, returning `None` if it is invalid
+fn session_description(input: &[u8]) -> Option<SdpSession> {
+    let input = match str::from_utf8(input) {
+        Ok(input) => input,
+        Err(_) => return None,
+    };
+    let mut lines = input.lines().filter(|l| !l.is_empty());
+    if lines.next() != Some("v=0") {
+        return None;
+    }
+
+    let mut session = SdpSession {
+        origin: String::new(),
+        name: String::new(),
+        connection: None,
+        attributes: vec![],
+        media: vec![],
+    };
+    for line in lines {
+        let mut parts = line.splitn(2, '=');
+        let (kind, value) = match (parts.next(), parts.next()) {
+            (Some(kind), Some(value)) if kind.len() == 1 => (kind, value),
+            _ => return None,
+        };
+
+        // Connection and attribute lines belong to the preceding media description
+        match (kind, session.media.last_mut()) {
+            ("o", None) => session.origin = value.to_owned(),
+            ("s", None) => session.name = value.to_owned(),
+            ("c", None) => session.connection = Some(connection_address(value)?),
+            ("c", Some(media)) => media.connection = Some(connection_address(value)?),
+            ("a", None) => session.attributes.push(attribute(value)),
+            ("a", Some(media)) => media.attributes.push(attribute(value)),
+            ("m", _) => {
+                let mut fields = value.split_whitespace();
+                let (kind, ports, protocol) = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(kind), Some(ports), Some(protocol)) => (kind, ports, protocol),
+                    _ => return None,
+                };
+                let mut ports = ports.splitn(2, '/');
+                let port = ports.next()?.parse().ok()?;
+                let port_count = match ports.next() {
+                    Some(count) => count.parse().ok()?,
+                    None => 1,
+                };
+                session.media.push(SdpMedia {
+                    kind: kind.to_owned(),
+                    port: port,
+                    port_count: port_count,
+                    protocol: protocol.to_owned(),
+                    formats: fields.map(|f| f.to_owned()).collect(),
+                    connection: None,
+                    attributes: vec![],
+                });
+            }
+            _ => {}
+        }
+    }
+    Some(session)
+}
+
+/// Parse the address of a connection line, which may be followed by a multicast TTL
+fn connection_address(value: &str) -> Option<IpAddr> {
+    let mut fields = value.split_whitespace();
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some("IN"), Some("IP4"), Some(address)) |
+        (Some("IN"), Some("IP6"), Some(address)) => address.split('/').next().and_then(|a| a.parse().ok()),
+        _ => None,
+    }
+}
+
+/// Parse an attribute line
+fn attribute(value: &str) -> SdpAttribute {
+    let mut parts = value.splitn(2, ':');
+    SdpAttribute {
+        name: parts.next().unwrap_or("").to_owned(),
+        value: parts.next().map(|v| v.to_owned()),
+    }
+}
@@ -0,0 +1,458 @@
+//! Reassembly of TCP streams
+//!
+//! The TCP parser stores all segments within the `StreamCache` of the shared `PacketData` if
+//! available. Every direction of a connection is ordered separately, where retransmitted and
+//! overlapping data is taken from the first segment which arrived. The ordered data is passed to
+//! the children of the TCP node via `Peel::traverse_streams`, whereas the first result of every
+//! traversal is a `TcpStream`. Parsers which need more data can return `Incomplete`, the
+//! traversal will be resumed at them as soon as the stream continues.
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use petgraph::graph::NodeIndex;
+
+use error::ErrorType;
+use packet::prelude::*;
+use PeelResult;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// Identifies a single direction of a TCP connection
+pub struct StreamKey {
+    /// The source address
+    pub src: IpAddr,
+
+    /// The source port
+    pub src_port: u16,
+
+    /// The destination address
+    pub dst: IpAddr,
+
+    /// The destination port
+    pub dst_port: u16,
+}
+
+impl StreamKey {
+    /// Get the key of the opposite direction
+    pub fn reverse(&self) -> StreamKey {
+        StreamKey {
+            src: self.dst,
+            src_port: self.dst_port,
+            dst: self.src,
+            dst_port: self.src_port,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The first result of a stream traversal, describing the stream data
+pub struct TcpStream {
+    /// The direction of the connection
+    pub key: StreamKey,
+
+    /// The position of the first input byte within the stream
+    pub offset: u64,
+
+    /// The sender finished the stream, no more data will follow
+    pub finished: bool,
+
+    /// The connection was reset
+    pub reset: bool,
+}
+
+/// Return the stream if the traversal was started by `Peel::traverse_streams`
+pub fn tcp_stream(result: Option<&ParserResultVec>) -> Option<&TcpStream> {
+    result.and_then(|r| r.first()).and_then(|r| r.downcast_ref::<TcpStream>())
+}
+
+/// A parser node together with the results it has to be resumed with
+type Resume = (NodeIndex, ParserResultVec);
+
+#[derive(Debug)]
+/// The state of a single direction
+struct StreamBuffer {
+    /// The sequence number of the first stream byte
+    base: u32,
+
+    /// The stream position of the first buffered byte
+    offset: u64,
+
+    /// The ordered data, not yet consumed by the parsers
+    data: Vec<u8>,
+
+    /// Segments which arrived out of order by their stream positions
+    pending: BTreeMap<u64, Vec<u8>>,
+
+    /// The number of bytes within the pending segments
+    pending_size: usize,
+
+    /// The stream position of the FIN flag
+    fin: Option<u64>,
+
+    /// The connection was reset
+    reset: bool,
+
+    /// The node and results to resume an incomplete traversal with
+    resume: Option<Resume>,
+
+    /// The arrival of the last segment
+    last_seen: Instant,
+}
+
+impl StreamBuffer {
+    fn new(base: u32, now: Instant) -> Self {
+        StreamBuffer {
+            base: base,
+            offset: 0,
+            data: vec![],
+            pending: BTreeMap::new(),
+            pending_size: 0,
+            fin: None,
+            reset: false,
+            resume: None,
+            last_seen: now,
+        }
+    }
+
+    /// The stream position of the next expected byte
+    fn received(&self) -> u64 {
+        self.offset + self.data.len() as u64
+    }
+
+    /// The FIN flag was received and all data before
+    fn finished(&self) -> bool {
+        self.fin.map_or(false, |fin| fin <= self.received())
+    }
+
+    /// Append the data at the stream position if it continues the stream
+    fn append(&mut self, position: u64, data: &[u8]) -> bool {
+        let received = self.received();
+        if position > received {
+            return false;
+        }
+        let skip = (received - position) as usize;
+        if skip < data.len() {
+            self.data.extend_from_slice(&data[skip..]);
+        }
+        true
+    }
+
+    /// The number of buffered bytes, ordered or not
+    fn size(&self) -> usize {
+        self.data.len() + self.pending_size
+    }
+
+    /// Move all pending segments which continue the stream
+    fn advance(&mut self) {
+        loop {
+            let position = match self.pending.keys().next() {
+                Some(position) if *position <= self.received() => *position,
+                _ => break,
+            };
+            if let Some(segment) = self.pending.remove(&position) {
+                self.pending_size -= segment.len();
+                self.append(position, &segment);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A cache for TCP streams with timeout and memory limits
+pub struct StreamCache {
+    /// Idle connections will be dropped after this duration
+    pub timeout: Duration,
+
+    /// The maximum number of bytes buffered per direction
+    pub max_bytes: usize,
+
+    /// The maximum number of tracked directions
+    pub max_streams: usize,
+
+    /// The maximum number of bytes buffered over all directions
+    pub max_total_bytes: usize,
+
+    buffers: HashMap<StreamKey, StreamBuffer>,
+    size: usize,
+}
+
+impl Default for StreamCache {
+    fn default() -> Self {
+        StreamCache::new(Duration::from_secs(120), 1024 * 1024, 4096, 64 * 1024 * 1024)
+    }
+}
+
+impl StreamCache {
+    /// Create a new `StreamCache`
+    pub fn new(timeout: Duration, max_bytes: usize, max_streams: usize, max_total_bytes: usize) -> Self {
+        StreamCache {
+            timeout: timeout,
+            max_bytes: max_bytes,
+            max_streams: max_streams,
+            max_total_bytes: max_total_bytes,
+            buffers: HashMap::new(),
+            size: 0,
+        }
+    }
+
+    /// The number of tracked directions
+    pub fn streams(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// The number of bytes buffered over all directions
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The number of ordered bytes not yet consumed by the parsers
+    pub fn buffered(&self, key: &StreamKey) -> usize {
+        self.buffers.get(key).map_or(0, |b| b.data.len())
+    }
+
+    /// Insert a segment of the direction. The sequence number of the first stream byte is taken
+    /// from the SYN, or from the first seen segment if the connection was already established.
+    /// Returns true if new data or state changes are available for the parsers.
+    pub fn insert(&mut self, key: StreamKey, sequence: u32, flags: TcpFlags, payload: &[u8], now: Instant) -> bool {
+        self.expire(now);
+
+        // The connection is closed in both directions
+        if flags.rst {
+            for key in &[key, key.reverse()] {
+                if let Some(buffer) = self.buffers.get_mut(key) {
+                    buffer.reset = true;
+                }
+            }
+            return self.buffers.contains_key(&key);
+        }
+
+        // Make room for the new direction
+        if !self.buffers.contains_key(&key) && self.buffers.len() >= self.max_streams {
+            let oldest = self.buffers.iter().min_by_key(|&(_, b)| b.last_seen).map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                debug!("Stream cache full, dropping {:?}", oldest);
+                self.drop_buffer(&oldest);
+            }
+        }
+
+        // The SYN occupies one sequence number
+        let sequence = if flags.syn {
+            sequence.wrapping_add(1)
+        } else {
+            sequence
+        };
+        let (position, payload, received, size) = {
+            let buffer = self.buffers.entry(key).or_insert_with(|| StreamBuffer::new(sequence, now));
+            buffer.last_seen = now;
+
+            // Locate the segment within the stream, data before the stream start is ignored
+            let relative = sequence.wrapping_sub(buffer.base.wrapping_add(buffer.received() as u32)) as i32 as i64;
+            let position = buffer.received() as i64 + relative;
+            let (position, payload) = if position < 0 {
+                let skip = ::std::cmp::min(-position as usize, payload.len());
+                (0, &payload[skip..])
+            } else {
+                (position as u64, payload)
+            };
+            if flags.fin && buffer.fin.is_none() {
+                buffer.fin = Some(position + payload.len() as u64);
+            }
+            (position, payload, buffer.received(), buffer.size())
+        };
+
+        // The parsers can not continue behind a gap, so an overflowing direction is dropped
+        let in_order = position <= received;
+        let new_bytes = if in_order {
+            (position + payload.len() as u64).saturating_sub(received) as usize
+        } else {
+            payload.len()
+        };
+        if in_order && size + new_bytes > self.max_bytes {
+            warn!("Stream buffer of {:?} full, dropping the direction", key);
+            self.drop_buffer(&key);
+            return false;
+        }
+
+        // Make room within the overall limit by dropping the most idle directions
+        while self.size + new_bytes > self.max_total_bytes {
+            let oldest = self.buffers
+                .iter()
+                .filter(|&(k, _)| *k != key)
+                .min_by_key(|&(_, b)| b.last_seen)
+                .map(|(key, _)| *key);
+            match oldest {
+                Some(oldest) => {
+                    debug!("Stream cache full, dropping {:?}", oldest);
+                    self.drop_buffer(&oldest);
+                }
+                None => break,
+            }
+        }
+        if in_order && self.size + new_bytes > self.max_total_bytes {
+            warn!("Stream cache full, dropping the direction {:?}", key);
+            self.drop_buffer(&key);
+            return false;
+        }
+
+        let max_bytes = self.max_bytes;
+        let full = self.size + new_bytes > self.max_total_bytes;
+        let buffer = match self.buffers.get_mut(&key) {
+            Some(buffer) => buffer,
+            None => return false,
+        };
+        if buffer.append(position, payload) {
+            buffer.advance();
+        } else if full || size + payload.len() > max_bytes {
+            warn!("Stream buffer of {:?} full, dropping segment", key);
+        } else if buffer.pending.get(&position).map_or(true, |s| s.len() < payload.len()) {
+            // Keep the longest segment for every position
+            if let Some(segment) = buffer.pending.insert(position, payload.to_vec()) {
+                buffer.pending_size -= segment.len();
+            }
+            buffer.pending_size += payload.len();
+        }
+        self.size = self.size - size + buffer.size();
+        buffer.received() > received || buffer.finished()
+    }
+
+    /// Drop all idle directions which exceeded the timeout and return their number
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let timeout = self.timeout;
+        let expired: Vec<StreamKey> = self.buffers
+            .iter()
+            .filter(|&(_, b)| now.duration_since(b.last_seen) >= timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &expired {
+            debug!("Stream {:?} timed out", key);
+            self.drop_buffer(key);
+        }
+        expired.len()
+    }
+
+    fn drop_buffer(&mut self, key: &StreamKey) {
+        if let Some(buffer) = self.buffers.remove(key) {
+            self.size -= buffer.size();
+        }
+    }
+
+    /// The directions which have to be passed to the parsers
+    fn ready(&self) -> Vec<StreamKey> {
+        self.buffers
+            .iter()
+            .filter(|&(_, b)| !b.data.is_empty() || b.finished() || b.reset)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Take the ordered data of a direction for parsing
+    fn take(&mut self, key: &StreamKey) -> Option<(TcpStream, Vec<u8>, Option<Resume>)> {
+        let size = &mut self.size;
+        self.buffers.get_mut(key).map(|buffer| {
+            *size -= buffer.data.len();
+            let stream = TcpStream {
+                key: *key,
+                offset: buffer.offset,
+                finished: buffer.finished(),
+                reset: buffer.reset,
+            };
+            (stream, ::std::mem::take(&mut buffer.data), buffer.resume.take())
+        })
+    }
+
+    /// Give back the unconsumed data after parsing
+    fn restore(&mut self, key: &StreamKey, consumed: usize, data: &[u8], resume: Option<Resume>) {
+        if let Some(buffer) = self.buffers.get_mut(key) {
+            self.size += data.len();
+            buffer.offset += consumed as u64;
+            buffer.data = data.to_vec();
+            buffer.resume = resume;
+        }
+    }
+
+    /// Remove the direction if no more data can be expected
+    fn close(&mut self, key: &StreamKey) {
+        let closed = self.buffers.get(key).map_or(false, |b| b.finished() || b.reset);
+        if closed {
+            debug!("Stream {:?} closed", key);
+            self.drop_buffer(key);
+        }
+    }
+}
+
+impl Peel<PacketData> {
+    /// Traverse the children of the root named `tcp` with the ordered data of all streams and
+    /// pass the results to the given closure. The traversal is repeated as long as the parsers
+    /// consume data. If a parser needs more data, the remaining data is kept and the traversal
+    /// will be resumed at this parser with the previous results.
+    pub fn traverse_streams<F>(&mut self, mut f: F)
+        where F: FnMut(&TcpStream, PeelResult)
+    {
+        let node = match self.get_root("tcp") {
+            Some(node) => node,
+            None => return,
+        };
+        let keys = match self.data.as_ref().and_then(|d| d.streams.as_ref()) {
+            Some(cache) => cache.ready(),
+            None => return,
+        };
+
+        for key in keys {
+            loop {
+                let (stream, data, resume) = match self.data
+                    .as_mut()
+                    .and_then(|d| d.streams.as_mut())
+                    .and_then(|c| c.take(&key)) {
+                    Some(taken) => taken,
+                    None => break,
+                };
+                // A finished stream has to be passed to a waiting parser even without new data
+                if data.is_empty() && !(stream.finished && resume.is_some()) {
+                    self.restore_stream(&key, 0, &[], resume);
+                    break;
+                }
+
+                let result = match resume {
+                    Some((node, mut results)) => {
+                        // The previous results start with an outdated stream state
+                        if let Some(first) = results.first_mut() {
+                            if first.is::<TcpStream>() {
+                                *first = Box::new(stream.clone());
+                            }
+                        }
+                        self.traverse_node(node, &data, results)
+                    }
+                    None => self.traverse_children(node, &data, vec![Box::new(stream.clone())]),
+                };
+                let consumed = data.len() - result.left_input.len();
+
+                // Wait for more data
+                if let (Some(node), Some(&ErrorType::Incomplete(_))) =
+                       (result.incomplete, result.error.as_ref().map(|e| &e.code)) {
+                    let resume = Some((node, result.result));
+                    self.restore_stream(&key, consumed, &data[consumed..], resume);
+                    break;
+                }
+
+                // Data which is not consumed at all can not be parsed and is dropped
+                let left = if consumed == 0 {
+                    &data[data.len()..]
+                } else {
+                    result.left_input
+                };
+                f(&stream, result);
+                self.restore_stream(&key, data.len() - left.len(), left, None);
+            }
+
+            if let Some(cache) = self.data.as_mut().and_then(|d| d.streams.as_mut()) {
+                cache.close(&key);
+            }
+        }
+    }
+
+    fn restore_stream(&mut self, key: &StreamKey, consumed: usize, data: &[u8], resume: Option<Resume>) {
+        if let Some(cache) = self.data.as_mut().and_then(|d| d.streams.as_mut()) {
+            cache.restore(key, consumed, data, resume);
+        }
+    }
+}
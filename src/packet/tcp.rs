@@ -0,0 +1,262 @@
+//! Transmission Control Protocol related packets
+use std::time::Instant;
+
+use nom::{IResult, ErrorKind, Needed, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The TCP parser
+#[derive(Debug)]
+pub struct TcpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a TCP segment header
+pub struct TcpPacket {
+    /// The source port
+    pub src_port: u16,
+
+    /// The destination port
+    pub dst_port: u16,
+
+    /// The sequence number
+    pub sequence: u32,
+
+    /// The acknowledgement number
+    pub acknowledgement: u32,
+
+    /// The header length in bytes
+    pub header_length: u8,
+
+    /// The control flags
+    pub flags: TcpFlags,
+
+    /// The receive window
+    pub window: u16,
+
+    /// The checksum over the pseudo header and the segment
+    pub checksum: u16,
+
+    /// Points to the end of the urgent data
+    pub urgent_pointer: u16,
+
+    /// The header options
+    pub options: Vec<TcpOption>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+/// The control flags of a TCP segment
+pub struct TcpFlags {
+    /// ECN nonce concealment protection
+    pub ns: bool,
+
+    /// Congestion window reduced
+    pub cwr: bool,
+
+    /// ECN echo
+    pub ece: bool,
+
+    /// The urgent pointer is significant
+    pub urg: bool,
+
+    /// The acknowledgement number is significant
+    pub ack: bool,
+
+    /// Push the buffered data to the application
+    pub psh: bool,
+
+    /// Reset the connection
+    pub rst: bool,
+
+    /// Synchronize the sequence numbers
+    pub syn: bool,
+
+    /// No more data from the sender
+    pub fin: bool,
+}
+
+impl From<u16> for TcpFlags {
+    fn from(value: u16) -> Self {
+        TcpFlags {
+            ns: value & 0x100 != 0,
+            cwr: value & 0x80 != 0,
+            ece: value & 0x40 != 0,
+            urg: value & 0x20 != 0,
+            ack: value & 0x10 != 0,
+            psh: value & 0x08 != 0,
+            rst: value & 0x04 != 0,
+            syn: value & 0x02 != 0,
+            fin: value & 0x01 != 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A TCP header option
+pub enum TcpOption {
+    /// End of the option list
+    EndOfList,
+
+    /// Padding between options
+    NoOperation,
+
+    /// The maximum segment size the sender is able to receive
+    MaximumSegmentSize(u16),
+
+    /// The shift count of the receive window
+    WindowScale(u8),
+
+    /// Selective acknowledgements are supported
+    SackPermitted,
+
+    /// The left and right edges of received blocks
+    Sack(Vec<(u32, u32)>),
+
+    /// Timestamps for round trip measurement and protection against wrapped sequences
+    Timestamps {
+        /// The timestamp of the sender
+        value: u32,
+
+        /// The echoed timestamp of the remote side
+        echo_reply: u32,
+    },
+
+    /// TCP Fast Open cookie, which is empty for a cookie request
+    FastOpen(Vec<u8>),
+
+    /// Any other option
+    Unknown {
+        /// The option type
+        kind: u8,
+
+        /// The option data without type and length
+        data: Vec<u8>,
+    },
+}
+
+impl Parsable<PacketData> for TcpParser {
+    /// Parse a TCP segment from a byte slice. The left input will be the segment payload.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 data: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the network layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ip_protocol(result) != Some(IpProtocol::Tcp) {
+            return reject(input);
+        }
+
+        // The original datagram of ICMP errors is usually truncated after the ports
+        let embedded = within_icmp_error(result);
+        if embedded && input.len() < 20 {
+            return reject(input);
+        }
+
+        let (_, packet) = try_parse!(input,
+            do_parse!(
+                src_port: be_u16 >>
+                dst_port: be_u16 >>
+                sequence: be_u32 >>
+                acknowledgement: be_u32 >>
+                offset_flags: be_u16 >>
+                window: be_u16 >>
+                checksum: be_u16 >>
+                urgent_pointer: be_u16 >>
+                (TcpPacket {
+                    src_port: src_port,
+                    dst_port: dst_port,
+                    sequence: sequence,
+                    acknowledgement: acknowledgement,
+                    header_length: (offset_flags >> 12) as u8 * 4,
+                    flags: TcpFlags::from(offset_flags & 0x1ff),
+                    window: window,
+                    checksum: checksum,
+                    urgent_pointer: urgent_pointer,
+                    options: vec![],
+                })
+            )
+        );
+
+        // Validate the data offset
+        let header_length = packet.header_length as usize;
+        if header_length < 20 {
+            return reject(input);
+        }
+        if input.len() < header_length {
+            if embedded {
+                return reject(input);
+            }
+            return IResult::Incomplete(Needed::Size(header_length));
+        }
+
+        let mut packet = packet;
+        packet.options = try_parse!(&input[20..header_length], complete!(tcp_options)).1;
+        let payload = &input[header_length..];
+
+        // Collect the segments for stream reassembly
+        let data = if embedded { None } else { data };
+        if let (Some((src, dst)), Some(cache)) = (ip_addresses(result), data.and_then(|d| d.streams.as_mut())) {
+            let key = StreamKey {
+                src: src,
+                src_port: packet.src_port,
+                dst: dst,
+                dst_port: packet.dst_port,
+            };
+            cache.insert(key, packet.sequence, packet.flags, payload, Instant::now());
+        }
+
+        IResult::Done(payload, Box::new(packet))
+    }
+}
+
+/// Parse all TCP options
+fn tcp_options(input: &[u8]) -> IResult<&[u8], Vec<TcpOption>> {
+    let mut options = vec![];
+    let mut input = input;
+    while !input.is_empty() {
+        let (rest, option) = try_parse!(input, tcp_option);
+        input = rest;
+        let end = option == TcpOption::EndOfList;
+        options.push(option);
+        if end {
+            break;
+        }
+    }
+    IResult::Done(input, options)
+}
+
+/// Parse a single TCP option
+fn tcp_option(input: &[u8]) -> IResult<&[u8], TcpOption> {
+    let (rest, kind) = try_parse!(input, be_u8);
+    match kind {
+        0 => return IResult::Done(rest, TcpOption::EndOfList),
+        1 => return IResult::Done(rest, TcpOption::NoOperation),
+        _ => {}
+    }
+
+    let (rest, data) = try_parse!(rest,
+                                  length_bytes!(map!(verify!(be_u8, |l: u8| l >= 2), |l: u8| l - 2)));
+    let option = match (kind, data.len()) {
+        (2, 2) => TcpOption::MaximumSegmentSize(try_parse!(data, be_u16).1),
+        (3, 1) => TcpOption::WindowScale(data[0]),
+        (4, 0) => TcpOption::SackPermitted,
+        (5, l) if l % 8 == 0 => TcpOption::Sack(try_parse!(data, many0!(complete!(pair!(be_u32, be_u32)))).1),
+        (8, 8) => {
+            let (_, (value, echo_reply)) = try_parse!(data, pair!(be_u32, be_u32));
+            TcpOption::Timestamps {
+                value: value,
+                echo_reply: echo_reply,
+            }
+        }
+        (34, _) => TcpOption::FastOpen(data.to_vec()),
+        // The experimental Fast Open option is identified by a magic number
+        (254, l) if l >= 2 && data[..2] == [0xf9, 0x89] => TcpOption::FastOpen(data[2..].to_vec()),
+        (2, _) | (3, _) | (4, _) | (5, _) | (8, _) => return IResult::Error(error_position!(ErrorKind::Verify, input)),
+        _ => {
+            TcpOption::Unknown {
+                kind: kind,
+                data: data.to_vec(),
+            }
+        }
+    };
+    IResult::Done(rest, option)
+}
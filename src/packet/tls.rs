@@ -0,0 +1,600 @@
+//! Transport Layer Security related packets
+//!
+//! The parser interprets the record layer and the unencrypted handshake messages, nothing will be
+//! decrypted. Handshake messages spanning several records are collected until they are complete,
+//! so every result contains whole messages. Handshake records which can not be interpreted, like
+//! the encrypted `Finished` message following a `ChangeCipherSpec`, are reported as opaque data.
+use std::fmt;
+
+use nom::{IResult, ErrorKind, Needed, be_u8, be_u16};
+
+use packet::prelude::*;
+
+/// The length of the record header
+const RECORD_HEADER_LENGTH: usize = 5;
+
+/// The maximum length of a record payload
+const MAX_RECORD_LENGTH: usize = 16384 + 2048;
+
+/// The maximum size of handshake messages collected from several records
+const MAX_HANDSHAKE_LENGTH: usize = 64 * 1024;
+
+/// The TLS parser
+#[derive(Debug)]
+pub struct TlsParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of one or more TLS records of the same content type
+pub struct TlsPacket {
+    /// The protocol version of the record layer
+    pub version: u16,
+
+    /// The number of records
+    pub records: usize,
+
+    /// The content of the records
+    pub message: TlsMessage,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The content of the records
+pub enum TlsMessage {
+    /// Unencrypted handshake messages
+    Handshake(Vec<TlsHandshake>),
+
+    /// The following records of the sender are protected
+    ChangeCipherSpec,
+
+    /// An unencrypted alert
+    Alert {
+        /// Warning or fatal
+        level: u8,
+
+        /// The reason
+        description: u8,
+    },
+
+    /// Data which is encrypted or not interpreted
+    Opaque {
+        /// The content type of the records
+        content_type: u8,
+
+        /// The length of the data
+        length: usize,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The handshake messages
+pub enum TlsHandshake {
+    /// The offer of the client
+    ClientHello(TlsClientHello),
+
+    /// The choices of the server
+    ServerHello(TlsServerHello),
+
+    /// A certificate chain
+    Certificate(Vec<Vec<u8>>),
+
+    /// Any other message
+    Other {
+        /// The message type
+        kind: u8,
+
+        /// The message body
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The first message of the client
+pub struct TlsClientHello {
+    /// The offered protocol version, superseded by the supported versions extension
+    pub version: u16,
+
+    /// The random value
+    pub random: Vec<u8>,
+
+    /// The session to resume
+    pub session_id: Vec<u8>,
+
+    /// The offered cipher suites in order of preference
+    pub cipher_suites: Vec<u16>,
+
+    /// The offered compression methods
+    pub compression_methods: Vec<u8>,
+
+    /// The extensions in the sent order
+    pub extensions: Vec<TlsExtension>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The first message of the server, which might be a retry request as well
+pub struct TlsServerHello {
+    /// The chosen protocol version, superseded by the supported versions extension
+    pub version: u16,
+
+    /// The random value
+    pub random: Vec<u8>,
+
+    /// The session identifier
+    pub session_id: Vec<u8>,
+
+    /// The chosen cipher suite
+    pub cipher_suite: u16,
+
+    /// The chosen compression method
+    pub compression_method: u8,
+
+    /// The extensions in the sent order
+    pub extensions: Vec<TlsExtension>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The hello extensions
+pub enum TlsExtension {
+    /// The host names of the server (SNI)
+    ServerName(Vec<String>),
+
+    /// The supported key exchange groups
+    SupportedGroups(Vec<u16>),
+
+    /// The supported elliptic curve point formats
+    EcPointFormats(Vec<u8>),
+
+    /// The supported signature algorithms
+    SignatureAlgorithms(Vec<u16>),
+
+    /// The application layer protocols (ALPN)
+    Alpn(Vec<String>),
+
+    /// The supported or chosen protocol versions
+    SupportedVersions(Vec<u16>),
+
+    /// The key shares of the client, or the single share or group of the server
+    KeyShare(Vec<TlsKeyShare>),
+
+    /// Any other extension
+    Unknown {
+        /// The extension type
+        kind: u16,
+
+        /// The raw extension data
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A key share entry
+pub struct TlsKeyShare {
+    /// The key exchange group
+    pub group: u16,
+
+    /// The public key, empty for the group requested by a retry
+    pub key_exchange: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+/// The parts of a client hello used for fingerprinting, without GREASE values. The `Display`
+/// implementation produces the JA3 string.
+pub struct TlsFingerprint {
+    /// The legacy protocol version
+    pub version: u16,
+
+    /// The cipher suites
+    pub cipher_suites: Vec<u16>,
+
+    /// The extension types in the sent order
+    pub extensions: Vec<u16>,
+
+    /// The supported groups
+    pub groups: Vec<u16>,
+
+    /// The elliptic curve point formats
+    pub point_formats: Vec<u8>,
+}
+
+impl TlsExtension {
+    /// The extension type
+    pub fn kind(&self) -> u16 {
+        match *self {
+            TlsExtension::ServerName(_) => 0,
+            TlsExtension::SupportedGroups(_) => 10,
+            TlsExtension::EcPointFormats(_) => 11,
+            TlsExtension::SignatureAlgorithms(_) => 13,
+            TlsExtension::Alpn(_) => 16,
+            TlsExtension::SupportedVersions(_) => 43,
+            TlsExtension::KeyShare(_) => 51,
+            TlsExtension::Unknown { kind, .. } => kind,
+        }
+    }
+}
+
+impl TlsPacket {
+    /// The client hello if contained
+    pub fn client_hello(&self) -> Option<&TlsClientHello> {
+        self.handshakes().filter_map(|h| match *h {
+            TlsHandshake::ClientHello(ref hello) => Some(hello),
+            _ => None,
+        }).next()
+    }
+
+    /// The server hello if contained
+    pub fn server_hello(&self) -> Option<&TlsServerHello> {
+        self.handshakes().filter_map(|h| match *h {
+            TlsHandshake::ServerHello(ref hello) => Some(hello),
+            _ => None,
+        }).next()
+    }
+
+    fn handshakes<'a>(&'a self) -> ::std::slice::Iter<'a, TlsHandshake> {
+        match self.message {
+            TlsMessage::Handshake(ref messages) => messages.iter(),
+            _ => [].iter(),
+        }
+    }
+}
+
+impl TlsClientHello {
+    /// The first host name of the server name indication
+    pub fn server_name(&self) -> Option<&str> {
+        self.extensions.iter().filter_map(|e| match *e {
+            TlsExtension::ServerName(ref names) => names.first().map(|n| n.as_str()),
+            _ => None,
+        }).next()
+    }
+
+    /// The offered application layer protocols
+    pub fn alpn(&self) -> Vec<&str> {
+        self.extensions.iter().filter_map(|e| match *e {
+            TlsExtension::Alpn(ref protocols) => Some(protocols.iter().map(|p| p.as_str()).collect()),
+            _ => None,
+        }).next().unwrap_or_default()
+    }
+
+    /// The offered protocol versions, either from the extension or the legacy version
+    pub fn supported_versions(&self) -> Vec<u16> {
+        self.extensions.iter().filter_map(|e| match *e {
+            TlsExtension::SupportedVersions(ref versions) => Some(versions.clone()),
+            _ => None,
+        }).next().unwrap_or_else(|| vec![self.version])
+    }
+
+    /// The summary of the offer for fingerprinting
+    pub fn fingerprint(&self) -> TlsFingerprint {
+        let mut fingerprint = TlsFingerprint {
+            version: self.version,
+            cipher_suites: self.cipher_suites.iter().cloned().filter(|c| !is_grease(*c)).collect(),
+            extensions: self.extensions.iter().map(|e| e.kind()).filter(|e| !is_grease(*e)).collect(),
+            ..TlsFingerprint::default()
+        };
+        for extension in &self.extensions {
+            match *extension {
+                TlsExtension::SupportedGroups(ref groups) => {
+                    fingerprint.groups = groups.iter().cloned().filter(|g| !is_grease(*g)).collect();
+                }
+                TlsExtension::EcPointFormats(ref formats) => fingerprint.point_formats = formats.clone(),
+                _ => {}
+            }
+        }
+        fingerprint
+    }
+}
+
+impl TlsServerHello {
+    /// The chosen protocol version, either from the extension or the legacy version
+    pub fn selected_version(&self) -> u16 {
+        self.extensions.iter().filter_map(|e| match *e {
+            TlsExtension::SupportedVersions(ref versions) => versions.first().cloned(),
+            _ => None,
+        }).next().unwrap_or(self.version)
+    }
+
+    /// The chosen application layer protocol
+    pub fn alpn(&self) -> Option<&str> {
+        self.extensions.iter().filter_map(|e| match *e {
+            TlsExtension::Alpn(ref protocols) => protocols.first().map(|p| p.as_str()),
+            _ => None,
+        }).next()
+    }
+}
+
+impl fmt::Display for TlsFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn join<T: ToString>(values: &[T]) -> String {
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("-")
+        }
+        write!(f,
+               "{},{},{},{},{}",
+               self.version,
+               join(&self.cipher_suites),
+               join(&self.extensions),
+               join(&self.groups),
+               join(&self.point_formats))
+    }
+}
+
+/// Returns true for the reserved values used to check the tolerance of peers (GREASE)
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+impl Parsable<PacketData> for TlsParser {
+    /// Parse TLS records from a byte slice. The left input will be the data following the
+    /// records.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or carried by TCP
+        if input.is_empty() ||
+           result.map_or(false, |r| !r.is_empty()) && last_result::<TcpPacket>(result).is_none() &&
+           tcp_stream(result).is_none() {
+            return reject(input);
+        }
+
+        let (rest, (content_type, version, payload)) = try_parse!(input, record);
+        let packet = |records, message| {
+            Box::new(TlsPacket {
+                version: version,
+                records: records,
+                message: message,
+            })
+        };
+        let opaque = TlsMessage::Opaque {
+            content_type: content_type,
+            length: payload.len(),
+        };
+
+        match content_type {
+            20 if payload == [1] => return IResult::Done(rest, packet(1, TlsMessage::ChangeCipherSpec)),
+            21 if payload.len() == 2 => {
+                let message = TlsMessage::Alert {
+                    level: payload[0],
+                    description: payload[1],
+                };
+                return IResult::Done(rest, packet(1, message));
+            }
+            22 => {}
+            _ => return IResult::Done(rest, packet(1, opaque)),
+        }
+
+        // Collect the following handshake records until the last message is complete
+        let mut data = payload.to_vec();
+        let mut records = 1;
+        let mut rest = rest;
+        loop {
+            match tls_handshakes(&data) {
+                IResult::Done(_, messages) => return IResult::Done(rest, packet(records, TlsMessage::Handshake(messages))),
+                IResult::Incomplete(_) if data.len() < MAX_HANDSHAKE_LENGTH => {}
+                _ => break,
+            }
+            match record(rest) {
+                IResult::Done(left, (22, _, payload)) => {
+                    data.extend_from_slice(payload);
+                    records += 1;
+                    rest = left;
+                }
+                IResult::Incomplete(n) => {
+                    let needed = match n {
+                        Needed::Size(size) => Needed::Size(input.len() - rest.len() + size),
+                        Needed::Unknown => Needed::Unknown,
+                    };
+                    return IResult::Incomplete(needed);
+                }
+                _ => break,
+            }
+        }
+
+        // The first record is probably encrypted
+        let (rest, _) = try_parse!(input, record);
+        IResult::Done(rest, packet(1, opaque))
+    }
+}
+
+/// Parse a record, which has to be complete
+fn record(input: &[u8]) -> IResult<&[u8], (u8, u16, &[u8])> {
+    if input.is_empty() {
+        return IResult::Incomplete(Needed::Size(RECORD_HEADER_LENGTH));
+    }
+    let (rest, (content_type, version, length)) = try_parse!(input, tuple!(be_u8, be_u16, be_u16));
+    if !(20..=24).contains(&content_type) || version >> 8 != 3 || version & 0xff > 4 ||
+       length as usize > MAX_RECORD_LENGTH {
+        return reject(input);
+    }
+    if rest.len() < length as usize {
+        return IResult::Incomplete(Needed::Size(RECORD_HEADER_LENGTH + length as usize));
+    }
+    IResult::Done(&rest[length as usize..],
+                  (content_type, version, &rest[..length as usize]))
+}
+
+/// Parse a 24 bit length
+fn be_u24(input: &[u8]) -> IResult<&[u8], usize> {
+    do_parse!(input,
+        high: be_u8 >>
+        low: be_u16 >>
+        ((high as usize) << 16 | low as usize)
+    )
+}
+
+/// Parse a list of 16 bit values
+fn u16_list(input: &[u8]) -> IResult<&[u8], Vec<u16>> {
+    if input.len() % 2 != 0 {
+        return IResult::Error(error_position!(ErrorKind::Verify, input));
+    }
+    IResult::Done(&input[input.len()..],
+                  input.chunks(2).map(|c| (c[0] as u16) << 8 | c[1] as u16).collect())
+}
+
+/// Parse all handshake messages of the input, which has to end with a complete message. This
+/// is also used for the handshake data of other transports like QUIC.
+pub fn tls_handshakes(input: &[u8]) -> IResult<&[u8], Vec<TlsHandshake>> {
+    let mut messages = vec![];
+    let mut input = input;
+    while !input.is_empty() {
+        let (rest, (kind, length)) = try_parse!(input, tuple!(be_u8, be_u24));
+        if rest.len() < length {
+            return IResult::Incomplete(Needed::Size(input.len() - rest.len() + length));
+        }
+        let body = &rest[..length];
+        let message = match kind {
+            1 => TlsHandshake::ClientHello(try_parse!(body, client_hello).1),
+            2 => TlsHandshake::ServerHello(try_parse!(body, server_hello).1),
+            11 => TlsHandshake::Certificate(try_parse!(body, certificates).1),
+            0 | 4 | 5 | 8 | 12 | 13 | 14 | 15 | 16 | 20 | 21 | 22 | 24 | 254 => {
+                TlsHandshake::Other {
+                    kind: kind,
+                    data: body.to_vec(),
+                }
+            }
+            _ => return IResult::Error(error_position!(ErrorKind::Switch, input)),
+        };
+        messages.push(message);
+        input = &rest[length..];
+    }
+    IResult::Done(input, messages)
+}
+
+/// Parse the body of a client hello
+fn client_hello(input: &[u8]) -> IResult<&[u8], TlsClientHello> {
+    do_parse!(input,
+        version: be_u16 >>
+        random: take!(32) >>
+        session_id: length_bytes!(be_u8) >>
+        cipher_suites: flat_map!(length_bytes!(be_u16), u16_list) >>
+        compression_methods: length_bytes!(be_u8) >>
+        extensions: apply!(extensions, false) >>
+        (TlsClientHello {
+            version: version,
+            random: random.to_vec(),
+            session_id: session_id.to_vec(),
+            cipher_suites: cipher_suites,
+            compression_methods: compression_methods.to_vec(),
+            extensions: extensions,
+        })
+    )
+}
+
+/// Parse the body of a server hello
+fn server_hello(input: &[u8]) -> IResult<&[u8], TlsServerHello> {
+    do_parse!(input,
+        version: be_u16 >>
+        random: take!(32) >>
+        session_id: length_bytes!(be_u8) >>
+        cipher_suite: be_u16 >>
+        compression_method: be_u8 >>
+        extensions: apply!(extensions, true) >>
+        (TlsServerHello {
+            version: version,
+            random: random.to_vec(),
+            session_id: session_id.to_vec(),
+            cipher_suite: cipher_suite,
+            compression_method: compression_method,
+            extensions: extensions,
+        })
+    )
+}
+
+/// Parse the certificate chain
+fn certificates(input: &[u8]) -> IResult<&[u8], Vec<Vec<u8>>> {
+    let (rest, length) = try_parse!(input, be_u24);
+    if rest.len() != length {
+        return IResult::Error(error_position!(ErrorKind::Verify, input));
+    }
+    let mut certificates = vec![];
+    let mut input = rest;
+    while !input.is_empty() {
+        let (rest, length) = try_parse!(input, be_u24);
+        if rest.len() < length {
+            return IResult::Error(error_position!(ErrorKind::Verify, input));
+        }
+        certificates.push(rest[..length].to_vec());
+        input = &rest[length..];
+    }
+    IResult::Done(input, certificates)
+}
+
+/// Parse the optional extensions of a hello message
+fn extensions(input: &[u8], server: bool) -> IResult<&[u8], Vec<TlsExtension>> {
+    if input.is_empty() {
+        return IResult::Done(input, vec![]);
+    }
+    let (rest, mut data) = try_parse!(input, length_bytes!(be_u16));
+    let mut extensions = vec![];
+    while !data.is_empty() {
+        let (left, (kind, body)) = try_parse!(data, tuple!(be_u16, length_bytes!(be_u16)));
+        let extension = match extension(body, kind, server) {
+            IResult::Done(&[], extension) => extension,
+            _ => {
+                TlsExtension::Unknown {
+                    kind: kind,
+                    data: body.to_vec(),
+                }
+            }
+        };
+        extensions.push(extension);
+        data = left;
+    }
+    IResult::Done(rest, extensions)
+}
+
+/// Parse the data of a single extension, the format of some depends on the sender
+fn extension(input: &[u8], kind: u16, server: bool) -> IResult<&[u8], TlsExtension> {
+    match kind {
+        // The server acknowledges the name with an empty extension
+        0 if input.is_empty() => IResult::Done(input, TlsExtension::ServerName(vec![])),
+        0 => {
+            do_parse!(input,
+                names: flat_map!(length_bytes!(be_u16), many0!(complete!(tuple!(be_u8, length_bytes!(be_u16))))) >>
+                (TlsExtension::ServerName(names.into_iter()
+                    .filter(|&(kind, _)| kind == 0)
+                    .map(|(_, name)| String::from_utf8_lossy(name).into_owned())
+                    .collect()))
+            )
+        }
+        10 => map!(input, flat_map!(length_bytes!(be_u16), u16_list), TlsExtension::SupportedGroups),
+        11 => map!(input, length_bytes!(be_u8), |f: &[u8]| TlsExtension::EcPointFormats(f.to_vec())),
+        13 => map!(input, flat_map!(length_bytes!(be_u16), u16_list), TlsExtension::SignatureAlgorithms),
+        16 => {
+            do_parse!(input,
+                protocols: flat_map!(length_bytes!(be_u16), many0!(complete!(length_bytes!(be_u8)))) >>
+                (TlsExtension::Alpn(protocols.into_iter()
+                    .map(|p| String::from_utf8_lossy(p).into_owned())
+                    .collect()))
+            )
+        }
+        43 if server => map!(input, be_u16, |v| TlsExtension::SupportedVersions(vec![v])),
+        43 => map!(input, flat_map!(length_bytes!(be_u8), u16_list), TlsExtension::SupportedVersions),
+        51 if server && input.len() == 2 => {
+            map!(input, be_u16, |g| {
+                TlsExtension::KeyShare(vec![TlsKeyShare {
+                                                group: g,
+                                                key_exchange: vec![],
+                                            }])
+            })
+        }
+        51 if server => map!(input, key_share, |s| TlsExtension::KeyShare(vec![s])),
+        51 => map!(input, flat_map!(length_bytes!(be_u16), many0!(complete!(key_share))), TlsExtension::KeyShare),
+        _ => {
+            IResult::Done(&input[input.len()..],
+                          TlsExtension::Unknown {
+                              kind: kind,
+                              data: input.to_vec(),
+                          })
+        }
+    }
+}
+
+/// Parse a key share entry
+fn key_share(input: &[u8]) -> IResult<&[u8], TlsKeyShare> {
+    do_parse!(input,
+        group: be_u16 >>
+        key_exchange: length_bytes!(be_u16) >>
+        (TlsKeyShare {
+            group: group,
+            key_exchange: key_exchange.to_vec(),
+        })
+    )
+}
@@ -0,0 +1,233 @@
+//! Tunnel protocols carrying complete inner packets
+//!
+//! The left input of the tunnel parsers will be the encapsulated packet, whose protocol is
+//! announced via `next_ethertype`. The Ethernet and IP parsers should be linked to the tunnel
+//! parsers via `Peel::link_reenter`, which marks the results of the inner packet with a deeper
+//! encapsulation level.
+use nom::{IResult, be_u8, be_u16, be_u32};
+
+use packet::prelude::*;
+
+/// The well known VXLAN port
+pub const VXLAN_PORT: u16 = 4789;
+
+/// The well known Geneve port
+pub const GENEVE_PORT: u16 = 6081;
+
+/// The GRE parser
+#[derive(Debug)]
+pub struct GreParser;
+
+/// The VXLAN parser
+#[derive(Debug)]
+pub struct VxlanParser;
+
+/// The Geneve parser
+#[derive(Debug)]
+pub struct GeneveParser;
+
+/// The parser for IPv4 and IPv6 packets carried directly by IP, like IP-in-IP or 6in4
+#[derive(Debug)]
+pub struct IpInIpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a GRE header
+pub struct GrePacket {
+    /// The checksum over the header and the payload if present
+    pub checksum: Option<u16>,
+
+    /// Indicates if the checksum is correct
+    pub checksum_valid: Option<bool>,
+
+    /// The key identifying the flow within the tunnel
+    pub key: Option<u32>,
+
+    /// The sequence number of the packet
+    pub sequence: Option<u32>,
+
+    /// The protocol of the payload
+    pub protocol: EtherType,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a VXLAN header, which always carries an Ethernet frame
+pub struct VxlanPacket {
+    /// The VXLAN network identifier
+    pub vni: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a Geneve header
+pub struct GenevePacket {
+    /// The payload is a control message
+    pub oam: bool,
+
+    /// Critical options are present
+    pub critical: bool,
+
+    /// The protocol of the payload
+    pub protocol: EtherType,
+
+    /// The virtual network identifier
+    pub vni: u32,
+
+    /// The variable length options
+    pub options: Vec<GeneveOption>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A Geneve option
+pub struct GeneveOption {
+    /// The namespace of the option type
+    pub class: u16,
+
+    /// The type of the option, where the highest bit marks critical options
+    pub kind: u8,
+
+    /// The option data
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of an IP-in-IP tunnel, which has no header of its own
+pub struct IpTunnel {
+    /// The protocol of the payload
+    pub ethertype: EtherType,
+}
+
+impl Parsable<PacketData> for GreParser {
+    /// Parse a GRE header as specified by RFC 2784 and RFC 2890 from a byte slice. The left input
+    /// will be the encapsulated packet.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the network layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ip_protocol(result) != Some(IpProtocol::Gre) {
+            return reject(input);
+        }
+
+        // Routing and other versions are not supported
+        let (rest, (flags, protocol)) = try_parse!(input, pair!(verify!(be_u16, |f: u16| f & 0x4fff == 0), be_u16));
+        let (rest, checksum) = try_parse!(rest, cond!(flags & 0x8000 != 0, terminated!(be_u16, be_u16)));
+        let (rest, key) = try_parse!(rest, cond!(flags & 0x2000 != 0, be_u32));
+        let (rest, sequence) = try_parse!(rest, cond!(flags & 0x1000 != 0, be_u32));
+
+        let checksum_valid = checksum.map(|_| internet_checksum(input) == 0);
+        if checksum_valid == Some(false) {
+            debug!("Invalid GRE checksum {:#06x}", checksum.unwrap_or_default());
+        }
+
+        IResult::Done(rest,
+                      Box::new(GrePacket {
+                          checksum: checksum,
+                          checksum_valid: checksum_valid,
+                          key: key,
+                          sequence: sequence,
+                          protocol: EtherType::from(protocol),
+                      }))
+    }
+}
+
+impl Parsable<PacketData> for VxlanParser {
+    /// Parse a VXLAN header from a byte slice. The left input will be the encapsulated frame.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or sent to the well known port, the source port is random
+        if result.map_or(false, |r| !r.is_empty()) && !transport_ports(result).map_or(false, |(_, d)| d == VXLAN_PORT) {
+            return reject(input);
+        }
+
+        let (rest, vni) = try_parse!(input,
+            do_parse!(
+                verify!(be_u8, |f: u8| f & 0x08 != 0) >>
+                take!(3) >>
+                vni: be_u32 >>
+                (vni >> 8)
+            )
+        );
+
+        IResult::Done(rest, Box::new(VxlanPacket { vni: vni }))
+    }
+}
+
+impl Parsable<PacketData> for GeneveParser {
+    /// Parse a Geneve header including its options from a byte slice. The left input will be
+    /// the encapsulated packet.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or sent to the well known port, the source port is random
+        if result.map_or(false, |r| !r.is_empty()) && !transport_ports(result).map_or(false, |(_, d)| d == GENEVE_PORT) {
+            return reject(input);
+        }
+
+        let (rest, packet) = try_parse!(input,
+            do_parse!(
+                first: verify!(be_u8, |v: u8| v >> 6 == 0) >>
+                flags: be_u8 >>
+                protocol: be_u16 >>
+                vni: be_u32 >>
+                options: flat_map!(take!((first & 0x3f) as usize * 4), geneve_options) >>
+                (GenevePacket {
+                    oam: flags & 0x80 != 0,
+                    critical: flags & 0x40 != 0,
+                    protocol: EtherType::from(protocol),
+                    vni: vni >> 8,
+                    options: options,
+                })
+            )
+        );
+
+        IResult::Done(rest, Box::new(packet))
+    }
+}
+
+impl Parsable<PacketData> for IpInIpParser {
+    /// Recognize an IPv4 or IPv6 packet carried by IP without consuming any input
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Only announced by the network layer
+        let ethertype = match next_ip_protocol(result) {
+            Some(IpProtocol::IpInIp) => EtherType::Ipv4,
+            Some(IpProtocol::Ipv6) => EtherType::Ipv6,
+            _ => return reject(input),
+        };
+        if input.is_empty() {
+            return reject(input);
+        }
+
+        IResult::Done(input, Box::new(IpTunnel { ethertype: ethertype }))
+    }
+}
+
+/// Parse the Geneve options, which fill the whole input
+fn geneve_options(mut input: &[u8]) -> IResult<&[u8], Vec<GeneveOption>> {
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (rest, option) = try_parse!(input,
+            do_parse!(
+                class: be_u16 >>
+                kind: be_u8 >>
+                data: length_bytes!(map!(be_u8, |l: u8| (l & 0x1f) as usize * 4)) >>
+                (GeneveOption {
+                    class: class,
+                    kind: kind,
+                    data: data.to_vec(),
+                })
+            )
+        );
+        options.push(option);
+        input = rest;
+    }
+    IResult::Done(input, options)
+}
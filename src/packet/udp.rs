@@ -0,0 +1,72 @@
+//! User Datagram Protocol related packets
+use nom::{IResult, be_u16};
+
+use packet::prelude::*;
+
+/// The UDP parser
+#[derive(Debug)]
+pub struct UdpParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a UDP datagram header
+pub struct UdpPacket {
+    /// The source port
+    pub src_port: u16,
+
+    /// The destination port
+    pub dst_port: u16,
+
+    /// The length of header and payload in bytes
+    pub length: u16,
+
+    /// The checksum over the pseudo header and the datagram
+    pub checksum: u16,
+
+    /// Indicates if the checksum is correct, which is only known if the preceding IP header and
+    /// the whole datagram are available and the checksum is used at all
+    pub checksum_valid: Option<bool>,
+}
+
+impl Parsable<PacketData> for UdpParser {
+    /// Parse a UDP datagram from a byte slice. The left input will be the payload, which can be
+    /// shorter than announced if the datagram was truncated.
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        // Either the first parser or announced by the network layer
+        if result.map_or(false, |r| !r.is_empty()) && next_ip_protocol(result) != Some(IpProtocol::Udp) {
+            return reject(input);
+        }
+
+        let (_, mut packet) = try_parse!(input,
+            do_parse!(
+                src_port: be_u16 >>
+                dst_port: be_u16 >>
+                length: verify!(be_u16, |l: u16| l >= 8) >>
+                checksum: be_u16 >>
+                (UdpPacket {
+                    src_port: src_port,
+                    dst_port: dst_port,
+                    length: length,
+                    checksum: checksum,
+                    checksum_valid: None,
+                })
+            )
+        );
+
+        // A zero checksum is not used, which is only allowed for IPv4
+        let length = packet.length as usize;
+        if input.len() >= length && (packet.checksum != 0 || last_result::<Ipv6Packet>(result).is_some()) {
+            packet.checksum_valid = pseudo_header_sum(result, IpProtocol::Udp, length)
+                .map(|sum| fold_checksum(ones_complement_sum(sum, &input[..length])) == 0);
+        }
+        if packet.checksum_valid == Some(false) {
+            debug!("Invalid UDP checksum {:#06x}", packet.checksum);
+        }
+
+        let end = ::std::cmp::min(length, input.len());
+        IResult::Done(&input[8..end], Box::new(packet))
+    }
+}
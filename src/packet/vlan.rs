@@ -0,0 +1,55 @@
+//! IEEE 802.1Q and IEEE 802.1ad VLAN tags
+use nom::{IResult, be_u16};
+
+use packet::prelude::*;
+
+/// The VLAN tag parser, which parses a single tag. Stacked tags are parsed by linking the parser
+/// to itself.
+#[derive(Debug)]
+pub struct VlanParser;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// Representation of a VLAN tag
+pub struct VlanTag {
+    /// The tag protocol identifier of the preceding header
+    pub tpid: EtherType,
+
+    /// The priority code point
+    pub priority: u8,
+
+    /// The drop eligible indicator
+    pub drop_eligible: bool,
+
+    /// The VLAN identifier
+    pub id: u16,
+
+    /// The protocol of the payload
+    pub ethertype: EtherType,
+}
+
+impl Parsable<PacketData> for VlanParser {
+    /// Parse a VLAN tag from a byte slice
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        let tpid = match next_ethertype(result) {
+            Some(tpid @ EtherType::Vlan) |
+            Some(tpid @ EtherType::ProviderBridging) => tpid,
+            _ => return reject(input),
+        };
+
+        do_parse!(input,
+            tci: be_u16 >>
+            ethertype: be_u16 >>
+            (Box::new(VlanTag {
+                tpid: tpid,
+                priority: (tci >> 13) as u8,
+                drop_eligible: tci & 0x1000 != 0,
+                id: tci & 0x0fff,
+                ethertype: EtherType::from(ethertype),
+            }))
+        )
+    }
+}
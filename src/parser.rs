@@ -1,9 +1,11 @@
 //! General parser descriptions and traits
 use nom::IResult;
 
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::fmt::Debug;
 
+use registry::Parameters;
+
 /// The type which will be stored within the tree structure
 pub type Parser<D> = Box<Parsable<D>>;
 
@@ -15,6 +17,24 @@ pub type ParserResultVec = Vec<ParserResult>;
 
 /// The parsing trait
 pub trait Parsable<D>: Debug {
+    /// The name of the parser, which defaults to its `Debug` representation
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    /// The parameters of the parser, which will be passed to its registered factory if the tree
+    /// gets restored from a configuration
+    fn parameters(&self) -> Parameters {
+        Parameters::new()
+    }
+
+    /// The type of the parser, which is used to find nodes by their parser type
+    fn parser_type(&self) -> TypeId
+        where Self: 'static
+    {
+        TypeId::of::<Self>()
+    }
+
     /// Parse using nom and return the result
     fn parse<'a>(&mut self,
                  input: &'a [u8],
@@ -0,0 +1,150 @@
+//! Registration of parsers by name
+use std::collections::{BTreeMap, btree_map};
+
+use petgraph::graph::NodeIndex;
+
+use prelude::*;
+use parser::Parser;
+
+/// Additional parameters which will be passed to a parser factory
+pub type Parameters = BTreeMap<String, String>;
+
+/// A closure which creates a new parser from the given parameters
+pub type Factory<D> = Box<Fn(&Parameters) -> Result<Parser<D>, PeelError> + Send + Sync>;
+
+#[derive(Clone, Debug, PartialEq)]
+/// Metadata about a registered parser
+pub struct ParserInfo {
+    /// The name of the parser, which should be equal to `Parsable::name`
+    pub name: String,
+
+    /// The version of the parser
+    pub version: String,
+
+    /// A short description what the parser does
+    pub description: String,
+}
+
+impl ParserInfo {
+    /// Create a new `ParserInfo`
+    pub fn new(name: &str, version: &str, description: &str) -> Self {
+        ParserInfo {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            description: description.to_owned(),
+        }
+    }
+}
+
+/// A registered parser factory together with its metadata
+struct Entry<D> {
+    info: ParserInfo,
+    factory: Factory<D>,
+}
+
+/// A collection of parser factories which can be looked up by name
+pub struct ParserRegistry<D> {
+    entries: BTreeMap<String, Entry<D>>,
+}
+
+impl<D> Default for ParserRegistry<D> {
+    fn default() -> Self {
+        ParserRegistry::new()
+    }
+}
+
+impl<D> ParserRegistry<D> {
+    /// Create a new empty `ParserRegistry`
+    pub fn new() -> Self {
+        ParserRegistry { entries: BTreeMap::new() }
+    }
+
+    /// Register a new factory. An already existing factory with the same name will be replaced.
+    pub fn register<F>(&mut self, info: ParserInfo, factory: F)
+        where F: Fn(&Parameters) -> Result<Parser<D>, PeelError> + Send + Sync + 'static
+    {
+        info!("Registered parser: {} ({})", info.name, info.version);
+        self.entries.insert(info.name.clone(),
+                            Entry {
+                                info: info,
+                                factory: Box::new(factory),
+                            });
+    }
+
+    /// Remove a factory from the registry and return its metadata if existing
+    pub fn unregister(&mut self, name: &str) -> Option<ParserInfo> {
+        self.entries.remove(name).map(|entry| entry.info)
+    }
+
+    /// Returns true if a factory with the given name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Get the metadata of a registered parser
+    pub fn info(&self, name: &str) -> Option<&ParserInfo> {
+        self.entries.get(name).map(|entry| &entry.info)
+    }
+
+    /// Iterate over the metadata of all registered parsers, sorted by their names
+    pub fn parsers<'a>(&'a self) -> ParserInfos<'a, D> {
+        ParserInfos { iter: self.entries.values() }
+    }
+
+    /// Create a new parser by its registered name
+    ///
+    /// # Errors
+    /// When no factory was registered for the name or the factory itself fails.
+    pub fn create(&self, name: &str, parameters: &Parameters) -> Result<Parser<D>, PeelError> {
+        match self.entries.get(name) {
+            Some(entry) => (entry.factory)(parameters),
+            None => {
+                Err(PeelError::new(ErrorType::UnknownParser,
+                                   &format!("No parser registered as '{}'", name)))
+            }
+        }
+    }
+}
+
+/// Iterator over the metadata of all registered parsers
+pub struct ParserInfos<'a, D: 'a> {
+    iter: btree_map::Values<'a, String, Entry<D>>,
+}
+
+impl<'a, D> Iterator for ParserInfos<'a, D> {
+    type Item = &'a ParserInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|entry| &entry.info)
+    }
+}
+
+impl<D> Peel<D> {
+    /// Create a new parser by its registered name and return a corresponding Node
+    ///
+    /// # Errors
+    /// When the registry was not able to create the parser.
+    pub fn new_parser_by_name(&mut self,
+                              registry: &ParserRegistry<D>,
+                              name: &str,
+                              parameters: &Parameters)
+                              -> Result<NodeIndex, PeelError> {
+        let parser = registry.create(name, parameters)?;
+        Ok(self.new_boxed_parser(parser))
+    }
+
+    /// Create a new parser by its registered name and link it with the provided node
+    ///
+    /// # Errors
+    /// When the registry was not able to create the parser.
+    pub fn link_new_parser_by_name(&mut self,
+                                   left: NodeIndex,
+                                   registry: &ParserRegistry<D>,
+                                   name: &str,
+                                   parameters: &Parameters)
+                                   -> Result<NodeIndex, PeelError> {
+        let new_parser = self.new_parser_by_name(registry, name, parameters)?;
+        self.link(left, new_parser);
+        Ok(new_parser)
+    }
+}
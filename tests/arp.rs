@@ -0,0 +1,105 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+/// Create an Ethernet frame carrying an ARP packet
+fn arp_frame(operation: u8, sender_mac: [u8; 6], sender_ip: [u8; 4], target_ip: [u8; 4]) -> Vec<u8> {
+    let mut frame = vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    frame.extend_from_slice(&sender_mac);
+    frame.extend_from_slice(&[0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, operation]);
+    frame.extend_from_slice(&sender_mac);
+    frame.extend_from_slice(&sender_ip);
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&target_ip);
+    frame.extend_from_slice(&[0; 18]);
+    frame
+}
+
+const HOST: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+const ATTACKER: [u8; 6] = [0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb];
+
+#[test]
+fn arp_success_request() {
+    let mut peel = packet_peel();
+    let input = arp_frame(1, HOST, [192, 168, 0, 1], [192, 168, 0, 2]);
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input.len(), 18);
+    let arp = result.result[1].downcast_ref::<ArpPacket>().unwrap();
+    assert_eq!(arp.operation, ArpOperation::Request);
+    assert_eq!(arp.sender_mac, MacAddress(HOST));
+    assert_eq!(arp.sender_ip, Ipv4Addr::new(192, 168, 0, 1));
+    assert_eq!(arp.target_ip, Ipv4Addr::new(192, 168, 0, 2));
+    assert!(!arp.is_gratuitous());
+    assert!(arp.conflict.is_none());
+
+    let neighbors = peel.data.as_ref().unwrap().neighbors.as_ref().unwrap();
+    assert_eq!(neighbors.lookup(&IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1))), Some(MacAddress(HOST)));
+}
+
+#[test]
+fn arp_success_conflict() {
+    let mut peel = packet_peel();
+    peel.traverse(&arp_frame(2, HOST, [192, 168, 0, 1], [192, 168, 0, 2]), vec![]);
+    let input = arp_frame(2, ATTACKER, [192, 168, 0, 1], [192, 168, 0, 1]);
+    let result = peel.traverse(&input, vec![]);
+    let arp = result.result[1].downcast_ref::<ArpPacket>().unwrap();
+    assert!(arp.is_gratuitous());
+    assert_eq!(arp.conflict,
+               Some(NeighborConflict {
+                   ip: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+                   known: MacAddress(HOST),
+                   announced: MacAddress(ATTACKER),
+               }));
+}
+
+#[test]
+fn arp_success_probe() {
+    let mut peel = packet_peel();
+    peel.traverse(&arp_frame(1, HOST, [0, 0, 0, 0], [192, 168, 0, 1]), vec![]);
+    assert!(peel.data.as_ref().unwrap().neighbors.as_ref().unwrap().is_empty());
+}
+
+#[test]
+fn arp_success_cache_timeout() {
+    let mut cache = NeighborCache::new(Duration::from_secs(60), 16);
+    let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let now = Instant::now();
+    assert!(cache.learn(ip, MacAddress(HOST), now).is_none());
+    assert!(cache.learn(ip, MacAddress(HOST), now).is_none());
+    assert!(cache.learn(ip, MacAddress(ATTACKER), now + Duration::from_secs(59)).is_some());
+    assert!(cache.learn(ip, MacAddress(HOST), now + Duration::from_secs(120)).is_none());
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.expire(now + Duration::from_secs(179)), 0);
+    assert_eq!(cache.expire(now + Duration::from_secs(180)), 1);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn arp_success_cache_limit() {
+    let mut cache = NeighborCache::new(Duration::from_secs(60), 2);
+    let now = Instant::now();
+    let ips: Vec<IpAddr> = (1..4).map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i))).collect();
+    cache.learn(ips[0], MacAddress(HOST), now);
+    cache.learn(ips[1], MacAddress(HOST), now + Duration::from_secs(1));
+
+    // Known addresses are updated, new ones replace the oldest mapping
+    cache.learn(ips[0], MacAddress(HOST), now + Duration::from_secs(2));
+    cache.learn(ips[2], MacAddress(ATTACKER), now + Duration::from_secs(3));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.lookup(&ips[0]), Some(MacAddress(HOST)));
+    assert!(cache.lookup(&ips[1]).is_none());
+    assert_eq!(cache.lookup(&ips[2]), Some(MacAddress(ATTACKER)));
+}
+
+#[test]
+fn arp_failure_unsupported_hardware() {
+    let mut peel = packet_peel();
+    let mut input = arp_frame(1, HOST, [192, 168, 0, 1], [192, 168, 0, 2]);
+    input[15] = 6;
+    let result = peel.traverse(&input, vec![]);
+    assert_eq!(result.result.len(), 1);
+    assert!(peel.data.as_ref().unwrap().neighbors.as_ref().unwrap().is_empty());
+}
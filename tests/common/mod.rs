@@ -0,0 +1,55 @@
+//! Packet builders shared by the integration tests
+#![allow(dead_code)]
+use peel::packet::prelude::*;
+
+/// The address of the client side
+pub const CLIENT: [u8; 4] = [192, 168, 0, 1];
+
+/// The address of the server side
+pub const SERVER: [u8; 4] = [10, 0, 0, 1];
+
+/// Create an IPv4 header for the given protocol and payload length
+fn ipv4(protocol: u8, src: [u8; 4], dst: [u8; 4], length: usize) -> Vec<u8> {
+    let total_length = 20 + length;
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0, 0, 0x40, 0, 64, protocol, 0, 0];
+    packet.extend_from_slice(&src);
+    packet.extend_from_slice(&dst);
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet
+}
+
+/// Create an IPv4 packet carrying a UDP datagram with a valid checksum
+pub fn ipv4_udp(src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let length = 8 + payload.len();
+    let mut packet = ipv4(17, src, dst, length);
+
+    let mut datagram = vec![(src_port >> 8) as u8, src_port as u8, (dst_port >> 8) as u8, dst_port as u8,
+                            (length >> 8) as u8, length as u8, 0, 0];
+    datagram.extend_from_slice(payload);
+    let mut pseudo_header = src.to_vec();
+    pseudo_header.extend_from_slice(&dst);
+    pseudo_header.extend_from_slice(&[0, 17, (length >> 8) as u8, length as u8]);
+    let checksum = fold_checksum(ones_complement_sum(ones_complement_sum(0, &pseudo_header), &datagram));
+    datagram[6] = (checksum >> 8) as u8;
+    datagram[7] = checksum as u8;
+
+    packet.extend_from_slice(&datagram);
+    packet
+}
+
+/// Create an IPv4 packet carrying a TCP segment from the client port 50000 to the server
+pub fn ipv4_tcp(dst_port: u16, sequence: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = ipv4(6, CLIENT, SERVER, 20 + payload.len());
+    packet.extend_from_slice(&[0xc3, 0x50, (dst_port >> 8) as u8, dst_port as u8, (sequence >> 24) as u8,
+                               (sequence >> 16) as u8, (sequence >> 8) as u8, sequence as u8, 0, 0, 0, 0, 0x50,
+                               flags, 0xff, 0xff, 0, 0, 0, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Decode a string of hex digits
+pub fn hex(data: &str) -> Vec<u8> {
+    (0..data.len()).step_by(2).map(|i| u8::from_str_radix(&data[i..i + 2], 16).unwrap()).collect()
+}
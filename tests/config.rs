@@ -0,0 +1,199 @@
+extern crate peel;
+use peel::example::prelude::*;
+use peel::prelude::{TreeConfig, ErrorType, ParserRegistry, ParserInfo, Parameters, PeelError};
+
+extern crate nom;
+use nom::{ErrorKind, Needed};
+
+static CONFIG: &str = r#"
+[roots]
+second = "two"
+
+[[parser]]
+id = "one"
+parser = "Parser1"
+children = ["three", "two"]
+
+[[parser]]
+id = "two"
+parser = "Parser2"
+children = ["three"]
+
+[[parser]]
+id = "three"
+parser = "Parser3"
+children = ["three", "four"]
+
+[[parser]]
+id = "four"
+parser = "Parser4"
+"#;
+
+#[test]
+fn config_success_build_tree() {
+    let config = TreeConfig::from_toml(CONFIG).unwrap();
+    let mut peel = Peel::from_config(&config, &example_registry()).unwrap();
+    assert_eq!(peel.graph.node_count(), 4);
+    assert_eq!(peel.graph.edge_count(), 5);
+    assert_eq!(peel.traverse(b"1234", vec![]).result.len(), 4);
+    assert_eq!(peel.traverse_root("second", b"234", vec![]).result.len(), 3);
+}
+
+#[test]
+fn config_success_child_order() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[0].children = vec!["two".to_owned()];
+    let peel = Peel::from_config(&config, &example_registry()).unwrap();
+    let root = peel.root.unwrap();
+    let mut dot = vec![];
+    peel.write_dot(&mut dot, None).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains(&format!("{} -> 1 [label=\"0\"]", root.index())));
+}
+
+#[test]
+fn config_success_disabled_node() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[1].enabled = false;
+    let mut peel = Peel::from_config(&config, &example_registry()).unwrap();
+    assert_eq!(peel.graph.node_count(), 3);
+    assert!(peel.get_root("second").is_none());
+    assert_eq!(peel.traverse(b"1234", vec![]).result.len(), 1);
+}
+
+#[test]
+fn config_success_to_toml() {
+    let config = TreeConfig::from_toml(CONFIG).unwrap();
+    let output = config.to_toml().unwrap();
+    assert_eq!(TreeConfig::from_toml(&output).unwrap(), config);
+}
+
+#[test]
+fn config_success_reenter() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[2].reenter = vec!["three".to_owned()];
+    let mut peel = Peel::from_config(&config, &example_registry()).unwrap();
+    assert_eq!(peel.to_config().parsers[2].reenter, vec!["2".to_owned()]);
+
+    let result = peel.traverse(b"13334", vec![]);
+    assert_eq!(result.result.len(), 5);
+    assert_eq!(result.depths, vec![0, 0, 1, 2, 2]);
+
+    // Deeper encapsulations are skipped
+    peel.max_depth = 1;
+    assert_eq!(peel.traverse(b"13334", vec![]).depths, vec![0, 0, 1]);
+
+    let mut dot = vec![];
+    peel.write_dot(&mut dot, None).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("2 -> 2 [label=\"0\", style=dashed]"));
+    assert!(dot.contains("2 -> 3 [label=\"1\"]"));
+}
+
+#[test]
+fn config_failure_reenter_no_child() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[1].reenter = vec!["four".to_owned()];
+    let error = Peel::from_config(&config, &example_registry()).err().unwrap();
+    assert_eq!(error.code, ErrorType::InvalidConfig);
+}
+
+#[test]
+fn config_failure_unknown_node() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[0].children.push("five".to_owned());
+    let error = Peel::from_config(&config, &example_registry()).err().unwrap();
+    assert_eq!(error.code, ErrorType::InvalidConfig);
+    assert_eq!(error.description, "Unknown node identifier 'five'");
+
+    // Roots have to point to existing nodes as well
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.roots.insert("sixth".to_owned(), "six".to_owned());
+    let error = Peel::from_config(&config, &example_registry()).err().unwrap();
+    assert_eq!(error.code, ErrorType::InvalidConfig);
+    assert_eq!(error.description, "Unknown node identifier 'six'");
+}
+
+#[test]
+fn config_failure_unknown_parser() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[0].parser = "Parser5".to_owned();
+    let error = Peel::from_config(&config, &example_registry()).err().unwrap();
+    assert_eq!(error.code, ErrorType::UnknownParser);
+    assert_eq!(error.description, "No parser registered as 'Parser5'");
+}
+
+#[test]
+fn config_failure_invalid_toml() {
+    let error = TreeConfig::from_toml("[[parser]]\nid = 1").err().unwrap();
+    assert_eq!(error.code, ErrorType::Other);
+}
+
+#[derive(Debug)]
+struct ByteParser(u8);
+
+#[derive(Debug, PartialEq)]
+struct ByteParserResult(u8);
+
+impl Parsable<()> for ByteParser {
+    fn name(&self) -> String {
+        "ByteParser".to_owned()
+    }
+
+    fn parameters(&self) -> Parameters {
+        let mut parameters = Parameters::new();
+        parameters.insert("byte".to_owned(), self.0.to_string());
+        parameters
+    }
+
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 _: Option<&ParserResultVec>,
+                 _: Option<&mut ()>)
+                 -> IResult<&'a [u8], ParserResult> {
+        match input.first() {
+            Some(byte) if *byte == self.0 => IResult::Done(&input[1..], Box::new(ByteParserResult(*byte))),
+            Some(_) => IResult::Error(nom::Err::Position(ErrorKind::Custom(0), input)),
+            None => IResult::Incomplete(Needed::Size(1)),
+        }
+    }
+}
+
+fn byte_registry() -> ParserRegistry<()> {
+    let mut registry = example_registry();
+    registry.register(ParserInfo::new("ByteParser", "1.0.0", "Parses a configurable byte"),
+                      |parameters| match parameters.get("byte").and_then(|b| b.parse().ok()) {
+                          Some(byte) => Ok(Box::new(ByteParser(byte))),
+                          None => Err(PeelError::new(ErrorType::InvalidConfig, "Invalid byte")),
+                      });
+    registry
+}
+
+#[test]
+fn config_success_save_and_restore() {
+    let mut peel = peel_example();
+    let last_node = peel.graph.node_indices().next_back().unwrap();
+    let byte_node = peel.link_new_parser(last_node, ByteParser(b'5'));
+    peel.add_root("byte", byte_node);
+
+    let path = std::env::temp_dir().join("peel_test_tree.toml");
+    peel.to_config_file(&path).unwrap();
+
+    let mut restored = Peel::from_config_file(&path, &byte_registry()).unwrap();
+    assert_eq!(restored.graph.node_count(), 5);
+    let result = restored.traverse(b"12345", vec![]).result;
+    assert_eq!(result.len(), 5);
+    assert_eq!(result[4].downcast_ref::<ByteParserResult>(),
+               Some(&ByteParserResult(b'5')));
+    assert_eq!(restored.traverse_root("byte", b"5", vec![]).result.len(), 1);
+    assert_eq!(restored.to_config().to_toml().unwrap(),
+               peel.to_config().to_toml().unwrap());
+}
+
+#[test]
+fn config_failure_invalid_parameters() {
+    let mut config = TreeConfig::from_toml(CONFIG).unwrap();
+    config.parsers[3].parser = "ByteParser".to_owned();
+    let error = Peel::from_config(&config, &byte_registry()).err().unwrap();
+    assert_eq!(error.code, ErrorType::InvalidConfig);
+}
@@ -0,0 +1,224 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+mod common;
+use common::ipv4_udp;
+
+/// Create an IPv6 packet carrying a UDP datagram from port 546 to 547
+fn ipv6_udp(payload: &[u8]) -> Vec<u8> {
+    let length = 8 + payload.len();
+    let mut packet = vec![0x60, 0, 0, 0, (length >> 8) as u8, length as u8, 17, 1];
+    packet.extend_from_slice(&[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    packet.extend_from_slice(&[0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2]);
+    packet.extend_from_slice(&[2, 0x22, 2, 0x23, (length >> 8) as u8, length as u8, 0, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// A DHCPv4 request for 192.168.0.10 forwarded by a relay agent
+fn dhcpv4_request() -> Vec<u8> {
+    let mut message = vec![1, 1, 6, 1, 0x39, 0x03, 0xf3, 0x26, 0, 3, 0x80, 0];
+    message.extend_from_slice(&[0; 12]);
+    message.extend_from_slice(&[10, 0, 0, 1]);
+    message.extend_from_slice(&[0x00, 0x0c, 0x29, 0x12, 0x34, 0x56]);
+    message.extend_from_slice(&[0; 10]);
+    message.extend_from_slice(b"server");
+    message.extend_from_slice(&[0; 58 + 128]);
+    message.extend_from_slice(&[0x63, 0x82, 0x53, 0x63]);
+    message.extend_from_slice(&[53, 1, 3]);
+    message.extend_from_slice(&[61, 7, 1, 0x00, 0x0c, 0x29, 0x12, 0x34, 0x56]);
+    message.extend_from_slice(&[50, 4, 192, 168, 0, 10]);
+    message.extend_from_slice(&[54, 4, 192, 168, 0, 1]);
+    message.extend_from_slice(&[0, 0]);
+    message.extend_from_slice(&[51, 4, 0, 0, 0x0e, 0x10]);
+    message.extend_from_slice(&[82, 9, 1, 3, b'e', b't', b'h', 2, 2, 0xab, 0xcd]);
+    message.extend_from_slice(&[53, 2, 1, 1]);
+    message.extend_from_slice(&[255, 0, 0, 0]);
+    message
+}
+
+/// Prefix the data with a DHCPv6 option header
+fn dhcpv6_option(code: u16, data: &[u8]) -> Vec<u8> {
+    let mut option = vec![(code >> 8) as u8, code as u8, (data.len() >> 8) as u8, data.len() as u8];
+    option.extend_from_slice(data);
+    option
+}
+
+/// A DHCPv6 solicit asking for a non-temporary address
+fn dhcpv6_solicit() -> Vec<u8> {
+    let mut message = vec![1, 0x10, 0x08, 0x74];
+    message.extend_from_slice(&dhcpv6_option(1, &[0, 1, 0, 1, 0x1c, 0x39, 0xcf, 0x88, 0x08, 0, 0x27, 0xfe, 0x8f,
+                                                  0x95]));
+    message.extend_from_slice(&dhcpv6_option(6, &[0, 23, 0, 24]));
+    message.extend_from_slice(&dhcpv6_option(8, &[0, 0]));
+    let mut address = vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10];
+    address.extend_from_slice(&[0, 0, 0x0e, 0x10, 0, 0, 0x1c, 0x20]);
+    address.extend_from_slice(&dhcpv6_option(13, b"\x00\x00ok"));
+    let mut ia_na = vec![0x0e, 0x00, 0x27, 0xfe, 0, 0, 0x0e, 0x10, 0, 0, 0x15, 0x18];
+    ia_na.extend_from_slice(&dhcpv6_option(5, &address));
+    message.extend_from_slice(&dhcpv6_option(3, &ia_na));
+    message
+}
+
+/// Wrap the message into a relay forward message
+fn dhcpv6_relay(hop_count: u8, message: &[u8]) -> Vec<u8> {
+    let mut relay = vec![12, hop_count];
+    relay.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    relay.extend_from_slice(&[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    relay.extend_from_slice(&dhcpv6_option(18, b"eth0"));
+    relay.extend_from_slice(&dhcpv6_option(9, message));
+    relay
+}
+
+#[test]
+fn dhcpv4_success_request() {
+    let mut peel = packet_peel();
+    let input = ipv4_udp([0, 0, 0, 0], [255, 255, 255, 255], 67, 67, &dhcpv4_request());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert!(result.left_input.is_empty());
+    assert_eq!(result.result.len(), 3);
+
+    let packet = result.result[2].downcast_ref::<Dhcpv4Packet>().unwrap();
+    assert!(!packet.reply);
+    assert_eq!(packet.hops, 1);
+    assert_eq!(packet.transaction_id, 0x3903f326);
+    assert_eq!(packet.seconds, 3);
+    assert!(packet.broadcast);
+    assert_eq!(packet.relay_address, Ipv4Addr::new(10, 0, 0, 1));
+    assert_eq!(packet.client_hardware_address, vec![0x00, 0x0c, 0x29, 0x12, 0x34, 0x56]);
+    assert_eq!(packet.server_name, "server");
+    assert_eq!(packet.boot_file, "");
+
+    assert_eq!(packet.message_type(), Some(Dhcpv4MessageType::Request));
+    assert_eq!(packet.requested_address(), Some(Ipv4Addr::new(192, 168, 0, 10)));
+    assert_eq!(packet.server_identifier(), Some(Ipv4Addr::new(192, 168, 0, 1)));
+    assert_eq!(packet.lease_time(), Some(3600));
+    assert_eq!(packet.options.len(), 7);
+    assert_eq!(packet.options[1],
+               Dhcpv4Option::ClientIdentifier {
+                   kind: 1,
+                   id: vec![0x00, 0x0c, 0x29, 0x12, 0x34, 0x56],
+               });
+    assert_eq!(packet.options[5],
+               Dhcpv4Option::RelayAgent(vec![Dhcpv4SubOption {
+                                                 code: 1,
+                                                 data: b"eth".to_vec(),
+                                             },
+                                             Dhcpv4SubOption {
+                                                 code: 2,
+                                                 data: vec![0xab, 0xcd],
+                                             }]));
+
+    // Options with an unexpected length are passed through
+    assert_eq!(packet.options[6],
+               Dhcpv4Option::Unknown {
+                   code: 53,
+                   data: vec![1, 1],
+               });
+}
+
+#[test]
+fn dhcpv4_failure_invalid() {
+    let mut peel = packet_peel();
+    let mut message = dhcpv4_request();
+    let input = ipv4_udp([0, 0, 0, 0], [255, 255, 255, 255], 1067, 1068, &message);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // Wrong magic cookie
+    message[236] = 0;
+    let input = ipv4_udp([0, 0, 0, 0], [255, 255, 255, 255], 68, 67, &message);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // Missing end option
+    let message = dhcpv4_request();
+    let input = ipv4_udp([0, 0, 0, 0], [255, 255, 255, 255], 68, 67, &message[..message.len() - 4]);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+}
+
+#[test]
+fn dhcpv6_success_solicit() {
+    let mut peel = packet_peel();
+    let input = ipv6_udp(&dhcpv6_solicit());
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 3);
+
+    let packet = result.result[2].downcast_ref::<Dhcpv6Packet>().unwrap();
+    assert_eq!(packet.kind, Dhcpv6MessageType::Solicit);
+    assert_eq!(packet.transaction_id, 0x100874);
+    assert!(packet.relay.is_none());
+    assert_eq!(packet.client_id(),
+               Some(&Duid::LinkLayerTime {
+                   hardware_type: 1,
+                   time: 0x1c39cf88,
+                   address: vec![0x08, 0, 0x27, 0xfe, 0x8f, 0x95],
+               }));
+    assert!(packet.server_id().is_none());
+    assert_eq!(packet.options[1], Dhcpv6Option::OptionRequest(vec![23, 24]));
+    assert_eq!(packet.options[2], Dhcpv6Option::ElapsedTime(0));
+    assert_eq!(packet.options[3],
+               Dhcpv6Option::IaNa {
+                   iaid: 0x0e0027fe,
+                   t1: 3600,
+                   t2: 5400,
+                   options: vec![Dhcpv6Option::IaAddress {
+                                     address: "2001:db8::10".parse::<Ipv6Addr>().unwrap(),
+                                     preferred_lifetime: 3600,
+                                     valid_lifetime: 7200,
+                                     options: vec![Dhcpv6Option::StatusCode {
+                                                       code: 0,
+                                                       message: "ok".to_owned(),
+                                                   }],
+                                 }],
+               });
+}
+
+#[test]
+fn dhcpv6_success_nested_relay() {
+    let mut peel = Peel::new();
+    peel.new_root("dhcpv6", Dhcpv6Parser);
+    let input = dhcpv6_relay(1, &dhcpv6_relay(0, &dhcpv6_solicit()));
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+
+    let packet = result.result[0].downcast_ref::<Dhcpv6Packet>().unwrap();
+    assert_eq!(packet.kind, Dhcpv6MessageType::RelayForward);
+    assert_eq!(packet.relay,
+               Some(Dhcpv6Relay {
+                   hop_count: 1,
+                   link_address: "2001:db8::1".parse::<Ipv6Addr>().unwrap(),
+                   peer_address: "fe80::1".parse::<Ipv6Addr>().unwrap(),
+               }));
+    assert_eq!(packet.options[0], Dhcpv6Option::InterfaceId(b"eth0".to_vec()));
+    assert_eq!(packet.relayed().and_then(|p| p.relay.as_ref()).map(|r| r.hop_count), Some(0));
+
+    let client = packet.innermost();
+    assert_eq!(client.kind, Dhcpv6MessageType::Solicit);
+    assert!(client.client_id().is_some());
+}
+
+#[test]
+fn dhcpv6_failure_invalid() {
+    let mut peel = Peel::new();
+    peel.new_root("dhcpv6", Dhcpv6Parser);
+    let mut message = dhcpv6_solicit();
+    message[0] = 42;
+    assert!(peel.traverse(&message, vec![]).error.is_some());
+
+    // Truncated options
+    let message = dhcpv6_solicit();
+    assert!(peel.traverse(&message[..message.len() - 1], vec![]).error.is_some());
+
+    // Relay messages nested too deeply are not interpreted
+    let mut message = dhcpv6_solicit();
+    for _ in 0..40 {
+        message = dhcpv6_relay(0, &message);
+    }
+    let result = peel.traverse(&message, vec![]);
+    assert!(result.error.is_none());
+    let packet = result.result[0].downcast_ref::<Dhcpv6Packet>().unwrap();
+    assert_eq!(packet.innermost().kind, Dhcpv6MessageType::RelayForward);
+}
@@ -0,0 +1,171 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+mod common;
+use common::{ipv4_tcp, ipv4_udp, CLIENT, SERVER};
+
+/// A query for the A record of www.example.com with an EDNS0 record
+fn query() -> Vec<u8> {
+    let mut message = vec![0x12, 0x34, 0x01, 0x20, 0, 1, 0, 0, 0, 0, 0, 1];
+    message.extend_from_slice(b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+    message.extend_from_slice(&[0, 0, 41, 0x10, 0x00, 0, 0, 0x80, 0, 0, 8, 0, 10, 0, 4, 1, 2, 3, 4]);
+    message
+}
+
+/// A response using compressed names
+fn response() -> Vec<u8> {
+    let mut message = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 5, 0, 1, 0, 1];
+    message.extend_from_slice(b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+    // CNAME to cdn.example.com
+    message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0x0e, 0x10, 0, 6, 3, b'c', b'd', b'n', 0xc0, 16]);
+    // A of cdn.example.com
+    message.extend_from_slice(&[0xc0, 45, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34]);
+    // AAAA of cdn.example.com
+    message.extend_from_slice(&[0xc0, 45, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0,
+                                0, 0, 0, 0, 0, 0, 1]);
+    // TXT with two strings
+    message.extend_from_slice(&[0xc0, 16, 0, 16, 0, 1, 0, 0, 0, 60, 0, 6, 2, b'h', b'i', 2, b'y', b'o']);
+    // Unknown record type
+    message.extend_from_slice(&[0xc0, 16, 0x01, 0x01, 0, 1, 0, 0, 0, 60, 0, 2, 0xab, 0xcd]);
+    // SOA in the authority section
+    message.extend_from_slice(&[0xc0, 16, 0, 6, 0, 1, 0, 0, 0, 60, 0, 27, 2, b'n', b's', 0xc0, 16, 0xc0, 16,
+                                0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5]);
+    // SRV in the additional section
+    message.extend_from_slice(&[0xc0, 16, 0, 33, 0, 1, 0, 0, 0, 60, 0, 8, 0, 10, 0, 20, 0x13, 0xc4, 0xc0, 45]);
+    message
+}
+
+#[test]
+fn dns_success_query() {
+    let mut peel = packet_peel();
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 53, &query());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 3);
+    assert!(result.left_input.is_empty());
+    let dns = result.result[2].downcast_ref::<DnsPacket>().unwrap();
+    assert_eq!(dns.id, 0x1234);
+    assert!(!dns.response);
+    assert!(dns.recursion_desired);
+    assert!(dns.authentic_data);
+    assert_eq!(dns.questions,
+               vec![DnsQuestion {
+                        name: "www.example.com".to_owned(),
+                        kind: DnsRecordType::A,
+                        class: 1,
+                    }]);
+    let opt = &dns.additionals[0];
+    assert_eq!(opt.name, ".");
+    assert_eq!(opt.kind(), DnsRecordType::Opt);
+    assert_eq!(opt.class, 4096);
+    assert_eq!(opt.ttl, 0x8000);
+    assert_eq!(opt.data,
+               DnsRecordData::Opt(vec![EdnsOption {
+                                           code: 10,
+                                           data: vec![1, 2, 3, 4],
+                                       }]));
+}
+
+#[test]
+fn dns_success_response() {
+    let mut peel = packet_peel();
+    let input = ipv4_udp(CLIENT, SERVER, 53, 50000, &response());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    let dns = result.result[2].downcast_ref::<DnsPacket>().unwrap();
+    assert!(dns.response);
+    assert!(dns.recursion_available);
+    assert_eq!(dns.rcode, 0);
+    assert_eq!(dns.answers.len(), 5);
+    assert_eq!(dns.answers[0].name, "www.example.com");
+    assert_eq!(dns.answers[0].ttl, 3600);
+    assert_eq!(dns.answers[0].data, DnsRecordData::Cname("cdn.example.com".to_owned()));
+    assert_eq!(dns.answers[1].name, "cdn.example.com");
+    assert_eq!(dns.answers[1].data, DnsRecordData::A(Ipv4Addr::new(93, 184, 216, 34)));
+    assert_eq!(dns.answers[2].data, DnsRecordData::Aaaa("2001:db8::1".parse::<Ipv6Addr>().unwrap()));
+    assert_eq!(dns.answers[3].data, DnsRecordData::Txt(vec![b"hi".to_vec(), b"yo".to_vec()]));
+    assert_eq!(dns.answers[4].data,
+               DnsRecordData::Unknown {
+                   kind: 257,
+                   data: vec![0xab, 0xcd],
+               });
+    assert_eq!(dns.authorities[0].data,
+               DnsRecordData::Soa {
+                   mname: "ns.example.com".to_owned(),
+                   rname: "example.com".to_owned(),
+                   serial: 1,
+                   refresh: 2,
+                   retry: 3,
+                   expire: 4,
+                   minimum: 5,
+               });
+    assert_eq!(dns.additionals[0].data,
+               DnsRecordData::Srv {
+                   priority: 10,
+                   weight: 20,
+                   port: 5060,
+                   target: "cdn.example.com".to_owned(),
+               });
+}
+
+#[test]
+fn dns_success_tcp_stream() {
+    let mut peel = packet_peel();
+    let mut stream = vec![0, query().len() as u8];
+    stream.extend_from_slice(&query());
+    stream.extend_from_slice(&[0, response().len() as u8]);
+    stream.extend_from_slice(&response());
+
+    // The first message is split across two segments
+    let input = ipv4_tcp(53, 1000, 0x18, &stream[..20]);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_some());
+    let input = ipv4_tcp(53, 1020, 0x18, &stream[20..]);
+    peel.traverse_root("ipv4", &input, vec![]);
+
+    let mut messages = vec![];
+    peel.traverse_streams(|_, result| {
+        assert!(result.error.is_none());
+        messages.push(result.result[1].downcast_ref::<DnsPacket>().unwrap().clone());
+    });
+    assert_eq!(messages.len(), 2);
+    assert!(!messages[0].response);
+    assert_eq!(messages[1].answers.len(), 5);
+}
+
+#[test]
+fn dns_failure_compression_loop() {
+    let mut peel = packet_peel();
+    let mut message = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    message.extend_from_slice(&[1, b'a', 0xc0, 12, 0, 1, 0, 1]);
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 53, &message);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+
+    // Forward pointers are rejected as well
+    let mut message = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    message.extend_from_slice(&[0xc0, 16, 0, 1, 0, 1]);
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 53, &message);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+}
+
+#[test]
+fn dns_failure_other_port() {
+    let mut peel = packet_peel();
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 5353, &query());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+}
+
+#[test]
+fn dns_failure_truncated() {
+    let mut peel = packet_peel();
+    let message = query();
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 53, &message[..20]);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+    assert!(result.error.is_none());
+}
@@ -0,0 +1,99 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+static ETHERNET_HEADER: &[u8] = &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, // dst
+                                  0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb]; // src
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = ETHERNET_HEADER.to_vec();
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[test]
+fn ethernet_success_ethernet2() {
+    let mut peel = packet_peel();
+    let input = frame(&[0x08, 0x00, 0x45]);
+    let result = peel.traverse(&input, vec![]);
+    assert_eq!(result.left_input, &[0x45]);
+    let ethernet = result.result[0].downcast_ref::<EthernetPacket>().unwrap();
+    assert_eq!(ethernet.dst.to_string(), "00:11:22:33:44:55");
+    assert_eq!(ethernet.src, MacAddress([0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb]));
+    assert_eq!(ethernet.ethertype, Some(EtherType::Ipv4));
+    assert!(ethernet.llc.is_none());
+}
+
+#[test]
+fn ethernet_success_snap() {
+    let mut peel = packet_peel();
+    let input = frame(&[0x00, 0x09, 0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x86, 0xdd, 0x60, 0xff, 0xff]);
+    let result = peel.traverse(&input, vec![]);
+    assert_eq!(result.left_input, &[0x60]);
+    let ethernet = result.result[0].downcast_ref::<EthernetPacket>().unwrap();
+    assert_eq!(ethernet.length, Some(9));
+    assert_eq!(ethernet.ethertype, Some(EtherType::Ipv6));
+    let llc = ethernet.llc.as_ref().unwrap();
+    assert_eq!(llc.control, 0x03);
+    assert_eq!(llc.snap.as_ref().unwrap().protocol, 0x86dd);
+}
+
+#[test]
+fn ethernet_success_llc() {
+    let mut peel = packet_peel();
+    let input = frame(&[0x00, 0x05, 0x42, 0x42, 0x03, 0x00, 0x00]);
+    let result = peel.traverse(&input, vec![]);
+    assert_eq!(result.result.len(), 1);
+    let ethernet = result.result[0].downcast_ref::<EthernetPacket>().unwrap();
+    assert_eq!(ethernet.ethertype, None);
+    assert_eq!(ethernet.llc.as_ref().unwrap().dsap, 0x42);
+}
+
+#[test]
+fn ethernet_success_qinq() {
+    let mut peel = packet_peel();
+    let input = frame(&[0x88, 0xa8, 0xa0, 0x64, 0x81, 0x00, 0x10, 0x0a, 0x08, 0x00]);
+    let result = peel.traverse(&input, vec![]).result;
+    assert_eq!(result.len(), 3);
+    let outer = result[1].downcast_ref::<VlanTag>().unwrap();
+    assert_eq!(outer.tpid, EtherType::ProviderBridging);
+    assert_eq!(outer.priority, 5);
+    assert_eq!(outer.id, 100);
+    let inner = result[2].downcast_ref::<VlanTag>().unwrap();
+    assert_eq!(inner.tpid, EtherType::Vlan);
+    assert!(inner.drop_eligible);
+    assert_eq!(inner.id, 10);
+    assert_eq!(next_ethertype(Some(&result)), Some(EtherType::Ipv4));
+}
+
+#[test]
+fn ethernet_success_mpls() {
+    let mut peel = packet_peel();
+    let input = frame(&[0x88, 0x47, 0x00, 0x01, 0x00, 0x40, 0x00, 0x02, 0x01, 0x3f, 0x45]);
+    let result = peel.traverse(&input, vec![]);
+    assert_eq!(result.left_input, &[0x45]);
+    let mpls = result.result[1].downcast_ref::<MplsPacket>().unwrap();
+    assert_eq!(mpls.labels.len(), 2);
+    assert_eq!(mpls.labels[0].label, 16);
+    assert!(!mpls.labels[0].bottom_of_stack);
+    assert_eq!(mpls.labels[1].label, 32);
+    assert_eq!(mpls.labels[1].ttl, 63);
+    assert_eq!(mpls.ethertype, Some(EtherType::Ipv4));
+}
+
+#[test]
+fn ethernet_failure_incomplete() {
+    let mut peel = packet_peel();
+    let result = peel.traverse(&ETHERNET_HEADER[..10], vec![]);
+    assert!(result.error.is_some());
+    assert!(result.result.is_empty());
+}
+
+#[test]
+fn ethernet_success_registry() {
+    let registry = packet_registry();
+    assert!(registry.contains("EthernetParser"));
+    let config = packet_peel().to_config();
+    let mut peel = Peel::from_config(&config, &registry).unwrap();
+    peel.data = Some(PacketData::default());
+    assert_eq!(peel.traverse(&frame(&[0x81, 0x00, 0x00, 0x01, 0x08, 0x00]), vec![]).result.len(), 2);
+}
@@ -0,0 +1,144 @@
+extern crate peel;
+use peel::example::prelude::*;
+use peel::prelude::{TreeHandle, TreeConfig, ErrorType, PeelError, ParserInfo};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+
+fn example_handle() -> TreeHandle<()> {
+    TreeHandle::from_peel(&peel_example(), example_registry()).unwrap()
+}
+
+fn remove_parser4(handle: &TreeHandle<()>) -> usize {
+    handle.modify(|peel, _| {
+            let node = peel.graph
+                .node_indices()
+                .find(|node| peel.graph[*node].name() == "Parser4")
+                .unwrap();
+            peel.remove(node);
+            Ok(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn handle_success_modify() {
+    let handle = example_handle();
+    let mut worker = handle.worker().unwrap();
+    assert_eq!(worker.traverse(b"1234", vec![]).result.len(), 4);
+
+    assert_eq!(remove_parser4(&handle), 1);
+    assert_eq!(handle.epoch(), 1);
+    assert_eq!(handle.config().unwrap().parsers.len(), 3);
+    assert_eq!(worker.epoch(), 0);
+
+    assert_eq!(worker.traverse(b"1234", vec![]).result.len(), 3);
+    assert_eq!(worker.epoch(), 1);
+}
+
+#[test]
+fn handle_success_keep_data() {
+    let handle = example_handle();
+    let mut worker = handle.worker().unwrap();
+    worker.peel().data = Some(());
+    remove_parser4(&handle);
+    assert!(worker.refresh().unwrap());
+    assert!(!worker.refresh().unwrap());
+    assert!(worker.peel().data.is_some());
+}
+
+#[test]
+fn handle_success_rebuild_all_parsers() {
+    let created = Arc::new(AtomicUsize::new(0));
+    let counter = created.clone();
+    let mut registry = example_registry();
+    registry.register(ParserInfo::new("Parser1", "1.0.0", "Parses the character '1'"),
+                      move |_| {
+                          counter.fetch_add(1, Ordering::SeqCst);
+                          Ok(Box::new(Parser1))
+                      });
+    let handle = TreeHandle::from_peel(&peel_example(), registry).unwrap();
+    let mut worker = handle.worker().unwrap();
+    remove_parser4(&handle);
+
+    // The unchanged root parser will be created again by the worker
+    let before = created.load(Ordering::SeqCst);
+    assert!(worker.refresh().unwrap());
+    assert_eq!(created.load(Ordering::SeqCst), before + 1);
+    assert!(!worker.refresh().unwrap());
+    assert_eq!(created.load(Ordering::SeqCst), before + 1);
+}
+
+#[test]
+fn handle_success_threads() {
+    let handle = example_handle();
+    let (request_tx, request_rx) = channel();
+    let (response_tx, response_rx) = channel();
+
+    let worker_handle = handle.clone();
+    let worker_thread = thread::spawn(move || {
+        let mut worker = worker_handle.worker().unwrap();
+        for input in request_rx {
+            let input: Vec<u8> = input;
+            response_tx.send(worker.traverse(&input, vec![]).result.len()).unwrap();
+        }
+    });
+
+    request_tx.send(b"1234".to_vec()).unwrap();
+    assert_eq!(response_rx.recv().unwrap(), 4);
+    remove_parser4(&handle);
+    request_tx.send(b"1234".to_vec()).unwrap();
+    assert_eq!(response_rx.recv().unwrap(), 3);
+
+    drop(request_tx);
+    worker_thread.join().unwrap();
+}
+
+#[test]
+fn handle_success_read_during_modify() {
+    let handle = example_handle();
+    let (entered_tx, entered_rx) = channel();
+    let (release_tx, release_rx) = channel::<()>();
+
+    let modify_handle = handle.clone();
+    let modify_thread = thread::spawn(move || {
+        modify_handle.modify(|_, _| {
+                entered_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                Ok(())
+            })
+            .unwrap()
+    });
+
+    // The snapshot stays readable while the closure runs
+    entered_rx.recv().unwrap();
+    assert_eq!(handle.config().unwrap().parsers.len(), 4);
+    let mut worker = handle.worker().unwrap();
+    assert_eq!(worker.traverse(b"1234", vec![]).result.len(), 4);
+
+    release_tx.send(()).unwrap();
+    assert_eq!(modify_thread.join().unwrap(), 1);
+    assert!(worker.refresh().unwrap());
+}
+
+#[test]
+fn handle_failure_modify() {
+    let handle = example_handle();
+    let error = handle.modify(|_, _| Err(PeelError::new(ErrorType::Other, "Failed")))
+        .err()
+        .unwrap();
+    assert_eq!(error.code, ErrorType::Other);
+    assert_eq!(handle.epoch(), 0);
+}
+
+#[test]
+fn handle_failure_replace() {
+    let handle = example_handle();
+    let mut config: TreeConfig = (*handle.config().unwrap()).clone();
+    config.parsers[0].parser = "Parser5".to_owned();
+    let error = handle.replace(config).err().unwrap();
+    assert_eq!(error.code, ErrorType::UnknownParser);
+    assert_eq!(handle.epoch(), 0);
+}
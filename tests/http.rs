@@ -0,0 +1,148 @@
+extern crate peel;
+use peel::packet::prelude::*;
+use peel::error::ErrorType;
+
+mod common;
+use common::ipv4_tcp;
+
+fn http_peel() -> Peel<PacketData> {
+    let mut peel = Peel::new();
+    peel.new_root("http", HttpParser);
+    peel
+}
+
+fn http(result: &ParserResult) -> &HttpPacket {
+    result.downcast_ref::<HttpPacket>().unwrap()
+}
+
+#[test]
+fn http_success_request() {
+    let mut peel = packet_peel();
+    let input = ipv4_tcp(8080,
+                         1000,
+                         0x18,
+                         b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nAccept: text/html,\r\n \
+                           application/xml\r\nX-Test: 1\r\nx-test: 2\r\n\r\n");
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 3);
+
+    let request = http(&result.result[2]);
+    assert_eq!(request.start,
+               HttpStart::Request {
+                   method: "GET".to_owned(),
+                   target: "/index.html".to_owned(),
+                   version: HttpVersion::Http11,
+               });
+    assert_eq!(request.headers.len(), 4);
+    assert_eq!(request.headers.host(), Some("example.com"));
+    assert_eq!(request.headers.get("accept"), Some("text/html, application/xml"));
+    assert_eq!(request.headers.get_all("X-TEST"), vec!["1", "2"]);
+    assert!(request.body.is_empty());
+}
+
+#[test]
+fn http_success_chunked_response() {
+    let mut peel = http_peel();
+    let input = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n\
+                  5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nExpires: never\r\n\r\nHTTP/1.1";
+    let result = peel.traverse(input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"HTTP/1.1");
+
+    let response = http(&result.result[0]);
+    assert_eq!(response.start,
+               HttpStart::Response {
+                   version: HttpVersion::Http11,
+                   status: 200,
+                   reason: "OK".to_owned(),
+               });
+    assert!(response.headers.is_chunked());
+    assert_eq!(response.body, b"hello, world");
+    assert_eq!(response.trailers.get("expires"), Some("never"));
+}
+
+#[test]
+fn http_success_continue_traverse() {
+    let mut peel = http_peel();
+    let message = b"POST /form HTTP/1.0\r\nContent-Length: 11\r\nConnection: close\r\n\r\nhello world";
+
+    // Incomplete headers and bodies need more data
+    for length in &[10, 40, 70] {
+        let result = peel.traverse(&message[..*length], vec![]);
+        match result.error.map(|e| e.code) {
+            Some(ErrorType::Incomplete(_)) => {}
+            code => panic!("Unexpected error {:?}", code),
+        }
+    }
+
+    let result = peel.continue_traverse(message, vec![]);
+    assert!(result.error.is_none());
+    assert!(result.left_input.is_empty());
+    let request = http(&result.result[0]);
+    assert_eq!(request.headers.content_length(), Some(11));
+    assert!(request.headers.connection_close());
+    assert_eq!(request.body, b"hello world");
+}
+
+#[test]
+fn http_success_stream() {
+    let mut peel = packet_peel();
+    let stream = b"GET / HTTP/1.1\r\nHost: a\r\n\r\nHEAD / HTTP/1.1\r\nHost: a\r\n\r\n\
+                   HTTP/1.0 200 OK\r\nServer: test\r\n\r\nuntil the end";
+    peel.traverse_root("ipv4", &ipv4_tcp(8080, 1000, 0x18, &stream[..20]), vec![]);
+    peel.traverse_root("ipv4", &ipv4_tcp(8080, 1020, 0x18, &stream[20..]), vec![]);
+
+    let mut messages = vec![];
+    peel.traverse_streams(|_, result| {
+        assert!(result.error.is_none());
+        messages.push(http(&result.result[1]).clone());
+    });
+    assert_eq!(messages.len(), 2);
+
+    // The response without length ends with the stream
+    peel.traverse_root("ipv4", &ipv4_tcp(8080, 1000 + stream.len() as u32, 0x11, &[]), vec![]);
+    peel.traverse_streams(|stream, result| {
+        assert!(stream.finished);
+        messages.push(http(&result.result[1]).clone());
+    });
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1].start,
+               HttpStart::Request {
+                   method: "HEAD".to_owned(),
+                   target: "/".to_owned(),
+                   version: HttpVersion::Http11,
+               });
+    assert_eq!(messages[2].headers.get("server"), Some("test"));
+    assert_eq!(messages[2].body, b"until the end");
+}
+
+#[test]
+fn http_failure_invalid() {
+    let mut peel = http_peel();
+    for input in &[&b""[..],
+                   b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n",
+                   b"get / HTTP/1.1\r\n\r\n",
+                   b"\x16\x03\x01\x00",
+                   b"HTTP/1.1 20 OK\r\n\r\n",
+                   b"GET / HTTP/1.1\r\nNo colon\r\n\r\n",
+                   b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nxyz\r\n"] {
+        let result = peel.traverse(input, vec![]);
+        match result.error.map(|e| e.code) {
+            Some(ErrorType::NoParserSucceed) => {}
+            code => panic!("Unexpected error {:?} for {:?}", code, input),
+        }
+    }
+}
+
+#[test]
+fn http_failure_length_overflow() {
+    let mut peel = http_peel();
+
+    // The missing body size can not be represented
+    for input in &[&b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n"[..],
+                   b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n"] {
+        let result = peel.traverse(input, vec![]);
+        assert_eq!(result.error.map(|e| e.code), Some(ErrorType::NoParserSucceed));
+    }
+}
@@ -0,0 +1,192 @@
+extern crate peel;
+use peel::packet::prelude::*;
+use peel::error::ErrorType;
+
+mod common;
+use common::ipv4_tcp;
+
+/// Create an HTTP/2 frame
+fn frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+    let length = payload.len();
+    let mut frame = vec![(length >> 16) as u8, (length >> 8) as u8, length as u8, kind, flags, (stream_id >> 24) as u8,
+                         (stream_id >> 16) as u8, (stream_id >> 8) as u8, stream_id as u8];
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn http2_peel() -> Peel<PacketData> {
+    let mut peel = Peel::new();
+    peel.data = Some(PacketData { http2: Some(Http2Cache::default()), ..PacketData::default() });
+    peel.new_root("http2", Http2Parser);
+    peel
+}
+
+fn http2(result: &ParserResult) -> &Http2Frame {
+    result.downcast_ref::<Http2Frame>().unwrap()
+}
+
+/// The first request of RFC 7541 C.4, which uses Huffman encoding
+const REQUEST: &[u8] = b"\x82\x86\x84\x41\x8c\xf1\xe3\xc2\xe5\xf2\x3a\x6b\xa0\xab\x90\xf4\xff";
+
+#[test]
+fn hpack_success_rfc_requests() {
+    let mut decoder = HpackDecoder::default();
+    let headers = decoder.decode(REQUEST).unwrap();
+    assert_eq!(headers.iter().cloned().collect::<Vec<_>>(),
+               vec![(":method".to_owned(), "GET".to_owned()),
+                    (":scheme".to_owned(), "http".to_owned()),
+                    (":path".to_owned(), "/".to_owned()),
+                    (":authority".to_owned(), "www.example.com".to_owned())]);
+    assert_eq!(decoder.size(), 57);
+
+    let headers = decoder.decode(b"\x82\x86\x84\xbe\x58\x86\xa8\xeb\x10\x64\x9c\xbf").unwrap();
+    assert_eq!(headers.get(":authority"), Some("www.example.com"));
+    assert_eq!(headers.get("cache-control"), Some("no-cache"));
+    assert_eq!(decoder.size(), 110);
+
+    let headers = decoder.decode(b"\x82\x87\x85\xbf\x40\x88\x25\xa8\x49\xe9\x5b\xa9\x7d\x7f\x89\x25\xa8\x49\xe9\
+                                   \x5b\xb8\xe8\xb4\xbf")
+        .unwrap();
+    assert_eq!(headers.get(":scheme"), Some("https"));
+    assert_eq!(headers.get(":path"), Some("/index.html"));
+    assert_eq!(headers.get(":authority"), Some("www.example.com"));
+    assert_eq!(headers.get("custom-key"), Some("custom-value"));
+    assert_eq!(decoder.len(), 3);
+    assert_eq!(decoder.size(), 164);
+
+    // Size updates evict entries
+    assert!(decoder.decode(b"\x3f\x1b").is_some());
+    assert_eq!(decoder.len(), 1);
+    assert_eq!(decoder.size(), 54);
+}
+
+#[test]
+fn http2_success_preface() {
+    let mut peel = http2_peel();
+    let mut input = HTTP2_PREFACE.to_vec();
+    input.extend_from_slice(&frame(4, 0, 0, &[0, 1, 0, 0, 0x10, 0, 0, 3, 0, 0, 0, 100]));
+    input.extend_from_slice(&frame(8, 0, 0, &[0x80, 0, 0x10, 0]));
+
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    let settings = http2(&result.result[0]);
+    assert!(settings.preface);
+    assert!(!settings.ack());
+    assert_eq!(settings.payload,
+               Http2Payload::Settings(vec![Http2Setting::HeaderTableSize(4096),
+                                           Http2Setting::MaxConcurrentStreams(100)]));
+
+    let left = result.left_input;
+    let result = peel.traverse(left, vec![]);
+    assert!(result.left_input.is_empty());
+    assert_eq!(http2(&result.result[0]).payload,
+               Http2Payload::WindowUpdate { increment: 0x1000 });
+}
+
+#[test]
+fn http2_success_continuation() {
+    let mut peel = http2_peel();
+    let mut input = frame(4, 1, 0, &[]);
+
+    // A padded header block with priority split across two frames
+    let mut headers = vec![2, 0x80, 0, 0, 1, 15];
+    headers.extend_from_slice(&REQUEST[..5]);
+    headers.extend_from_slice(&[0, 0]);
+    input.extend_from_slice(&frame(1, 0x29, 3, &headers));
+    input.extend_from_slice(&frame(9, 0x04, 3, &REQUEST[5..]));
+    input.extend_from_slice(&frame(0, 0x09, 3, &[1, b'h', b'i', 0]));
+
+    let mut frames = vec![];
+    let mut left = &input[..];
+    while !left.is_empty() {
+        let result = peel.traverse(left, vec![]);
+        assert!(result.error.is_none());
+        frames.push(http2(&result.result[0]).clone());
+        left = result.left_input;
+    }
+
+    assert!(frames[0].ack());
+    assert_eq!(frames[1].payload,
+               Http2Payload::Headers {
+                   priority: Some(Http2Priority {
+                       exclusive: true,
+                       dependency: 1,
+                       weight: 15,
+                   }),
+                   headers: None,
+               });
+    assert!(!frames[1].end_headers());
+    assert!(frames[2].end_headers());
+    assert_eq!(frames[2].headers().and_then(|h| h.get(":authority")), Some("www.example.com"));
+    assert!(frames[3].end_stream());
+    assert_eq!(frames[3].payload, Http2Payload::Data(b"hi".to_vec()));
+}
+
+#[test]
+fn http2_success_stream() {
+    let mut peel = packet_peel();
+    let mut stream = HTTP2_PREFACE.to_vec();
+    stream.extend_from_slice(&frame(4, 0, 0, &[]));
+    stream.extend_from_slice(&frame(1, 0x05, 1, REQUEST));
+    stream.extend_from_slice(&frame(1, 0x05, 3, b"\x82\x86\x84\xbe"));
+    peel.traverse_root("ipv4", &ipv4_tcp(443, 1000, 0x18, &stream[..30]), vec![]);
+    peel.traverse_root("ipv4", &ipv4_tcp(443, 1030, 0x18, &stream[30..]), vec![]);
+
+    let mut frames = vec![];
+    peel.traverse_streams(|_, result| {
+        assert!(result.error.is_none());
+        frames.push(http2(&result.result[1]).clone());
+    });
+    assert_eq!(frames.len(), 3);
+
+    // The dynamic table is shared by all requests of the direction
+    let second = frames[2].headers().unwrap();
+    assert_eq!(second.get(":authority"), Some("www.example.com"));
+    assert_eq!(peel.data.as_ref().unwrap().http2.as_ref().unwrap().len(), 1);
+}
+
+#[test]
+fn http2_failure_invalid() {
+    let mut peel = http2_peel();
+    for input in &[b"GET / HTTP/1.1\r\n\r\n".to_vec(),
+                   frame(0, 0, 1, b"data"),
+                   frame(4, 0, 0, &[0, 1, 0])] {
+        let result = peel.traverse(input, vec![]);
+        match result.error.map(|e| e.code) {
+            Some(ErrorType::NoParserSucceed) => {}
+            code => panic!("Unexpected error {:?} for {:?}", code, input),
+        }
+    }
+
+    // Header blocks must not be interrupted
+    let mut input = frame(4, 0, 0, &[]);
+    input.extend_from_slice(&frame(1, 0, 1, &REQUEST[..5]));
+    input.extend_from_slice(&frame(0, 0, 1, b"data"));
+    let result = peel.traverse(&input, vec![]);
+    let result = peel.traverse(result.left_input, vec![]);
+    let left = result.left_input;
+    assert!(peel.traverse(left, vec![]).error.is_some());
+    assert!(peel.data.as_ref().unwrap().http2.as_ref().unwrap().is_empty());
+
+    // Header blocks continued beyond the limit drop the direction
+    let mut input = frame(4, 0, 0, &[]);
+    input.extend_from_slice(&frame(1, 0, 1, &[0; 16384]));
+    for _ in 0..4 {
+        input.extend_from_slice(&frame(9, 0, 1, &[0; 16384]));
+    }
+    let mut left = &input[..];
+    for _ in 0..5 {
+        let result = peel.traverse(left, vec![]);
+        assert!(result.error.is_none());
+        left = result.left_input;
+    }
+    assert!(peel.traverse(left, vec![]).error.is_some());
+    assert!(peel.data.as_ref().unwrap().http2.as_ref().unwrap().is_empty());
+
+    // The padding has to fit into the frame
+    let mut input = frame(4, 0, 0, &[]);
+    input.extend_from_slice(&frame(0, 0x08, 1, &[4, 0]));
+    let result = peel.traverse(&input, vec![]);
+    let left = result.left_input;
+    assert!(peel.traverse(left, vec![]).error.is_some());
+}
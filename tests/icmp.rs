@@ -0,0 +1,138 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::Ipv4Addr;
+
+/// Create an IPv4 packet with a valid checksum
+fn ipv4_packet(protocol: u8, total_length: usize, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0, 0, 0x40, 0, 64, protocol, 0, 0,
+                          192, 168, 0, 1, 10, 0, 0, 1];
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Create an ICMPv4 message with a valid checksum
+fn icmpv4_message(kind: u8, code: u8, header: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind, code, 0, 0];
+    message.extend_from_slice(&header);
+    message.extend_from_slice(data);
+    let checksum = internet_checksum(&message);
+    message[2] = (checksum >> 8) as u8;
+    message[3] = checksum as u8;
+    ipv4_packet(1, 20 + message.len(), &message)
+}
+
+/// Create an IPv6 packet carrying an ICMPv6 message with a valid checksum
+fn icmpv6_message(kind: u8, header: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let length = 8 + data.len();
+    let mut packet = vec![0x60, 0, 0, 0, (length >> 8) as u8, length as u8, 58, 64];
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+
+    let mut message = vec![kind, 0, 0, 0];
+    message.extend_from_slice(&header);
+    message.extend_from_slice(data);
+    let mut sum = ones_complement_sum(0, &packet[8..40]);
+    sum = ones_complement_sum(sum, &[0, 0, (length >> 8) as u8, length as u8, 0, 0, 0, 58]);
+    let checksum = fold_checksum(ones_complement_sum(sum, &message));
+    message[2] = (checksum >> 8) as u8;
+    message[3] = checksum as u8;
+
+    packet.extend_from_slice(&message);
+    packet
+}
+
+#[test]
+fn icmp_success_echo_request() {
+    let mut peel = packet_peel();
+    let input = icmpv4_message(8, 0, [0x12, 0x34, 0, 1], b"ping");
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 2);
+    assert_eq!(result.left_input, b"ping");
+    let icmp = result.result[1].downcast_ref::<Icmpv4Packet>().unwrap();
+    assert!(icmp.checksum_valid);
+    assert!(!icmp.is_error());
+    assert_eq!(icmp.message,
+               Icmpv4Message::EchoRequest {
+                   id: 0x1234,
+                   sequence: 1,
+               });
+}
+
+#[test]
+fn icmp_success_embedded_datagram() {
+    let mut peel = packet_peel();
+    let original = ipv4_packet(17, 60, &[0x04, 0xd2, 0, 53, 0, 40, 0xab, 0xcd]);
+    let input = icmpv4_message(3, 4, [0, 0, 0x05, 0xdc], &original);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 4);
+    let icmp = result.result[1].downcast_ref::<Icmpv4Packet>().unwrap();
+    assert_eq!(icmp.code, 4);
+    assert_eq!(icmp.message, Icmpv4Message::DestinationUnreachable { next_hop_mtu: 1500 });
+    assert!(icmp.is_error());
+    let ipv4 = result.result[2].downcast_ref::<Ipv4Packet>().unwrap();
+    assert_eq!(ipv4.total_length, 60);
+    assert_eq!(ipv4.dst, Ipv4Addr::new(10, 0, 0, 1));
+    let udp = result.result[3].downcast_ref::<UdpPacket>().unwrap();
+    assert_eq!(udp.dst_port, 53);
+    assert_eq!(udp.checksum_valid, None);
+}
+
+#[test]
+fn icmp_success_embedded_truncated_tcp() {
+    let mut peel = packet_peel();
+    let original = ipv4_packet(6, 60, &[0x04, 0xd2, 0, 80, 0, 0, 0, 1]);
+    let input = icmpv4_message(11, 0, [0; 4], &original);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 3);
+    assert_eq!(result.left_input.len(), 8);
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().streams(), 0);
+}
+
+#[test]
+fn icmp_success_checksum_mismatch() {
+    let mut peel = packet_peel();
+    let mut input = icmpv4_message(0, 0, [0, 1, 0, 2], &[]);
+    input[22] ^= 0xff;
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(!result.result[1].downcast_ref::<Icmpv4Packet>().unwrap().checksum_valid);
+}
+
+#[test]
+fn icmpv6_success_echo_reply() {
+    let mut peel = packet_peel();
+    let input = icmpv6_message(129, [0, 7, 0, 3], b"pong");
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.left_input, b"pong");
+    let icmp = result.result[1].downcast_ref::<Icmpv6Packet>().unwrap();
+    assert_eq!(icmp.checksum_valid, Some(true));
+    assert_eq!(icmp.message,
+               Icmpv6Message::EchoReply {
+                   id: 7,
+                   sequence: 3,
+               });
+}
+
+#[test]
+fn icmpv6_success_packet_too_big() {
+    let mut peel = packet_peel();
+    let mut original = vec![0x60, 0, 0, 0, 0x05, 0xdc, 17, 64];
+    original.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    original.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]);
+    original.extend_from_slice(&[0x04, 0xd2, 0, 53, 0x05, 0xdc, 0, 0]);
+    let input = icmpv6_message(2, [0, 0, 0x05, 0x00], &original);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 4);
+    let icmp = result.result[1].downcast_ref::<Icmpv6Packet>().unwrap();
+    assert_eq!(icmp.checksum_valid, Some(true));
+    assert_eq!(icmp.message, Icmpv6Message::PacketTooBig { mtu: 1280 });
+    assert_eq!(result.result[2].downcast_ref::<Ipv6Packet>().unwrap().protocol, IpProtocol::Udp);
+    assert_eq!(result.result[3].downcast_ref::<UdpPacket>().unwrap().length, 1500);
+}
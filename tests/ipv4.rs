@@ -0,0 +1,133 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::Ipv4Addr;
+
+/// Create an IPv4 packet with a valid checksum
+fn ipv4_packet(options: &[u8], fragment: u16, payload: &[u8]) -> Vec<u8> {
+    let header_length = 20 + options.len();
+    let total_length = header_length + payload.len();
+    let mut packet = vec![0x40 | (header_length / 4) as u8,
+                          0xb8,
+                          (total_length >> 8) as u8,
+                          total_length as u8,
+                          0x12,
+                          0x34,
+                          (fragment >> 8) as u8,
+                          fragment as u8,
+                          64,
+                          17,
+                          0,
+                          0,
+                          192,
+                          168,
+                          0,
+                          1,
+                          10,
+                          0,
+                          0,
+                          1];
+    packet.extend_from_slice(options);
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(payload);
+    packet
+}
+
+#[test]
+fn ipv4_success_header() {
+    let mut peel = packet_peel();
+    let input = ipv4_packet(&[], 0x4000, &[1, 2, 3]);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.left_input, &[1, 2, 3]);
+    let ipv4 = result.result[0].downcast_ref::<Ipv4Packet>().unwrap();
+    assert_eq!(ipv4.header_length, 20);
+    assert_eq!(ipv4.dscp, 46);
+    assert_eq!(ipv4.total_length, 23);
+    assert_eq!(ipv4.id, 0x1234);
+    assert!(ipv4.dont_fragment);
+    assert!(!ipv4.is_fragment());
+    assert_eq!(ipv4.protocol, IpProtocol::Udp);
+    assert!(ipv4.checksum_valid);
+    assert_eq!(ipv4.src, Ipv4Addr::new(192, 168, 0, 1));
+    assert_eq!(ipv4.dst, Ipv4Addr::new(10, 0, 0, 1));
+    assert_eq!(next_ip_protocol(Some(&result.result)), Some(IpProtocol::Udp));
+}
+
+#[test]
+fn ipv4_success_ethernet_padding() {
+    let mut peel = packet_peel();
+    let mut input = vec![0; 12];
+    input.extend_from_slice(&[0x08, 0x00]);
+    input.extend_from_slice(&ipv4_packet(&[], 0, &[1, 2]));
+    input.extend_from_slice(&[0; 8]);
+    let result = peel.traverse(&input, vec![]);
+    assert_eq!(result.result.len(), 2);
+    assert_eq!(result.left_input, &[1, 2]);
+}
+
+#[test]
+fn ipv4_success_options() {
+    let mut peel = packet_peel();
+    let options = [148, 4, 0, 0, // Router alert
+                   1, // No operation
+                   7, 7, 8, 10, 0, 0, 1, // Record route
+                   68, 12, 13, 1, 10, 0, 0, 2, 0, 0, 1, 0, // Timestamp with addresses
+                   0, 0, 0, 0]; // End of list and padding
+    let input = ipv4_packet(&options, 0, &[]);
+    let result = peel.traverse_root("ipv4", &input, vec![]).result;
+    let ipv4 = result[0].downcast_ref::<Ipv4Packet>().unwrap();
+    assert!(ipv4.checksum_valid);
+    assert_eq!(ipv4.options,
+               vec![Ipv4Option::RouterAlert(0),
+                    Ipv4Option::NoOperation,
+                    Ipv4Option::RecordRoute {
+                        pointer: 8,
+                        route: vec![Ipv4Addr::new(10, 0, 0, 1)],
+                    },
+                    Ipv4Option::Timestamp {
+                        pointer: 13,
+                        overflow: 0,
+                        flag: 1,
+                        entries: vec![(Some(Ipv4Addr::new(10, 0, 0, 2)), 256)],
+                    },
+                    Ipv4Option::EndOfList]);
+}
+
+#[test]
+fn ipv4_success_checksum_mismatch() {
+    let mut peel = packet_peel();
+    let mut input = ipv4_packet(&[], 0, &[0, 53, 0, 53, 0, 8, 0, 0]);
+    input[11] ^= 0xff;
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert!(!result.result[0].downcast_ref::<Ipv4Packet>().unwrap().checksum_valid);
+}
+
+#[test]
+fn ipv4_success_fragment() {
+    let mut peel = packet_peel();
+    let input = ipv4_packet(&[], 0x2000 | 185, &[0; 8]);
+    let result = peel.traverse_root("ipv4", &input, vec![]).result;
+    let ipv4 = result[0].downcast_ref::<Ipv4Packet>().unwrap();
+    assert!(ipv4.is_fragment());
+    assert!(ipv4.more_fragments);
+    assert_eq!(ipv4.fragment_offset, 1480);
+    assert_eq!(next_ip_protocol(Some(&result)), None);
+}
+
+#[test]
+fn ipv4_failure_malformed_option() {
+    let mut peel = packet_peel();
+    let input = ipv4_packet(&[148, 8, 0, 0], 0, &[]);
+    assert!(peel.traverse_root("ipv4", &input, vec![]).error.is_some());
+}
+
+#[test]
+fn ipv4_failure_wrong_version() {
+    let mut peel = packet_peel();
+    let mut input = ipv4_packet(&[], 0, &[]);
+    input[0] = 0x65;
+    assert!(peel.traverse_root("ipv4", &input, vec![]).error.is_some());
+}
@@ -0,0 +1,131 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::Ipv6Addr;
+
+/// Create an IPv6 packet with the given next header and payload
+fn ipv6_packet(next_header: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x6a, 0x80, 0x00, 0x01, (payload.len() >> 8) as u8, payload.len() as u8, next_header, 64];
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+#[test]
+fn ipv6_success_header() {
+    let mut peel = packet_peel();
+    let input = ipv6_packet(6, &[1, 2, 3]);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.left_input, &[1, 2, 3]);
+    let ipv6 = result.result[0].downcast_ref::<Ipv6Packet>().unwrap();
+    assert_eq!(ipv6.traffic_class, 0xa8);
+    assert_eq!(ipv6.flow_label, 1);
+    assert_eq!(ipv6.payload_length, 3);
+    assert_eq!(ipv6.hop_limit, 64);
+    assert_eq!(ipv6.src, "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+    assert_eq!(ipv6.dst, "2001:db8::2".parse::<Ipv6Addr>().unwrap());
+    assert!(ipv6.extensions.is_empty());
+    assert_eq!(ipv6.protocol, IpProtocol::Tcp);
+    assert_eq!(next_ip_protocol(Some(&result.result)), Some(IpProtocol::Tcp));
+}
+
+#[test]
+fn ipv6_success_extension_chain() {
+    let mut peel = packet_peel();
+    let payload = [43, 0, 0, 1, 3, 0, 0, 0, // Hop-by-Hop: Pad1, PadN
+                   44, 0, 0, 1, 0, 0, 0, 0, // Routing: type 0, segments left 1
+                   51, 0, 0, 0, 0xde, 0xad, 0xbe, 0xef, // Fragment: first fragment, id
+                   60, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, // Authentication header
+                   17, 0, 0xc2, 4, 0, 0, 0, 0, // Destination options: jumbo payload
+                   9, 9];
+    let input = ipv6_packet(0, &payload);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.left_input, &[9, 9]);
+    let ipv6 = result.result[0].downcast_ref::<Ipv6Packet>().unwrap();
+    assert_eq!(ipv6.next_header, IpProtocol::HopByHop);
+    assert_eq!(ipv6.protocol, IpProtocol::Udp);
+    assert_eq!(ipv6.extensions.len(), 5);
+    assert_eq!(ipv6.extensions[0],
+               Ipv6Extension::HopByHop(vec![Ipv6Option {
+                                                kind: 0,
+                                                data: vec![],
+                                            },
+                                            Ipv6Option {
+                                                kind: 1,
+                                                data: vec![0, 0, 0],
+                                            }]));
+    assert_eq!(ipv6.extensions[1],
+               Ipv6Extension::Routing {
+                   routing_type: 0,
+                   segments_left: 1,
+                   data: vec![0, 0, 0, 0],
+               });
+    assert_eq!(ipv6.extensions[3],
+               Ipv6Extension::Authentication {
+                   spi: 1,
+                   sequence: 1,
+                   icv: vec![],
+               });
+    let fragment = ipv6.fragment().unwrap();
+    assert_eq!(fragment.id, 0xdeadbeef);
+    assert!(!ipv6.is_fragment());
+    assert_eq!(next_ip_protocol(Some(&result.result)), Some(IpProtocol::Udp));
+}
+
+#[test]
+fn ipv6_success_esp_boundary() {
+    let mut peel = packet_peel();
+    let input = ipv6_packet(50, &[0, 0, 0, 1, 0, 0, 0, 1, 0xff]);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.left_input.len(), 9);
+    let ipv6 = result.result[0].downcast_ref::<Ipv6Packet>().unwrap();
+    assert_eq!(ipv6.protocol, IpProtocol::Esp);
+}
+
+#[test]
+fn ipv6_success_non_first_fragment() {
+    let mut peel = packet_peel();
+    let input = ipv6_packet(44, &[17, 0, 0x05, 0xc9, 0, 0, 0, 1, 0, 0]);
+    let result = peel.traverse_root("ipv6", &input, vec![]).result;
+    let ipv6 = result[0].downcast_ref::<Ipv6Packet>().unwrap();
+    assert!(ipv6.is_fragment());
+    assert_eq!(ipv6.fragment().unwrap().offset, 1480);
+    assert_eq!(next_ip_protocol(Some(&result)), None);
+}
+
+#[test]
+fn ipv6_success_non_first_fragment_payload() {
+    let mut peel = packet_peel();
+
+    // The payload looks like a truncated destination options header
+    let input = ipv6_packet(44, &[60, 0, 0x05, 0xc9, 0, 0, 0, 1, 17, 0xff, 1, 2]);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, &[17, 0xff, 1, 2]);
+
+    let ipv6 = result.result[0].downcast_ref::<Ipv6Packet>().unwrap();
+    assert_eq!(ipv6.extensions.len(), 1);
+    assert_eq!(ipv6.protocol, IpProtocol::DestinationOptions);
+    assert_eq!(peel.data.as_ref().unwrap().fragments.as_ref().unwrap().pending(), 1);
+}
+
+#[test]
+fn ipv6_success_ethernet() {
+    let mut peel = packet_peel();
+    let mut input = vec![0; 12];
+    input.extend_from_slice(&[0x86, 0xdd]);
+    input.extend_from_slice(&ipv6_packet(59, &[]));
+    let result = peel.traverse(&input, vec![]).result;
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[1].downcast_ref::<Ipv6Packet>().unwrap().protocol,
+               IpProtocol::NoNextHeader);
+}
+
+#[test]
+fn ipv6_failure_wrong_version() {
+    let mut peel = packet_peel();
+    let mut input = ipv6_packet(59, &[]);
+    input[0] = 0x45;
+    assert!(peel.traverse_root("ipv6", &input, vec![]).error.is_some());
+}
@@ -5,6 +5,7 @@ use log::LogLevel;
 extern crate peel;
 use peel::example::prelude::*;
 use peel::error::ErrorType;
+use peel::prelude::LinkKind;
 
 extern crate nom;
 use nom::Needed;
@@ -133,3 +134,216 @@ fn peel_failure_no_parser_succeed() {
     println!("{:?}", error);
     println!("{}", error.description());
 }
+
+#[test]
+fn peel_success_named_roots() {
+    let mut peel: Peel<()> = Peel::new();
+    let p1 = peel.new_root("one", Parser1);
+    let p2 = peel.new_root("two", Parser2);
+    let p3 = peel.new_parser(Parser3);
+    peel.link_nodes(&[(p1, p3), (p2, p3)]);
+    assert_eq!(peel.root, Some(p1));
+    assert_eq!(peel.get_root("two"), Some(p2));
+
+    let result = peel.traverse_root("one", b"13", vec![]).result;
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].downcast_ref::<Parser1Result>(),
+               Some(&Parser1Result));
+
+    let result = peel.traverse_root("two", b"23", vec![]).result;
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].downcast_ref::<Parser2Result>(),
+               Some(&Parser2Result));
+    assert_eq!(result[1].downcast_ref::<Parser3Result>(),
+               Some(&Parser3Result));
+}
+
+#[test]
+fn peel_success_remove_root() {
+    let mut peel: Peel<()> = Peel::new();
+    let p1 = peel.new_root("one", Parser1);
+    let p2 = peel.new_parser(Parser2);
+    peel.add_root("two", p2);
+    assert_eq!(peel.remove_root("two"), Some(p2));
+    assert!(peel.get_root("two").is_none());
+    assert!(peel.remove(p1).is_some());
+    assert!(peel.root.is_none());
+    assert!(peel.roots.is_empty());
+}
+
+#[test]
+fn peel_failure_unknown_root() {
+    let mut peel = peel_example();
+    let error = peel.traverse_root("unknown", b"1234", vec![]).error.unwrap();
+    assert_eq!(error.code, ErrorType::NoTreeRoot);
+}
+
+#[test]
+fn peel_success_dot_highlight() {
+    let mut peel = peel_example();
+    let peel_result = peel.traverse(b"1234", vec![]);
+    assert_eq!(peel_result.path.len(), 4);
+    assert!(!peel_result.failed.is_empty());
+
+    let mut dot = vec![];
+    assert!(peel.write_dot(&mut dot, Some(&peel_result)).is_ok());
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph {"));
+    assert!(dot.contains("0 [label=\"Parser1\", style=filled, fillcolor=green]"));
+    assert!(dot.contains("0 -> 1 [label=\"1\", color=green]"));
+}
+
+#[test]
+fn peel_success_dot_file_at() {
+    let peel = peel_example();
+    let path = std::env::temp_dir().join("peel_test.dot");
+    assert!(peel.create_dot_file_at(&path, None).is_ok());
+    assert!(path.exists());
+}
+
+#[test]
+fn peel_success_graft() {
+    let mut peel: Peel<()> = Peel::new();
+    let p1 = peel.new_parser(Parser1);
+
+    let mut subtree = Peel::new();
+    let p2 = subtree.new_parser(Parser2);
+    let p3 = subtree.link_new_parser(p2, Parser3);
+    subtree.link(p3, p3);
+
+    let mapping = peel.graft(p1, subtree);
+    assert_eq!(mapping.len(), 2);
+    assert_eq!(peel.graph.node_count(), 3);
+    assert_eq!(peel.graph.edge_count(), 3);
+    assert!(peel.graph.find_edge(mapping[&p3], mapping[&p3]).is_some());
+    assert_eq!(peel.traverse(b"12333", vec![]).result.len(), 5);
+}
+
+#[test]
+fn peel_success_graft_reenter() {
+    let mut peel: Peel<()> = Peel::new();
+    let p1 = peel.new_parser(Parser1);
+    peel.max_depth = 1;
+
+    let mut subtree = Peel::new();
+    let p3 = subtree.new_parser(Parser3);
+    subtree.link_reenter(p3, p3);
+    subtree.max_depth = 8;
+
+    // The links keep their kind, but the depth is limited by the receiving tree
+    let mapping = peel.graft(p1, subtree);
+    let edge = peel.graph.find_edge(mapping[&p3], mapping[&p3]).unwrap();
+    assert_eq!(peel.graph[edge], LinkKind::Reenter);
+    assert_eq!(peel.max_depth, 1);
+    assert_eq!(peel.traverse(b"1333", vec![]).depths, vec![0, 0, 1]);
+
+    // Detaching keeps the maximum depth
+    let (mut subtree, mapping) = peel.detach(mapping[&p3]);
+    assert_eq!(subtree.max_depth, 1);
+    assert_eq!(subtree.traverse(b"333", vec![]).depths, vec![0, 1]);
+    subtree.max_depth = 2;
+    assert_eq!(subtree.traverse(b"333", vec![]).depths, vec![0, 1, 2]);
+    assert_eq!(mapping.len(), 1);
+}
+
+#[test]
+fn peel_success_detach() {
+    let mut peel = peel_example();
+    let p1 = peel.root.unwrap();
+    let p2 = peel.graph.neighbors(p1).find(|n| peel.graph[*n].name() == "Parser2").unwrap();
+    let p3 = peel.graph.neighbors(p1).find(|n| peel.graph[*n].name() == "Parser3").unwrap();
+    peel.add_root("three", p3);
+
+    // Parser3 is reachable from Parser1 as well and stays within the tree
+    let (mut subtree, mapping) = peel.detach(p2);
+    assert_eq!(mapping.len(), 1);
+    assert_eq!(subtree.graph.node_count(), 1);
+    assert_eq!(peel.graph.node_count(), 3);
+    assert_eq!(subtree.traverse(b"2", vec![]).result.len(), 1);
+
+    // Parser3 and Parser4 are only reachable via Parser3 now
+    let (mut subtree, mapping) = peel.detach(p3);
+    assert_eq!(mapping.len(), 2);
+    assert_eq!(peel.graph.node_count(), 1);
+    assert_eq!(subtree.graph.edge_count(), 2);
+    assert!(peel.get_root("three").is_none());
+    assert_eq!(subtree.get_root("three"), Some(mapping[&p3]));
+    assert_eq!(subtree.traverse(b"334", vec![]).result.len(), 3);
+}
+
+#[test]
+fn peel_success_detach_root() {
+    let mut peel = peel_example();
+    let root = peel.root.unwrap();
+    let (subtree, _) = peel.detach(root);
+    assert!(peel.root.is_none());
+    assert_eq!(peel.graph.node_count(), 0);
+    assert_eq!(subtree.graph.node_count(), 4);
+    assert_eq!(subtree.graph.edge_count(), 5);
+}
+
+#[test]
+fn peel_success_find() {
+    let peel = peel_example();
+    let p3 = peel.find("Parser3");
+    assert_eq!(p3.len(), 1);
+    assert!(peel.find("Parser5").is_empty());
+    assert_eq!(peel.find_by(|parser| parser.name().starts_with("Parser")).len(), 4);
+    assert_eq!(peel.find_type::<Parser3>(), p3);
+    assert_eq!(peel.find_type::<Parser1>(), vec![peel.root.unwrap()]);
+
+    let children: Vec<String> = peel.children(p3[0]).iter().map(|n| peel.graph[*n].name()).collect();
+    assert_eq!(children, vec!["Parser4", "Parser3"]);
+    assert_eq!(peel.parents(p3[0]).len(), 3);
+}
+
+#[test]
+fn peel_success_path() {
+    let peel = peel_example();
+    let p1 = peel.find("Parser1")[0];
+    let p3 = peel.find("Parser3")[0];
+    let p4 = peel.find("Parser4")[0];
+    assert_eq!(peel.path(p1, p4), Some(vec![p1, p3, p4]));
+    assert_eq!(peel.path(p3, p3), Some(vec![p3]));
+    assert!(peel.path(p4, p1).is_none());
+}
+
+#[test]
+fn peel_success_display() {
+    let mut peel = peel_example();
+    let p2 = peel.find("Parser2")[0];
+    peel.add_root("two", p2);
+    assert_eq!(format!("{}", peel),
+               "Parser1 (0)\n\
+                ├── Parser3 (2)\n\
+                │   ├── Parser4 (3)\n\
+                │   └── Parser3 (2) …\n\
+                └── Parser2 (1)\n    \
+                    └── Parser3 (2) …\n\
+                [two] Parser2 (1) …\n");
+}
+
+#[test]
+fn peel_success_traverse_node() {
+    let mut peel = peel_example();
+    let root = peel.root.unwrap();
+    let children = peel.children(root);
+
+    let result = peel.traverse_children(root, b"234", vec![Box::new(Parser1Result)]);
+    assert_eq!(result.result.len(), 4);
+    assert!(result.left_input.is_empty());
+    assert!(result.error.is_none());
+
+    let result = peel.traverse_node(children[0], b"34", vec![]);
+    assert_eq!(result.result.len(), 2);
+    assert_eq!(peel.last_position(), *result.path.last().unwrap());
+}
+
+#[test]
+fn peel_failure_traverse_node() {
+    let mut peel = peel_example();
+    let root = peel.root.unwrap();
+    peel.remove(root);
+    assert_eq!(peel.traverse_node(root, b"1234", vec![]).error.unwrap().code, ErrorType::NoTreeRoot);
+    assert!(peel.traverse_children(root, b"1234", vec![]).result.is_empty());
+}
@@ -0,0 +1,114 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{IpAddr, Ipv6Addr};
+
+/// Create an IPv6 packet from the given source carrying an ICMPv6 message with a valid checksum
+fn icmpv6_message(src: u8, kind: u8, header: [u8; 4], body: &[u8]) -> Vec<u8> {
+    let length = 8 + body.len();
+    let mut packet = vec![0x60, 0, 0, 0, (length >> 8) as u8, length as u8, 58, 255];
+    packet.extend_from_slice(&[0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, src]);
+    packet.extend_from_slice(&[0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let mut message = vec![kind, 0, 0, 0];
+    message.extend_from_slice(&header);
+    message.extend_from_slice(body);
+    let mut sum = ones_complement_sum(0, &packet[8..40]);
+    sum = ones_complement_sum(sum, &[0, 0, (length >> 8) as u8, length as u8, 0, 0, 0, 58]);
+    let checksum = fold_checksum(ones_complement_sum(sum, &message));
+    message[2] = (checksum >> 8) as u8;
+    message[3] = checksum as u8;
+
+    packet.extend_from_slice(&message);
+    packet
+}
+
+fn link_local(last: u8) -> Ipv6Addr {
+    Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, last as u16)
+}
+
+#[test]
+fn ndp_success_router_advertisement() {
+    let mut peel = packet_peel();
+    let mut body = vec![0, 0, 0x75, 0x30, 0, 0, 0x03, 0xe8];
+    body.extend_from_slice(&[1, 1, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+    body.extend_from_slice(&[5, 1, 0, 0, 0, 0, 0x05, 0xdc]);
+    body.extend_from_slice(&[3, 4, 64, 0xc0, 0, 0, 0x0e, 0x10, 0, 0, 0x07, 0x08, 0, 0, 0, 0]);
+    body.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let input = icmpv6_message(1, 134, [64, 0x80, 0x07, 0x08], &body);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 3);
+    let ndp = result.result[2].downcast_ref::<NdpPacket>().unwrap();
+    assert_eq!(ndp.message,
+               NdpMessage::RouterAdvertisement {
+                   hop_limit: 64,
+                   managed: true,
+                   other: false,
+                   lifetime: 1800,
+                   reachable_time: 30000,
+                   retrans_timer: 1000,
+               });
+    assert_eq!(ndp.source_link_layer_address(), Some(MacAddress([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])));
+    assert_eq!(ndp.options[1], NdpOption::Mtu(1500));
+    assert_eq!(ndp.options[2],
+               NdpOption::PrefixInformation {
+                   prefix_length: 64,
+                   on_link: true,
+                   autonomous: true,
+                   valid_lifetime: 3600,
+                   preferred_lifetime: 1800,
+                   prefix: "2001:db8::".parse().unwrap(),
+               });
+
+    let neighbors = peel.data.as_ref().unwrap().neighbors.as_ref().unwrap();
+    assert_eq!(neighbors.lookup(&IpAddr::V6(link_local(1))),
+               Some(MacAddress([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])));
+}
+
+#[test]
+fn ndp_success_neighbor_advertisement_conflict() {
+    let mut peel = packet_peel();
+    let mut body = vec![0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+    body.extend_from_slice(&[1, 1, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+    let input = icmpv6_message(2, 135, [0; 4], &body);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.result[2].downcast_ref::<NdpPacket>().unwrap().message,
+               NdpMessage::NeighborSolicitation { target: link_local(2) });
+
+    let mut body = vec![0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+    body.extend_from_slice(&[2, 1, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb]);
+    let input = icmpv6_message(3, 136, [0x60, 0, 0, 0], &body);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    let ndp = result.result[2].downcast_ref::<NdpPacket>().unwrap();
+    assert_eq!(ndp.message,
+               NdpMessage::NeighborAdvertisement {
+                   router: false,
+                   solicited: true,
+                   override_entry: true,
+                   target: link_local(2),
+               });
+    assert_eq!(ndp.conflict,
+               Some(NeighborConflict {
+                   ip: IpAddr::V6(link_local(2)),
+                   known: MacAddress([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]),
+                   announced: MacAddress([0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb]),
+               }));
+}
+
+#[test]
+fn ndp_failure_zero_option_length() {
+    let mut peel = packet_peel();
+    let input = icmpv6_message(1, 133, [0; 4], &[1, 0, 0, 0, 0, 0, 0, 0]);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+    assert!(peel.data.as_ref().unwrap().neighbors.as_ref().unwrap().is_empty());
+}
+
+#[test]
+fn ndp_failure_other_icmpv6() {
+    let mut peel = packet_peel();
+    let input = icmpv6_message(1, 128, [0, 1, 0, 1], &[]);
+    let result = peel.traverse_root("ipv6", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+}
@@ -0,0 +1,123 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+mod common;
+use common::{hex, ipv4_udp, CLIENT, SERVER};
+
+/// A protected Initial of the client with the connection ID of RFC 9001 appendix A, whose CRYPTO
+/// frames carry a ClientHello for example.com in two parts
+fn client_initial() -> Vec<u8> {
+    hex("ce00000001088394c8f03e51570804c0ffee010040771960d199ba7ec5699e55ef3e39ba4b652dbce103ea19f48ab8b2\
+         9d029dabdd4b7f1523367edb913218f090d51f2409d3c72715b61e86a9717ab54a3a8da643f4b45d5cd1062d7892ca994b\
+         629ea6438fd5cf8146fc0cff13d9aceaa86d741f3a6bda05876c2feeffd6d1d4aac3d921302527900d5da537")
+}
+
+fn quic(result: &ParserResult) -> &QuicPacket {
+    result.downcast_ref::<QuicPacket>().unwrap()
+}
+
+#[test]
+fn quic_success_initial_keys() {
+    // RFC 9001 appendix A.1
+    let keys = QuicKeys::client_initial(&hex("8394c8f03e515708"));
+    assert_eq!(keys.key.to_vec(), hex("1f369613dd76d5467730efcbe3b1a22d"));
+    assert_eq!(keys.iv.to_vec(), hex("fa044b2f42a3fd3b46fb255c"));
+    assert_eq!(keys.hp.to_vec(), hex("9f50449e04a0e810283a1e9933adedd2"));
+}
+
+#[test]
+fn quic_success_client_initial() {
+    let mut peel = packet_peel();
+    let mut datagram = client_initial();
+
+    // A coalesced handshake packet which can not be decrypted
+    datagram.extend_from_slice(&[0xe1, 0, 0, 0, 1, 4, 0xc0, 0xff, 0xee, 0x01, 0, 20]);
+    datagram.extend_from_slice(&[0xab; 20]);
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 443, &datagram);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 4);
+
+    let initial = quic(&result.result[2]);
+    assert_eq!(initial.kind, QuicPacketType::Initial);
+    assert_eq!(initial.version, QUIC_VERSION_1);
+    assert_eq!(initial.dcid, vec![0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]);
+    assert_eq!(initial.scid, vec![0xc0, 0xff, 0xee, 0x01]);
+    assert_eq!(initial.packet_number, Some(2));
+    assert_eq!(initial.frames.len(), 4);
+    assert_eq!(initial.frames[1], QuicFrame::Ping);
+    assert_eq!(initial.frames[3], QuicFrame::Padding(3));
+
+    let hello = initial.client_hello().unwrap();
+    assert_eq!(hello.server_name(), Some("example.com"));
+    assert_eq!(hello.alpn(), vec!["h3"]);
+    assert_eq!(hello.supported_versions(), vec![0x0304]);
+
+    let handshake = quic(&result.result[3]);
+    assert_eq!(handshake.kind, QuicPacketType::Handshake);
+    assert_eq!(handshake.packet_number, None);
+    assert!(handshake.frames.is_empty());
+}
+
+#[test]
+fn quic_success_other_headers() {
+    let mut peel = packet_peel();
+
+    // Version negotiation
+    let input = ipv4_udp(SERVER,
+                         CLIENT,
+                         443,
+                         50000,
+                         &[0x80, 0, 0, 0, 0, 0, 4, 1, 2, 3, 4, 0, 0, 0, 1, 0x6b, 0x33, 0x43, 0xcf]);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    let packet = quic(&result.result[2]);
+    assert_eq!(packet.kind, QuicPacketType::VersionNegotiation);
+    assert_eq!(packet.scid, vec![1, 2, 3, 4]);
+    assert_eq!(packet.supported_versions, vec![1, 0x6b3343cf]);
+
+    // Retry
+    let mut retry = vec![0xf0, 0, 0, 0, 1, 0, 0, b't', b'o', b'k'];
+    retry.extend_from_slice(&[0; 16]);
+    let input = ipv4_udp(SERVER, CLIENT, 443, 50000, &retry);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    let packet = quic(&result.result[2]);
+    assert_eq!(packet.kind, QuicPacketType::Retry);
+    assert_eq!(packet.token, b"tok");
+
+    // Short headers are only recognized by the port
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 443, &[0x61, 1, 2, 3, 4, 5]);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(quic(&result.result[2]).kind, QuicPacketType::Short { spin: true });
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 4433, &[0x61, 1, 2, 3, 4, 5]);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+}
+
+#[test]
+fn quic_failure_tampered() {
+    let mut peel = Peel::new();
+    peel.new_root("quic", QuicParser);
+    let mut packet = client_initial();
+    let last = packet.len() - 1;
+    packet[last] ^= 1;
+
+    let result = peel.traverse(&packet, vec![]);
+    assert!(result.error.is_none());
+    let initial = quic(&result.result[0]);
+    assert_eq!(initial.packet_number, None);
+    assert!(initial.frames.is_empty());
+    assert!(initial.client_hello().is_none());
+}
+
+#[test]
+fn quic_failure_unknown_version() {
+    let mut peel = packet_peel();
+    let mut packet = client_initial();
+    packet[4] = 2;
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 4433, &packet);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // The length exceeds the datagram
+    let packet = client_initial();
+    let input = ipv4_udp(CLIENT, SERVER, 50000, 443, &packet[..100]);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+}
@@ -0,0 +1,211 @@
+extern crate peel;
+use peel::packet::prelude::*;
+use peel::prelude::ErrorType;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+/// Create an IPv4 UDP fragment with a valid checksum
+fn ipv4_fragment(fragment: u16, payload: &[u8]) -> Vec<u8> {
+    let total_length = 20 + payload.len();
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0x12, 0x34,
+                          (fragment >> 8) as u8, fragment as u8, 64, 17, 0, 0, 192, 168, 0, 1, 10, 0, 0, 1];
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Create an IPv6 UDP fragment behind a destination options header
+fn ipv6_fragment(offset: u16, more_fragments: bool, payload: &[u8]) -> Vec<u8> {
+    let length = 16 + payload.len();
+    let mut packet = vec![0x60, 0, 0, 0, (length >> 8) as u8, length as u8, 60, 64];
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    packet.extend_from_slice(&[44, 0, 1, 4, 0, 0, 0, 0]);
+    let field = offset | more_fragments as u16;
+    packet.extend_from_slice(&[17, 0, (field >> 8) as u8, field as u8, 0, 0, 0, 42]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Create a UDP datagram without checksum carrying sixteen bytes
+fn udp_datagram() -> Vec<u8> {
+    let mut datagram = vec![0x04, 0xd2, 0, 53, 0, 24, 0, 0];
+    datagram.extend(0..16);
+    datagram
+}
+
+fn key() -> FragmentKey {
+    FragmentKey {
+        src: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+        dst: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        id: 1,
+        protocol: IpProtocol::Udp,
+    }
+}
+
+#[test]
+fn reassembly_success_ipv4_out_of_order() {
+    let mut peel = packet_peel();
+    let payload = udp_datagram();
+
+    let last = ipv4_fragment(0x0002, &payload[16..]);
+    let result = peel.traverse_root("ipv4", &last, vec![]);
+    assert_eq!(result.left_input, &payload[16..]);
+    assert_eq!(peel.data.as_ref().unwrap().fragments.as_ref().unwrap().pending(), 1);
+    peel.traverse_root("ipv4", &ipv4_fragment(0x2000, &payload[..16]), vec![]);
+    assert_eq!(peel.data.as_ref().unwrap().fragments.as_ref().unwrap().pending(), 0);
+
+    let mut count = 0;
+    peel.traverse_reassembled(|datagram, result| {
+        count += 1;
+        assert_eq!(datagram.key.id, 0x1234);
+        assert_eq!(datagram.key.protocol, IpProtocol::Udp);
+        assert_eq!(result.left_input, &payload[8..]);
+        assert_eq!(result.result[1].downcast_ref::<UdpPacket>().unwrap().length, 24);
+        let ipv4 = result.result[0].downcast_ref::<Ipv4Packet>().unwrap();
+        assert_eq!(ipv4.total_length, 44);
+        assert!(!ipv4.is_fragment());
+        assert!(ipv4.checksum_valid);
+    });
+    assert_eq!(count, 1);
+    assert_eq!(peel.data.as_ref().unwrap().fragments.as_ref().unwrap().size(), 0);
+}
+
+#[test]
+fn reassembly_success_ipv6() {
+    let mut peel = packet_peel();
+    let payload = udp_datagram();
+
+    peel.traverse_root("ipv6", &ipv6_fragment(0, true, &payload[..16]), vec![]);
+    peel.traverse_root("ipv6", &ipv6_fragment(16, false, &payload[16..]), vec![]);
+
+    let mut count = 0;
+    peel.traverse_reassembled(|datagram, result| {
+        count += 1;
+        assert_eq!(datagram.key.id, 42);
+        assert_eq!(datagram.key.protocol, IpProtocol::Udp);
+        assert_eq!(result.left_input, &payload[8..]);
+        assert_eq!(result.result[1].downcast_ref::<UdpPacket>().unwrap().length, 24);
+        let ipv6 = result.result[0].downcast_ref::<Ipv6Packet>().unwrap();
+        assert_eq!(ipv6.payload_length, 32);
+        assert!(ipv6.fragment().is_none());
+        assert_eq!(ipv6.extensions.len(), 1);
+        assert_eq!(ipv6.protocol, IpProtocol::Udp);
+    });
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn reassembly_success_link_padding() {
+    let mut peel = packet_peel();
+    let payload = udp_datagram();
+
+    // The link layer may pad the fragments or append its frame check sequence
+    for fragment in &[ipv6_fragment(0, true, &payload[..16]), ipv6_fragment(16, false, &payload[16..]),
+                      ipv4_fragment(0x2000, &payload[..16]), ipv4_fragment(0x0002, &payload[16..])] {
+        let mut input = fragment.clone();
+        input.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        peel.traverse_root(if input[0] >> 4 == 6 { "ipv6" } else { "ipv4" }, &input, vec![]);
+    }
+
+    let mut count = 0;
+    peel.traverse_reassembled(|datagram, result| {
+        count += 1;
+        assert_eq!(datagram.data.len(), if datagram.key.src.is_ipv6() { 72 } else { 44 });
+        assert!(result.error.is_none());
+        assert_eq!(result.left_input, &payload[8..]);
+        assert_eq!(result.result[1].downcast_ref::<UdpPacket>().unwrap().length, 24);
+    });
+    assert_eq!(count, 2);
+}
+
+#[test]
+fn reassembly_success_duplicates() {
+    let mut cache = FragmentCache::default();
+    let header = ipv4_fragment(0x2000, &[]);
+    let now = Instant::now();
+    assert!(!cache.insert(key(), 0, true, Some(&header), &[0; 8], now));
+    assert!(!cache.insert(key(), 0, true, Some(&header), &[0; 8], now));
+    assert_eq!(cache.size(), 28);
+    assert!(cache.insert(key(), 8, false, None, &[1; 8], now));
+    assert_eq!(cache.pop().unwrap().data.len(), 36);
+    assert!(cache.pop().is_none());
+}
+
+#[test]
+fn reassembly_failure_timeout() {
+    let mut cache = FragmentCache::default();
+    let header = ipv4_fragment(0x2000, &[]);
+    let now = Instant::now();
+    cache.insert(key(), 0, true, Some(&header), &[0; 8], now);
+    assert_eq!(cache.expire(now + Duration::from_secs(10)), 0);
+    assert_eq!(cache.expire(now + Duration::from_secs(30)), 1);
+    assert_eq!(cache.pending(), 0);
+    assert_eq!(cache.size(), 0);
+    assert!(!cache.insert(key(), 8, false, None, &[1; 8], now + Duration::from_secs(31)));
+}
+
+#[test]
+fn reassembly_failure_memory_limit() {
+    let mut cache = FragmentCache::new(Duration::from_secs(30), 64, 1);
+    let header = ipv4_fragment(0x2000, &[]);
+    let now = Instant::now();
+    cache.insert(key(), 0, true, Some(&header), &[0; 8], now);
+
+    // A second datagram replaces the oldest one
+    let mut other = key();
+    other.id = 2;
+    cache.insert(other, 0, true, Some(&header), &[0; 8], now);
+    assert_eq!(cache.pending(), 1);
+    assert_eq!(cache.size(), 28);
+
+    // Other datagrams are dropped before the one which receives the fragment
+    let mut cache = FragmentCache::new(Duration::from_secs(30), 64, 2);
+    cache.insert(key(), 0, true, Some(&header), &[0; 8], now);
+    cache.insert(other, 0, true, Some(&header), &[0; 8], now + Duration::from_secs(1));
+    assert!(cache.insert(key(), 8, false, None, &[1; 16], now + Duration::from_secs(2)));
+    let datagram = cache.pop().unwrap();
+    assert_eq!(datagram.key, key());
+    assert_eq!(datagram.data.len(), 44);
+    assert!(cache.pop().is_none());
+
+    // Reassembled datagrams which were not taken count into the limit
+    let mut cache = FragmentCache::new(Duration::from_secs(30), 64, 2);
+    assert!(cache.insert(key(), 0, false, Some(&header), &[0; 16], now));
+    assert_eq!(cache.size(), 36);
+    assert!(cache.insert(other, 0, false, Some(&header), &[0; 16], now));
+    assert_eq!(cache.size(), 36);
+    assert_eq!(cache.pop().unwrap().key, other);
+    assert!(cache.pop().is_none());
+
+    // A fragment larger than the whole cache is dropped
+    let later = now + Duration::from_secs(3);
+    cache.insert(other, 0, true, Some(&header), &[0; 8], later);
+    assert!(!cache.insert(other, 8, false, None, &[0; 65], later));
+    assert_eq!(cache.pending(), 0);
+    assert!(cache.pop().is_none());
+}
+
+#[test]
+fn reassembly_failure_missing_root() {
+    // The datagrams are dissected from the `ipv4` root of the tree
+    let mut peel = packet_peel();
+    let payload = udp_datagram();
+    peel.traverse_root("ipv4", &ipv4_fragment(0x2000, &payload[..16]), vec![]);
+    peel.traverse_root("ipv4", &ipv4_fragment(0x0002, &payload[16..]), vec![]);
+
+    let mut renamed = Peel::new();
+    renamed.new_root("ip", Ipv4Parser);
+    renamed.data = peel.data.take();
+    let mut count = 0;
+    renamed.traverse_reassembled(|datagram, result| {
+        count += 1;
+        assert_eq!(datagram.data.len(), 44);
+        assert_eq!(result.error.unwrap().code, ErrorType::NoTreeRoot);
+        assert!(result.result.is_empty());
+    });
+    assert_eq!(count, 1);
+}
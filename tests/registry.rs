@@ -0,0 +1,65 @@
+extern crate peel;
+use peel::example::prelude::*;
+use peel::prelude::{ParserInfo, Parameters, TreeConfig, ErrorType};
+
+#[test]
+fn registry_success_enumerate() {
+    let registry = example_registry();
+    let names: Vec<&str> = registry.parsers().map(|info| info.name.as_str()).collect();
+    assert_eq!(names, vec!["Parser1", "Parser2", "Parser3", "Parser4"]);
+    assert_eq!(registry.info("Parser2").unwrap().version, "1.0.0");
+}
+
+#[test]
+fn registry_success_unregister() {
+    let mut registry = example_registry();
+    assert_eq!(registry.unregister("Parser4").unwrap().name, "Parser4");
+    assert!(!registry.contains("Parser4"));
+    assert!(registry.unregister("Parser4").is_none());
+}
+
+#[test]
+fn registry_success_build_by_name() {
+    let registry = example_registry();
+    let parameters = Parameters::new();
+    let mut peel = Peel::new();
+    let p1 = peel.new_parser_by_name(&registry, "Parser1", &parameters).unwrap();
+    peel.link_new_parser_by_name(p1, &registry, "Parser2", &parameters).unwrap();
+    assert_eq!(peel.traverse(b"12", vec![]).result.len(), 2);
+}
+
+#[test]
+fn registry_success_custom_factory() {
+    let mut registry = example_registry();
+    registry.register(ParserInfo::new("Alias", "0.1.0", "Parser1 under another name"),
+                      |_| Ok(Box::new(Parser1)));
+    let mut peel = Peel::new();
+    peel.new_parser_by_name(&registry, "Alias", &Parameters::new()).unwrap();
+    assert_eq!(peel.traverse(b"1", vec![]).result.len(), 1);
+}
+
+#[test]
+fn registry_success_tree_roundtrip() {
+    let mut peel = peel_example();
+    let config = peel.to_config();
+    assert_eq!(config.parsers.len(), 4);
+    assert_eq!(config.parsers[0].parser, "Parser1");
+
+    let toml = config.to_toml().unwrap();
+    let mut restored = Peel::from_config(&TreeConfig::from_toml(&toml).unwrap(),
+                                         &example_registry())
+        .unwrap();
+    assert_eq!(restored.graph.edge_count(), peel.graph.edge_count());
+    assert_eq!(restored.to_config(), config);
+    assert_eq!(restored.traverse(b"1334", vec![]).result.len(),
+               peel.traverse(b"1334", vec![]).result.len());
+}
+
+#[test]
+fn registry_failure_unknown_parser() {
+    let mut peel = Peel::new();
+    let error = peel.new_parser_by_name(&example_registry(), "Parser5", &Parameters::new())
+        .err()
+        .unwrap();
+    assert_eq!(error.code, ErrorType::UnknownParser);
+}
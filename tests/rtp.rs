@@ -0,0 +1,161 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+fn rtp_peel() -> Peel<PacketData> {
+    let mut peel = Peel::new();
+    peel.new_root("rtp", RtpParser);
+    peel
+}
+
+fn rtcp_peel() -> Peel<PacketData> {
+    let mut peel = Peel::new();
+    peel.new_root("rtcp", RtcpParser);
+    peel
+}
+
+/// A sender report with a single report block, a source description and a padded goodbye
+fn compound_rtcp() -> Vec<u8> {
+    let mut packet = vec![0x81, 200, 0, 12, 0x12, 0x34, 0x56, 0x78];
+    packet.extend_from_slice(&[0xe0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0x0f, 0xa0, 0, 0, 0, 10, 0, 0, 6, 0x40]);
+    packet.extend_from_slice(&[0x9a, 0xbc, 0xde, 0xf0, 0x40, 0xff, 0xff, 0xfe, 0, 1, 0, 100, 0, 0, 0, 20, 0, 0, 0,
+                               0, 0, 0, 0, 0]);
+    packet.extend_from_slice(&[0x81, 202, 0, 3, 0x12, 0x34, 0x56, 0x78, 1, 5, b'a', b'l', b'i', b'c', b'e', 0]);
+    packet.extend_from_slice(&[0xa1, 203, 0, 3, 0x12, 0x34, 0x56, 0x78, 4, b'd', b'o', b'n', b'e', 0, 0, 3]);
+    packet
+}
+
+#[test]
+fn rtp_success_header() {
+    let mut peel = rtp_peel();
+    let mut input = vec![0xb1, 0xe0, 0x12, 0x34, 0, 0, 0x03, 0x20, 0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 7];
+    input.extend_from_slice(&[0xbe, 0xde, 0, 1, 0x10, 0xff, 0, 0]);
+    input.extend_from_slice(b"payload");
+    input.extend_from_slice(&[0, 0, 3]);
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"payload");
+    assert_eq!(result.result[0].downcast_ref::<RtpPacket>(),
+               Some(&RtpPacket {
+                   marker: true,
+                   payload_type: 96,
+                   sequence: 0x1234,
+                   timestamp: 800,
+                   ssrc: 0xcafebabe,
+                   csrc: vec![7],
+                   extension: Some(RtpExtension {
+                       profile: 0xbede,
+                       data: vec![0x10, 0xff, 0, 0],
+                   }),
+                   padding: 3,
+                   call_id: None,
+               }));
+}
+
+#[test]
+fn rtcp_success_compound() {
+    let mut peel = rtcp_peel();
+    let input = compound_rtcp();
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    assert!(result.left_input.is_empty());
+
+    let packet = result.result[0].downcast_ref::<RtcpPacket>().unwrap();
+    assert_eq!(packet.messages,
+               vec![RtcpMessage::SenderReport {
+                        ssrc: 0x12345678,
+                        ntp_timestamp: 0xe000000000000001,
+                        rtp_timestamp: 4000,
+                        packet_count: 10,
+                        octet_count: 1600,
+                        reports: vec![RtcpReportBlock {
+                                          ssrc: 0x9abcdef0,
+                                          fraction_lost: 0x40,
+                                          cumulative_lost: -2,
+                                          highest_sequence: 0x10064,
+                                          jitter: 20,
+                                          last_sr: 0,
+                                          delay_since_last_sr: 0,
+                                      }],
+                    },
+                    RtcpMessage::SourceDescription(vec![RtcpSourceDescription {
+                                                            ssrc: 0x12345678,
+                                                            items: vec![(1, "alice".to_owned())],
+                                                        }]),
+                    RtcpMessage::Goodbye {
+                        sources: vec![0x12345678],
+                        reason: Some("done".to_owned()),
+                    }]);
+}
+
+#[test]
+fn rtp_success_cache() {
+    let mut cache = RtpCache::new(Duration::from_secs(10), 16);
+    let address = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let now = Instant::now();
+    cache.expect(address, 4000, 4000, "call", "audio", now);
+    cache.expect(address, 6000, 6001, "other", "video", now);
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.lookup(address, 4000, now),
+               Some(&RtpExpectation {
+                   call_id: "call".to_owned(),
+                   media: "audio".to_owned(),
+                   rtcp: false,
+               }));
+    assert_eq!(cache.lookup(address, 6001, now).map(|e| e.rtcp), Some(true));
+
+    // Lookups keep the port alive
+    let later = now + Duration::from_secs(8);
+    assert!(cache.lookup(address, 4000, later).is_some());
+    assert_eq!(cache.expire(now + Duration::from_secs(12)), 2);
+    assert_eq!(cache.forget("call"), 1);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn rtp_success_cache_limit() {
+    let mut cache = RtpCache::new(Duration::from_secs(10), 3);
+    let address = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let now = Instant::now();
+    cache.expect(address, 4000, 4001, "first", "audio", now);
+    cache.expect(address, 5000, 5000, "second", "audio", now + Duration::from_secs(1));
+    assert_eq!(cache.len(), 3);
+
+    // The oldest ports are dropped, but never the ones of the same announcement
+    cache.lookup(address, 4000, now + Duration::from_secs(2));
+    cache.expect(address, 6000, 6001, "third", "video", now + Duration::from_secs(3));
+    assert_eq!(cache.len(), 3);
+    assert!(cache.lookup(address, 4001, now + Duration::from_secs(3)).is_none());
+    assert!(cache.lookup(address, 5000, now + Duration::from_secs(3)).is_none());
+    assert_eq!(cache.lookup(address, 4000, now + Duration::from_secs(3)).map(|e| e.rtcp), Some(false));
+    assert_eq!(cache.lookup(address, 6001, now + Duration::from_secs(3)).map(|e| e.rtcp), Some(true));
+    assert_eq!(cache.lookup(address, 6000, now + Duration::from_secs(3)).map(|e| e.rtcp), Some(false));
+}
+
+#[test]
+fn rtp_failure_invalid() {
+    let mut peel = rtp_peel();
+    let header = [0x80, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+
+    // Wrong version, RTCP packet type, missing sources and too much padding
+    let mut packets = vec![header.to_vec(), header.to_vec(), header.to_vec(), header.to_vec()];
+    packets[0][0] = 0x40;
+    packets[1][1] = 0xc8;
+    packets[2][0] = 0x82;
+    packets[3][0] = 0xa0;
+    packets[3].extend_from_slice(&[0, 3]);
+    for input in &packets {
+        assert!(peel.traverse(input, vec![]).error.is_some());
+    }
+
+    // RTCP with truncated messages, padding before the last message and RTP payload types
+    let mut peel = rtcp_peel();
+    let valid = compound_rtcp();
+    let mut padded = valid.clone();
+    padded[0] |= 0x20;
+    for input in &[valid[..valid.len() - 1].to_vec(), padded, header.to_vec()] {
+        assert!(peel.traverse(input, vec![]).error.is_some());
+    }
+}
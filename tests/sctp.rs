@@ -0,0 +1,178 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+/// Create an IPv4 packet carrying the SCTP packet
+fn ipv4_sctp(sctp: &[u8]) -> Vec<u8> {
+    let total_length = 20 + sctp.len();
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0, 0, 0x40, 0, 64, 132, 0, 0, 192,
+                          168, 0, 1, 10, 0, 0, 1];
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(sctp);
+    packet
+}
+
+/// Create a chunk or parameter padded to a multiple of four bytes
+fn tlv(header: &[u8], value: &[u8]) -> Vec<u8> {
+    let length = header.len() + 2 + value.len();
+    let mut tlv = header.to_vec();
+    tlv.extend_from_slice(&[(length >> 8) as u8, length as u8]);
+    tlv.extend_from_slice(value);
+    while tlv.len() % 4 != 0 {
+        tlv.push(0);
+    }
+    tlv
+}
+
+/// Create an SCTP packet from port 36412 to 3868 with a valid checksum
+fn sctp(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut packet = vec![0x8e, 0x3c, 0x0f, 0x1c, 0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 0];
+    for chunk in chunks {
+        packet.extend_from_slice(chunk);
+    }
+    let checksum = crc32c(&packet);
+    packet[8..12].copy_from_slice(&[checksum as u8, (checksum >> 8) as u8, (checksum >> 16) as u8,
+                                    (checksum >> 24) as u8]);
+    packet
+}
+
+fn data(flags: u8, tsn: u8, ppid: u8, payload: &[u8]) -> Vec<u8> {
+    let mut value = vec![0, 0, 0, tsn, 0, 1, 0, tsn, 0, 0, 0, ppid];
+    value.extend_from_slice(payload);
+    tlv(&[0, flags], &value)
+}
+
+fn sctp_packet(result: &ParserResult) -> &SctpPacket {
+    result.downcast_ref::<SctpPacket>().unwrap()
+}
+
+#[test]
+fn sctp_success_crc32c() {
+    assert_eq!(crc32c(b"123456789"), 0xe3069283);
+}
+
+#[test]
+fn sctp_success_init() {
+    let mut peel = packet_peel();
+    let mut init = vec![0x12, 0x34, 0x56, 0x78, 0, 1, 0, 0, 0, 10, 0xff, 0xff, 0, 0, 0, 1];
+    init.extend_from_slice(&tlv(&[0, 5], &[192, 168, 0, 1]));
+    init.extend_from_slice(&tlv(&[0xc0, 0], &[1]));
+    let input = ipv4_sctp(&sctp(&[tlv(&[1, 0], &init)]));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert!(result.left_input.is_empty());
+    assert_eq!(transport_ports(Some(&result.result)), Some((36412, 3868)));
+
+    let packet = sctp_packet(&result.result[1]);
+    assert!(packet.checksum_valid);
+    assert_eq!(packet.verification_tag, 0xdeadbeef);
+    assert_eq!(packet.chunks,
+               vec![SctpChunk::Init(SctpInit {
+                        initiate_tag: 0x12345678,
+                        a_rwnd: 0x10000,
+                        outbound_streams: 10,
+                        inbound_streams: 0xffff,
+                        initial_tsn: 1,
+                        parameters: vec![SctpParameter {
+                                             kind: 5,
+                                             value: vec![192, 168, 0, 1],
+                                         },
+                                         SctpParameter {
+                                             kind: 0xc000,
+                                             value: vec![1],
+                                         }],
+                    })]);
+}
+
+#[test]
+fn sctp_success_data() {
+    let mut peel = packet_peel();
+    let sack = tlv(&[3, 0], &[0, 0, 0, 7, 0, 1, 0, 0, 0, 1, 0, 1, 0, 2, 0, 3, 0, 0, 0, 5]);
+    let input = ipv4_sctp(&sctp(&[sack, data(0x03, 8, 46, b"diameter!"), data(0x07, 9, 18, b"s1ap")]));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"diameter!");
+    assert_eq!(next_sctp_ppid(Some(&result.result)), Some(SCTP_PPID_DIAMETER));
+
+    let packet = sctp_packet(&result.result[1]);
+    assert_eq!(packet.chunks[0],
+               SctpChunk::Sack {
+                   cumulative_tsn: 7,
+                   a_rwnd: 0x10000,
+                   gap_blocks: vec![(2, 3)],
+                   duplicates: vec![5],
+               });
+    let data = packet.data();
+    assert_eq!(data.len(), 2);
+    assert!(data[0].beginning && data[0].ending && !data[0].unordered);
+    assert_eq!(data[0].tsn, 8);
+    assert!(data[1].unordered);
+    assert_eq!(data[1].stream_id, 1);
+    assert_eq!(data[1].stream_sequence, 9);
+    assert_eq!(data[1].ppid, SCTP_PPID_S1AP);
+    assert_eq!(data[1].payload, b"s1ap");
+}
+
+#[test]
+fn sctp_success_control_chunks() {
+    let mut peel = Peel::new();
+    peel.new_root("sctp", SctpParser);
+    let mut input = sctp(&[tlv(&[4, 0], &[0, 1, 0, 6, 0xab, 0xcd]),
+                           tlv(&[6, 1], &tlv(&[0, 12], b"bye")),
+                           tlv(&[7, 0], &[0, 0, 0, 9]),
+                           tlv(&[11, 0], &[]),
+                           tlv(&[64, 0x80], &[1, 2, 3])]);
+
+    // The padding of the last chunk may be missing
+    input.truncate(input.len() - 1);
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    assert!(result.left_input.is_empty());
+    assert_eq!(sctp_packet(&result.result[0]).chunks,
+               vec![SctpChunk::Heartbeat(vec![0, 1, 0, 6, 0xab, 0xcd]),
+                    SctpChunk::Abort {
+                        reflected: true,
+                        causes: vec![SctpParameter {
+                                         kind: 12,
+                                         value: b"bye".to_vec(),
+                                     }],
+                    },
+                    SctpChunk::Shutdown { cumulative_tsn: 9 },
+                    SctpChunk::CookieAck,
+                    SctpChunk::Unknown {
+                        kind: 64,
+                        flags: 0x80,
+                        value: vec![1, 2, 3],
+                    }]);
+}
+
+#[test]
+fn sctp_failure_checksum() {
+    let mut peel = packet_peel();
+    let mut packet = sctp(&[data(0x03, 1, 46, b"diameter")]);
+    let last = packet.len() - 1;
+    packet[last] ^= 0xff;
+    let input = ipv4_sctp(&packet);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert!(!sctp_packet(&result.result[1]).checksum_valid);
+}
+
+#[test]
+fn sctp_failure_invalid() {
+    let mut peel = Peel::new();
+    peel.new_root("sctp", SctpParser);
+    let valid = sctp(&[data(0x03, 1, 46, b"diameter")]);
+
+    // Without chunks, with a too short chunk length and with a truncated chunk
+    let mut short = valid.clone();
+    short[15] = 3;
+    for input in &[valid[..12].to_vec(), short, valid[..valid.len() - 2].to_vec()] {
+        assert!(peel.traverse(input, vec![]).error.is_some());
+    }
+
+    // A DATA chunk needs its header
+    let input = sctp(&[tlv(&[0, 3], &[0; 8])]);
+    assert!(peel.traverse(&input, vec![]).error.is_some());
+}
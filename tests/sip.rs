@@ -0,0 +1,179 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{IpAddr, Ipv4Addr};
+
+mod common;
+use common::ipv4_udp;
+
+/// The address of the caller
+const ALICE: [u8; 4] = [10, 0, 0, 1];
+
+/// The address of the callee
+const BOB: [u8; 4] = [10, 0, 0, 2];
+
+/// Create a SIP message with the given start line, headers and SDP body
+fn sip(start: &str, headers: &str, sdp: &str) -> Vec<u8> {
+    let method = if start.starts_with("SIP/2.0") { "INVITE" } else { start.split(' ').next().unwrap() };
+    let mut message = format!("{}\r\n{}\r\nCSeq: 1 {}\r\ni: a84b4c76e66710\r\n", start, headers, method);
+    if !sdp.is_empty() {
+        message.push_str("c: application/sdp\r\n");
+    }
+    message.push_str(&format!("l: {}\r\n\r\n{}", sdp.len(), sdp));
+    message.into_bytes()
+}
+
+fn invite() -> Vec<u8> {
+    sip("INVITE sip:bob@example.com SIP/2.0",
+        "Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds\r\nMax-Forwards: 70",
+        "v=0\r\no=alice 2890844526 2890844526 IN IP4 10.0.0.1\r\ns=-\r\nc=IN IP4 10.0.0.1\r\nt=0 0\r\n\
+         m=audio 49170 RTP/AVP 0 8 97\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\nm=video 0 RTP/AVP 31\r\n")
+}
+
+fn ok() -> Vec<u8> {
+    sip("SIP/2.0 200 OK",
+        "Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds",
+        "v=0\r\no=bob 2808844564 2808844564 IN IP4 10.0.0.2\r\ns=-\r\nt=0 0\r\n\
+         m=audio 5004 RTP/AVP 0\r\nc=IN IP4 10.0.0.2\r\na=rtcp-mux\r\n")
+}
+
+fn rtp(sequence: u8) -> Vec<u8> {
+    let mut packet = vec![0x80, 0x00, 0, sequence, 0, 0, 0, 160, 0x12, 0x34, 0x56, 0x78];
+    packet.extend_from_slice(b"audio");
+    packet
+}
+
+fn rtcp() -> Vec<u8> {
+    vec![0x80, 201, 0, 1, 0x12, 0x34, 0x56, 0x78]
+}
+
+fn sip_packet(result: &ParserResult) -> &SipPacket {
+    result.downcast_ref::<SipPacket>().unwrap()
+}
+
+#[test]
+fn sip_success_invite() {
+    let mut peel = packet_peel();
+    let input = ipv4_udp(ALICE, BOB, SIP_PORT, SIP_PORT, &invite());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert!(result.left_input.is_empty());
+
+    let packet = sip_packet(&result.result[2]);
+    assert_eq!(packet.start,
+               SipStart::Request {
+                   method: "INVITE".to_owned(),
+                   uri: "sip:bob@example.com".to_owned(),
+               });
+    assert_eq!(packet.method(), Some("INVITE"));
+    assert_eq!(packet.call_id(), Some("a84b4c76e66710"));
+    assert_eq!(packet.cseq(), Some((1, "INVITE")));
+    assert_eq!(packet.headers.get("Max-Forwards"), Some("70"));
+    assert_eq!(packet.headers.content_type(), Some("application/sdp"));
+
+    let sdp = packet.sdp.as_ref().unwrap();
+    assert_eq!(sdp.origin, "alice 2890844526 2890844526 IN IP4 10.0.0.1");
+    assert_eq!(sdp.connection, Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+    assert_eq!(sdp.media.len(), 2);
+    let audio = &sdp.media[0];
+    assert_eq!(audio.kind, "audio");
+    assert_eq!(audio.port, 49170);
+    assert_eq!(audio.protocol, "RTP/AVP");
+    assert_eq!(audio.formats, vec!["0", "8", "97"]);
+    assert_eq!(audio.address(sdp), sdp.connection);
+    assert_eq!(audio.rtcp_port(), 49171);
+    assert_eq!(audio.attribute("rtpmap"), Some("0 PCMU/8000"));
+    assert_eq!(audio.attributes[1],
+               SdpAttribute {
+                   name: "sendrecv".to_owned(),
+                   value: None,
+               });
+
+    // The rejected video stream is not expected
+    assert_eq!(peel.data.as_ref().unwrap().rtp.as_ref().unwrap().len(), 2);
+}
+
+#[test]
+fn sip_success_media_routing() {
+    let mut peel = packet_peel();
+    for input in &[ipv4_udp(ALICE, BOB, SIP_PORT, SIP_PORT, &invite()),
+                   ipv4_udp(BOB, ALICE, SIP_PORT, SIP_PORT, &ok())] {
+        assert!(peel.traverse_root("ipv4", input, vec![]).error.is_none());
+    }
+    assert_eq!(peel.data.as_ref().unwrap().rtp.as_ref().unwrap().len(), 3);
+
+    // Media sent to the announced ports
+    let input = ipv4_udp(BOB, ALICE, 5004, 49170, &rtp(1));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"audio");
+    let packet = result.result[2].downcast_ref::<RtpPacket>().unwrap();
+    assert_eq!(packet.sequence, 1);
+    assert_eq!(packet.call_id, Some("a84b4c76e66710".to_owned()));
+
+    let input = ipv4_udp(BOB, ALICE, 5005, 49171, &rtcp());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.result[2].downcast_ref::<RtcpPacket>().is_some());
+
+    // Multiplexed RTCP on the RTP port
+    let input = ipv4_udp(ALICE, BOB, 49171, 5004, &rtcp());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.result[2].downcast_ref::<RtcpPacket>().is_some());
+    let input = ipv4_udp(ALICE, BOB, 49170, 5004, &rtp(2));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.result[2].downcast_ref::<RtpPacket>().is_some());
+
+    // Unknown ports are not interpreted
+    let input = ipv4_udp(BOB, ALICE, 5006, 40000, &rtp(3));
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // The call ends
+    let bye = sip("BYE sip:alice@10.0.0.1 SIP/2.0", "Via: SIP/2.0/UDP 10.0.0.2:5060", "");
+    let input = ipv4_udp(BOB, ALICE, SIP_PORT, SIP_PORT, &bye);
+    assert!(peel.traverse_root("ipv4", &input, vec![]).error.is_none());
+    assert!(peel.data.as_ref().unwrap().rtp.as_ref().unwrap().is_empty());
+    let input = ipv4_udp(BOB, ALICE, 5004, 49170, &rtp(4));
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+}
+
+#[test]
+fn sip_success_stream() {
+    let mut peel = Peel::new();
+    peel.new_root("sip", SipParser);
+    let mut input = b"SIP/2.0 180 Ringing\r\nCall-ID: 1\r\nContent-Length: 0\r\n\r\n".to_vec();
+    input.extend_from_slice(b"OPTIONS");
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"OPTIONS");
+    assert_eq!(sip_packet(&result.result[0]).start,
+               SipStart::Response {
+                   status: 180,
+                   reason: "Ringing".to_owned(),
+               });
+
+    // The body is still missing or its size can not be represented
+    let input = b"MESSAGE sip:bob@example.com SIP/2.0\r\nContent-Length: 5\r\n\r\nhi";
+    assert!(peel.traverse(input, vec![]).error.is_some());
+    let input = b"MESSAGE sip:bob@example.com SIP/2.0\r\nContent-Length: 18446744073709551615\r\n\r\n";
+    assert!(peel.traverse(input, vec![]).error.is_some());
+}
+
+#[test]
+fn sip_failure_invalid() {
+    let mut peel = packet_peel();
+
+    // Unsupported version and truncated datagrams
+    let input = ipv4_udp(ALICE, BOB, SIP_PORT, SIP_PORT, b"INVITE sip:bob@example.com SIP/3.0\r\n\r\n");
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+    let message = invite();
+    let input = ipv4_udp(ALICE, BOB, SIP_PORT, SIP_PORT, &message[..message.len() - 1]);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // Invalid session descriptions are not interpreted
+    let message = sip("INVITE sip:bob@example.com SIP/2.0", "Max-Forwards: 70", "v=0\r\nm=audio x RTP/AVP 0\r\n");
+    let input = ipv4_udp(ALICE, BOB, SIP_PORT, SIP_PORT, &message);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert!(sip_packet(&result.result[2]).sdp.is_none());
+    assert!(peel.data.as_ref().unwrap().rtp.as_ref().unwrap().is_empty());
+}
@@ -0,0 +1,166 @@
+#[macro_use]
+extern crate nom;
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+use nom::{IResult, ErrorKind, Needed};
+
+/// Parses newline terminated lines of a stream
+#[derive(Debug)]
+struct LineParser;
+
+impl Parsable<PacketData> for LineParser {
+    fn parse<'a>(&mut self,
+                 input: &'a [u8],
+                 result: Option<&ParserResultVec>,
+                 _: Option<&mut PacketData>)
+                 -> IResult<&'a [u8], ParserResult> {
+        if tcp_stream(result).is_none() {
+            return IResult::Error(error_position!(ErrorKind::Tag, input));
+        }
+        match input.iter().position(|b| *b == b'\n') {
+            Some(end) => IResult::Done(&input[end + 1..], Box::new(input[..end].to_vec())),
+            None => IResult::Incomplete(Needed::Unknown),
+        }
+    }
+}
+
+const SYN: u8 = 0x02;
+const ACK: u8 = 0x10;
+const FIN: u8 = 0x11;
+const RST: u8 = 0x04;
+
+/// Create an IPv4 packet carrying a TCP segment from port 50000 to 80
+fn segment(sequence: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let total_length = 40 + payload.len();
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0, 0, 0x40, 0, 64, 6, 0, 0, 192,
+                          168, 0, 1, 10, 0, 0, 1];
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(&[0xc3, 0x50, 0, 80, (sequence >> 24) as u8, (sequence >> 16) as u8,
+                               (sequence >> 8) as u8, sequence as u8, 0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0,
+                               0, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn stream_peel() -> Peel<PacketData> {
+    let mut peel = packet_peel();
+    let tcp = peel.get_root("tcp").unwrap();
+    peel.link_new_parser(tcp, LineParser);
+    peel
+}
+
+fn lines(peel: &mut Peel<PacketData>) -> Vec<Vec<u8>> {
+    let mut lines = vec![];
+    peel.traverse_streams(|stream, result| {
+        assert_eq!(stream.key.dst_port, 80);
+        assert!(result.error.is_none());
+        assert!(result.result[0].downcast_ref::<TcpStream>().is_some());
+        lines.push(result.result[1].downcast_ref::<Vec<u8>>().unwrap().clone());
+    });
+    lines
+}
+
+fn key() -> StreamKey {
+    StreamKey {
+        src: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+        src_port: 50000,
+        dst: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        dst_port: 80,
+    }
+}
+
+#[test]
+fn stream_success_ordering() {
+    let mut peel = stream_peel();
+    peel.traverse_root("ipv4", &segment(0xfffffffe, SYN, &[]), vec![]);
+    peel.traverse_root("ipv4", &segment(2, ACK, b"lo\nwor"), vec![]);
+    assert!(lines(&mut peel).is_empty());
+    peel.traverse_root("ipv4", &segment(0xffffffff, ACK, b"hel"), vec![]);
+    assert_eq!(lines(&mut peel), vec![b"hello".to_vec()]);
+
+    // Retransmissions and overlaps are ignored
+    peel.traverse_root("ipv4", &segment(0xffffffff, ACK, b"hel"), vec![]);
+    peel.traverse_root("ipv4", &segment(8, FIN, b"ld\n"), vec![]);
+    assert_eq!(lines(&mut peel), vec![b"world".to_vec()]);
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().streams(), 0);
+}
+
+#[test]
+fn stream_success_resume() {
+    let mut peel = stream_peel();
+    peel.traverse_root("ipv4", &segment(100, ACK, b"first\nsec"), vec![]);
+    assert_eq!(lines(&mut peel), vec![b"first".to_vec()]);
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().buffered(&key()), 3);
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().size(), 3);
+    peel.traverse_root("ipv4", &segment(109, ACK, b"ond\nthird\n"), vec![]);
+    assert_eq!(lines(&mut peel), vec![b"second".to_vec(), b"third".to_vec()]);
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().buffered(&key()), 0);
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().size(), 0);
+}
+
+#[test]
+fn stream_success_reset() {
+    let mut peel = stream_peel();
+    peel.traverse_root("ipv4", &segment(100, ACK, b"incomplete"), vec![]);
+    peel.traverse_root("ipv4", &segment(110, RST, &[]), vec![]);
+    assert!(lines(&mut peel).is_empty());
+    assert_eq!(peel.data.as_ref().unwrap().streams.as_ref().unwrap().streams(), 0);
+}
+
+#[test]
+fn stream_success_overlap() {
+    let mut cache = StreamCache::default();
+    let now = Instant::now();
+    let flags = TcpFlags::default();
+    assert!(cache.insert(key(), 0, flags, b"abc", now));
+    assert!(!cache.insert(key(), 5, flags, b"fgh", now));
+    assert!(cache.insert(key(), 2, flags, b"cdefghij", now));
+    assert_eq!(cache.buffered(&key()), 10);
+    assert!(!cache.insert(key(), 1, flags, b"bc", now));
+    assert_eq!(cache.buffered(&key()), 10);
+}
+
+#[test]
+fn stream_failure_limits() {
+    let mut cache = StreamCache::new(Duration::from_secs(10), 4, 1, 16);
+    let now = Instant::now();
+    let flags = TcpFlags::default();
+    cache.insert(key(), 0, flags, b"ab", now);
+    assert!(!cache.insert(key(), 10, flags, b"abcde", now));
+    assert!(!cache.insert(key(), 3, flags, b"d", now));
+
+    // Ordered data beyond the limit drops the direction
+    assert!(cache.insert(key(), 2, flags, b"c", now));
+    assert!(!cache.insert(key(), 3, flags, b"de", now));
+    assert_eq!(cache.streams(), 0);
+    cache.insert(key(), 5, flags, b"ab", now);
+
+    // The oldest direction is replaced
+    cache.insert(key().reverse(), 0, flags, b"ab", now + Duration::from_secs(1));
+    assert_eq!(cache.streams(), 1);
+    assert_eq!(cache.buffered(&key()), 0);
+    assert_eq!(cache.expire(now + Duration::from_secs(11)), 1);
+}
+
+#[test]
+fn stream_failure_total_limit() {
+    let mut cache = StreamCache::new(Duration::from_secs(10), 8, 4, 10);
+    let now = Instant::now();
+    let flags = TcpFlags::default();
+    assert!(cache.insert(key(), 0, flags, b"abcdef", now));
+    assert!(!cache.insert(key(), 10, flags, b"xy", now));
+    assert_eq!(cache.size(), 8);
+
+    // The most idle direction makes room for the other one
+    assert!(cache.insert(key().reverse(), 0, flags, b"abcdef", now + Duration::from_secs(1)));
+    assert_eq!(cache.streams(), 1);
+    assert_eq!(cache.buffered(&key()), 0);
+    assert_eq!(cache.buffered(&key().reverse()), 6);
+    assert_eq!(cache.size(), 6);
+}
@@ -0,0 +1,125 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+/// Create a TCP segment with the given options, which have to be padded to four bytes
+fn tcp_segment(flags: u8, options: &[u8], payload: &[u8]) -> Vec<u8> {
+    let offset = ((20 + options.len()) / 4) as u8;
+    let mut segment = vec![0xc3, 0x50, 0x01, 0xbb, 0, 0, 0, 1, 0, 0, 0, 2, offset << 4, flags, 0xff, 0xff, 0x12,
+                           0x34, 0, 0];
+    segment.extend_from_slice(options);
+    segment.extend_from_slice(payload);
+    segment
+}
+
+/// Create an IPv4 packet carrying the given TCP segment
+fn ipv4_packet(segment: &[u8]) -> Vec<u8> {
+    let total_length = 20 + segment.len();
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0, 0, 0x40, 0, 64, 6, 0, 0, 192,
+                          168, 0, 1, 10, 0, 0, 1];
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(segment);
+    packet
+}
+
+#[test]
+fn tcp_success_header() {
+    let mut peel = packet_peel();
+    let input = ipv4_packet(&tcp_segment(0x18, &[], b"GET"));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 2);
+    assert_eq!(result.left_input, b"GET");
+    let tcp = result.result[1].downcast_ref::<TcpPacket>().unwrap();
+    assert_eq!(tcp.src_port, 50000);
+    assert_eq!(tcp.dst_port, 443);
+    assert_eq!(tcp.sequence, 1);
+    assert_eq!(tcp.acknowledgement, 2);
+    assert_eq!(tcp.header_length, 20);
+    assert!(tcp.flags.ack && tcp.flags.psh);
+    assert!(!tcp.flags.syn && !tcp.flags.fin && !tcp.flags.rst);
+    assert_eq!(tcp.window, 0xffff);
+    assert_eq!(tcp.checksum, 0x1234);
+    assert!(tcp.options.is_empty());
+}
+
+#[test]
+fn tcp_success_syn_options() {
+    let mut peel = packet_peel();
+    let options = [2, 4, 0x05, 0xb4, // MSS 1460
+                   4, 2, // SACK permitted
+                   8, 10, 0, 0, 0, 1, 0, 0, 0, 0, // Timestamps
+                   1, // NOP
+                   3, 3, 7, // Window scale
+                   34, 2, // Fast Open cookie request
+                   0, 0]; // End of list
+    let input = ipv4_packet(&tcp_segment(0x02, &options, &[]));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.left_input.is_empty());
+    let tcp = result.result[1].downcast_ref::<TcpPacket>().unwrap();
+    assert!(tcp.flags.syn);
+    assert_eq!(tcp.header_length, 44);
+    assert_eq!(tcp.options,
+               vec![TcpOption::MaximumSegmentSize(1460),
+                    TcpOption::SackPermitted,
+                    TcpOption::Timestamps {
+                        value: 1,
+                        echo_reply: 0,
+                    },
+                    TcpOption::NoOperation,
+                    TcpOption::WindowScale(7),
+                    TcpOption::FastOpen(vec![]),
+                    TcpOption::EndOfList]);
+}
+
+#[test]
+fn tcp_success_sack_blocks() {
+    let mut peel = packet_peel();
+    let options = [1, 1, 5, 18, 0, 0, 0, 10, 0, 0, 0, 20, 0, 0, 0, 30, 0, 0, 0, 40, 254, 6, 0xf9, 0x89, 1, 2, 1, 1];
+    let input = ipv4_packet(&tcp_segment(0x10, &options, &[]));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    let tcp = result.result[1].downcast_ref::<TcpPacket>().unwrap();
+    assert_eq!(tcp.options[2], TcpOption::Sack(vec![(10, 20), (30, 40)]));
+    assert_eq!(tcp.options[3], TcpOption::FastOpen(vec![1, 2]));
+}
+
+#[test]
+fn tcp_failure_invalid_option() {
+    let mut peel = packet_peel();
+    let input = ipv4_packet(&tcp_segment(0x02, &[2, 3, 0, 0], &[]));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 1);
+    assert!(result.failed.contains(&peel.get_root("tcp").unwrap()));
+}
+
+#[test]
+fn tcp_failure_data_offset() {
+    let mut peel = Peel::new();
+    peel.new_parser(TcpParser);
+
+    // Offset smaller than the minimal header
+    let mut segment = tcp_segment(0x10, &[], &[]);
+    segment[12] = 0x40;
+    assert!(peel.traverse(&segment, vec![]).error.is_some());
+
+    // Offset exceeds the available data
+    segment[12] = 0x60;
+    let result = peel.traverse(&segment, vec![]);
+    assert!(result.result.is_empty());
+    assert!(result.error.is_some());
+}
+
+#[test]
+fn tcp_failure_not_announced() {
+    let mut peel = packet_peel();
+    let mut input = ipv4_packet(&tcp_segment(0x10, &[], &[]));
+    input[9] = 17;
+    input[10] = 0;
+    input[11] = 0;
+    let checksum = internet_checksum(&input[..20]);
+    input[10] = (checksum >> 8) as u8;
+    input[11] = checksum as u8;
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result.len(), 1);
+    assert_eq!(result.left_input.len(), 20);
+}
@@ -0,0 +1,186 @@
+extern crate peel;
+use peel::packet::prelude::*;
+use peel::error::ErrorType;
+
+mod common;
+use common::ipv4_tcp;
+
+/// Prefix the data with its length of the given number of bytes
+fn prefixed(bytes: usize, data: &[u8]) -> Vec<u8> {
+    let mut prefixed: Vec<u8> = (0..bytes).rev().map(|i| (data.len() >> (8 * i)) as u8).collect();
+    prefixed.extend_from_slice(data);
+    prefixed
+}
+
+fn record(content_type: u8, payload: &[u8]) -> Vec<u8> {
+    let mut record = vec![content_type, 3, 3];
+    record.extend_from_slice(&prefixed(2, payload));
+    record
+}
+
+fn handshake(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend_from_slice(&prefixed(3, body));
+    message
+}
+
+fn extension(kind: u16, data: &[u8]) -> Vec<u8> {
+    let mut extension = vec![(kind >> 8) as u8, kind as u8];
+    extension.extend_from_slice(&prefixed(2, data));
+    extension
+}
+
+/// A TLS 1.3 client hello with GREASE values
+fn client_hello() -> Vec<u8> {
+    let mut body = vec![3, 3];
+    body.extend_from_slice(&[0x11; 32]);
+    body.push(0);
+    body.extend_from_slice(&prefixed(2, &[0x0a, 0x0a, 0x13, 0x01, 0x13, 0x02, 0xc0, 0x2f]));
+    body.extend_from_slice(&[1, 0]);
+
+    let mut extensions = extension(0x1a1a, &[]);
+    let mut name = vec![0];
+    name.extend_from_slice(&prefixed(2, b"example.com"));
+    extensions.extend_from_slice(&extension(0, &prefixed(2, &name)));
+    extensions.extend_from_slice(&extension(10, &prefixed(2, &[0x2a, 0x2a, 0, 0x1d, 0, 0x17])));
+    extensions.extend_from_slice(&extension(11, &[1, 0]));
+    extensions.extend_from_slice(&extension(16, &prefixed(2, b"\x02h2\x08http/1.1")));
+    extensions.extend_from_slice(&extension(43, &[4, 3, 4, 3, 3]));
+    let mut share = vec![0, 0x1d];
+    share.extend_from_slice(&prefixed(2, &[0x22; 32]));
+    extensions.extend_from_slice(&extension(51, &prefixed(2, &share)));
+    body.extend_from_slice(&prefixed(2, &extensions));
+    handshake(1, &body)
+}
+
+/// A TLS 1.2 server hello followed by a certificate and the end of the hello
+fn server_flight() -> Vec<u8> {
+    let mut body = vec![3, 3];
+    body.extend_from_slice(&[0x33; 32]);
+    body.extend_from_slice(&prefixed(1, &[0x44; 32]));
+    body.extend_from_slice(&[0xc0, 0x2f, 0]);
+    let mut extensions = extension(0, &[]);
+    extensions.extend_from_slice(&extension(16, &prefixed(2, b"\x02h2")));
+    body.extend_from_slice(&prefixed(2, &extensions));
+
+    let mut flight = handshake(2, &body);
+    let mut chain = prefixed(3, &[0x30; 300]);
+    chain.extend_from_slice(&prefixed(3, &[0x31; 200]));
+    flight.extend_from_slice(&handshake(11, &prefixed(3, &chain)));
+    flight.extend_from_slice(&handshake(14, &[]));
+    flight
+}
+
+fn tls(result: &ParserResult) -> &TlsPacket {
+    result.downcast_ref::<TlsPacket>().unwrap()
+}
+
+#[test]
+fn tls_success_client_hello() {
+    let mut peel = packet_peel();
+    let input = ipv4_tcp(443, 1000, 0x18, &record(22, &client_hello()));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.result.len(), 3);
+
+    let hello = tls(&result.result[2]).client_hello().unwrap();
+    assert_eq!(hello.cipher_suites, vec![0x0a0a, 0x1301, 0x1302, 0xc02f]);
+    assert_eq!(hello.server_name(), Some("example.com"));
+    assert_eq!(hello.alpn(), vec!["h2", "http/1.1"]);
+    assert_eq!(hello.supported_versions(), vec![0x0304, 0x0303]);
+    assert_eq!(hello.extensions[6],
+               TlsExtension::KeyShare(vec![TlsKeyShare {
+                                               group: 0x1d,
+                                               key_exchange: vec![0x22; 32],
+                                           }]));
+    assert_eq!(hello.fingerprint().to_string(),
+               "771,4865-4866-49199,0-10-11-16-43-51,29-23,0");
+}
+
+#[test]
+fn tls_success_fragmented_handshake() {
+    let mut peel = packet_peel();
+    let flight = server_flight();
+    let mut records = record(22, &flight[..100]);
+    records.extend_from_slice(&record(22, &flight[100..]));
+    records.extend_from_slice(&record(21, &[1, 0]));
+    let input = ipv4_tcp(443, 1000, 0x18, &records);
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, &records[records.len() - 7..]);
+
+    let packet = tls(&result.result[2]);
+    assert_eq!(packet.records, 2);
+    let hello = packet.server_hello().unwrap();
+    assert_eq!(hello.cipher_suite, 0xc02f);
+    assert_eq!(hello.selected_version(), 0x0303);
+    assert_eq!(hello.alpn(), Some("h2"));
+    match packet.message {
+        TlsMessage::Handshake(ref messages) => {
+            assert_eq!(messages.len(), 3);
+            assert_eq!(messages[1],
+                       TlsHandshake::Certificate(vec![vec![0x30; 300], vec![0x31; 200]]));
+            assert_eq!(messages[2],
+                       TlsHandshake::Other {
+                           kind: 14,
+                           data: vec![],
+                       });
+        }
+        ref message => panic!("Unexpected message {:?}", message),
+    }
+}
+
+#[test]
+fn tls_success_stream() {
+    let mut peel = packet_peel();
+    let mut stream = record(22, &client_hello());
+    stream.extend_from_slice(&record(20, &[1]));
+    stream.extend_from_slice(&record(22, &[0, 0, 0, 0, 0, 0, 0, 0, 0x9a, 0xbc, 0xde, 0xf0, 1, 2, 3, 4]));
+    stream.extend_from_slice(&record(23, &[0x55; 40]));
+    peel.traverse_root("ipv4", &ipv4_tcp(443, 1000, 0x18, &stream[..50]), vec![]);
+    peel.traverse_root("ipv4", &ipv4_tcp(443, 1050, 0x18, &stream[50..]), vec![]);
+
+    let mut packets = vec![];
+    peel.traverse_streams(|_, result| {
+        assert!(result.error.is_none());
+        packets.push(tls(&result.result[1]).clone());
+    });
+    assert_eq!(packets.len(), 4);
+    assert!(packets[0].client_hello().is_some());
+    assert_eq!(packets[1].message, TlsMessage::ChangeCipherSpec);
+
+    // The encrypted finished message can not be interpreted
+    assert_eq!(packets[2].message,
+               TlsMessage::Opaque {
+                   content_type: 22,
+                   length: 16,
+               });
+    assert_eq!(packets[3].message,
+               TlsMessage::Opaque {
+                   content_type: 23,
+                   length: 40,
+               });
+}
+
+#[test]
+fn tls_failure_invalid() {
+    let mut peel = Peel::new();
+    peel.new_root("tls", TlsParser);
+    let mut wrong_version = record(22, &client_hello());
+    wrong_version[1] = 2;
+    for input in &[b"GET / HTTP/1.1\r\n\r\n".to_vec(), wrong_version, record(25, &[0; 4])] {
+        let result = peel.traverse(input, vec![]);
+        match result.error.map(|e| e.code) {
+            Some(ErrorType::NoParserSucceed) => {}
+            code => panic!("Unexpected error {:?} for {:?}", code, input),
+        }
+    }
+
+    // Truncated records need more data
+    let input = record(22, &client_hello());
+    let result = peel.traverse(&input[..100], vec![]);
+    match result.error.map(|e| e.code) {
+        Some(ErrorType::Incomplete(_)) => {}
+        code => panic!("Unexpected error {:?}", code),
+    }
+}
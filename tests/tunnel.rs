@@ -0,0 +1,172 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+use std::net::Ipv4Addr;
+
+/// Create an IPv4 packet with the given protocol and payload
+fn ipv4(protocol: u8, dst: u8, payload: &[u8]) -> Vec<u8> {
+    let total_length = 20 + payload.len();
+    let mut packet = vec![0x45, 0, (total_length >> 8) as u8, total_length as u8, 0, 0, 0x40, 0, 64, protocol, 0,
+                          0, 192, 168, 0, 1, 10, 0, 0, dst];
+    let checksum = internet_checksum(&packet);
+    packet[10] = (checksum >> 8) as u8;
+    packet[11] = checksum as u8;
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Create a UDP datagram without checksum
+fn udp(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let length = 8 + payload.len();
+    let mut datagram = vec![(src_port >> 8) as u8, src_port as u8, (dst_port >> 8) as u8, dst_port as u8,
+                            (length >> 8) as u8, length as u8, 0, 0];
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+/// Create an Ethernet frame with the given EtherType and payload
+fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, (ethertype >> 8) as u8, ethertype as u8];
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Create an IPv6 packet carrying a UDP datagram without checksum
+fn ipv6_udp(payload: &[u8]) -> Vec<u8> {
+    let datagram = udp(5000, 5001, payload);
+    let mut packet = vec![0x60, 0, 0, 0, (datagram.len() >> 8) as u8, datagram.len() as u8, 17, 64];
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    packet.extend_from_slice(&datagram);
+    packet
+}
+
+/// Create a GRE header with checksum and key followed by the payload
+fn gre(protocol: u16, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0xb0, 0, (protocol >> 8) as u8, protocol as u8, 0, 0, 0, 0, 0, 0, 0, 42, 0, 0, 0, 7];
+    packet.extend_from_slice(payload);
+    let checksum = internet_checksum(&packet);
+    packet[4] = (checksum >> 8) as u8;
+    packet[5] = checksum as u8;
+    packet
+}
+
+fn inner_ipv4() -> Vec<u8> {
+    ipv4(17, 2, &udp(1234, 5678, b"inner"))
+}
+
+#[test]
+fn tunnel_success_gre() {
+    let mut peel = packet_peel();
+    let input = ipv4(47, 1, &gre(0x0800, &inner_ipv4()));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"inner");
+    assert_eq!(result.depths, vec![0, 0, 1, 1]);
+
+    let gre = result.result[1].downcast_ref::<GrePacket>().unwrap();
+    assert_eq!(gre.checksum_valid, Some(true));
+    assert_eq!(gre.key, Some(42));
+    assert_eq!(gre.sequence, Some(7));
+    assert_eq!(gre.protocol, EtherType::Ipv4);
+    assert_eq!(result.result[2].downcast_ref::<Ipv4Packet>().unwrap().dst, Ipv4Addr::new(10, 0, 0, 2));
+    assert_eq!(result.result[3].downcast_ref::<UdpPacket>().unwrap().dst_port, 5678);
+}
+
+#[test]
+fn tunnel_success_vxlan() {
+    let mut peel = packet_peel();
+    let mut vxlan = vec![0x08, 0, 0, 0, 0x12, 0x34, 0x56, 0];
+    vxlan.extend_from_slice(&ethernet(0x0800, &inner_ipv4()));
+    let input = ethernet(0x0800, &ipv4(17, 1, &udp(49152, VXLAN_PORT, &vxlan)));
+    let result = peel.traverse(&input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.depths, vec![0, 0, 0, 0, 1, 1, 1]);
+    assert_eq!(result.result[3].downcast_ref::<VxlanPacket>().unwrap().vni, 0x123456);
+    assert!(result.result[4].downcast_ref::<EthernetPacket>().is_some());
+    assert_eq!(result.left_input, b"inner");
+
+    // Only the destination port identifies the tunnel
+    let input = ethernet(0x0800, &ipv4(17, 1, &udp(VXLAN_PORT, 49152, &vxlan)));
+    assert_eq!(peel.traverse(&input, vec![]).result.len(), 3);
+}
+
+#[test]
+fn tunnel_success_geneve() {
+    let mut peel = packet_peel();
+    let mut geneve = vec![0x02, 0x40, 0x86, 0xdd, 0, 0, 0x0a, 0];
+    geneve.extend_from_slice(&[0x01, 0x02, 0x80, 0x01, 1, 2, 3, 4]);
+    geneve.extend_from_slice(&ipv6_udp(b"inner"));
+    let input = ipv4(17, 1, &udp(49152, GENEVE_PORT, &geneve));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.depths, vec![0, 0, 0, 1, 1]);
+    assert_eq!(result.left_input, b"inner");
+
+    let geneve = result.result[2].downcast_ref::<GenevePacket>().unwrap();
+    assert!(!geneve.oam);
+    assert!(geneve.critical);
+    assert_eq!(geneve.protocol, EtherType::Ipv6);
+    assert_eq!(geneve.vni, 10);
+    assert_eq!(geneve.options,
+               vec![GeneveOption {
+                        class: 0x0102,
+                        kind: 0x80,
+                        data: vec![1, 2, 3, 4],
+                    }]);
+}
+
+#[test]
+fn tunnel_success_ip_in_ip() {
+    let mut peel = packet_peel();
+    let input = ipv4(41, 1, &ipv6_udp(b"inner"));
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.depths, vec![0, 0, 1, 1]);
+    assert_eq!(result.result[1].downcast_ref::<IpTunnel>(),
+               Some(&IpTunnel { ethertype: EtherType::Ipv6 }));
+    assert!(result.result[2].downcast_ref::<Ipv6Packet>().is_some());
+
+    let input = ipv4(4, 1, &inner_ipv4());
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.depths, vec![0, 0, 1, 1]);
+}
+
+#[test]
+fn tunnel_failure_max_depth() {
+    let mut peel = packet_peel();
+    peel.max_depth = 2;
+    let mut input = inner_ipv4();
+    for _ in 0..3 {
+        input = ipv4(47, 1, &gre(0x0800, &input));
+    }
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.depths, vec![0, 0, 1, 1, 2, 2]);
+    assert!(result.result[5].downcast_ref::<GrePacket>().is_some());
+}
+
+#[test]
+fn tunnel_failure_invalid() {
+    let mut peel = packet_peel();
+
+    // GRE with routing present
+    let mut packet = gre(0x0800, &inner_ipv4());
+    packet[0] |= 0x40;
+    let input = ipv4(47, 1, &packet);
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 1);
+
+    // VXLAN without valid network identifier
+    let mut vxlan = vec![0, 0, 0, 0, 0x12, 0x34, 0x56, 0];
+    vxlan.extend_from_slice(&ethernet(0x0800, &inner_ipv4()));
+    let input = ipv4(17, 1, &udp(49152, VXLAN_PORT, &vxlan));
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // Geneve options exceeding the packet
+    let input = ipv4(17, 1, &udp(49152, GENEVE_PORT, &[0x02, 0, 0x08, 0, 0, 0, 1, 0, 0, 0]));
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+
+    // Encapsulated Ethernet frames are only recognized if announced
+    let input = ipv4(47, 1, &gre(0x0800, &ethernet(0x0800, &inner_ipv4())));
+    assert_eq!(peel.traverse_root("ipv4", &input, vec![]).result.len(), 2);
+}
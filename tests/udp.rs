@@ -0,0 +1,54 @@
+extern crate peel;
+use peel::packet::prelude::*;
+
+mod common;
+use common::{ipv4_udp, CLIENT, SERVER};
+
+#[test]
+fn udp_success_checksum() {
+    let mut peel = packet_peel();
+    let input = ipv4_udp(CLIENT, SERVER, 1234, 53, b"query");
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert!(result.error.is_none());
+    assert_eq!(result.left_input, b"query");
+    let udp = result.result[1].downcast_ref::<UdpPacket>().unwrap();
+    assert_eq!(udp.src_port, 1234);
+    assert_eq!(udp.dst_port, 53);
+    assert_eq!(udp.length, 13);
+    assert_eq!(udp.checksum_valid, Some(true));
+}
+
+#[test]
+fn udp_success_checksum_mismatch() {
+    let mut peel = packet_peel();
+    let mut input = ipv4_udp(CLIENT, SERVER, 1234, 53, b"query");
+    input[30] ^= 0xff;
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result[1].downcast_ref::<UdpPacket>().unwrap().checksum_valid, Some(false));
+}
+
+#[test]
+fn udp_success_without_checksum() {
+    let mut peel = packet_peel();
+    let mut input = ipv4_udp(CLIENT, SERVER, 1234, 53, b"query");
+    input[26] = 0;
+    input[27] = 0;
+    let result = peel.traverse_root("ipv4", &input, vec![]);
+    assert_eq!(result.result[1].downcast_ref::<UdpPacket>().unwrap().checksum_valid, None);
+}
+
+#[test]
+fn udp_success_standalone() {
+    let mut peel = Peel::new();
+    peel.new_parser(UdpParser);
+    let result = peel.traverse(&[0, 53, 0x04, 0xd2, 0, 10, 0x12, 0x34, 1, 2, 3], vec![]);
+    assert_eq!(result.left_input, &[1, 2]);
+    assert_eq!(result.result[0].downcast_ref::<UdpPacket>().unwrap().checksum_valid, None);
+}
+
+#[test]
+fn udp_failure_length() {
+    let mut peel = Peel::new();
+    peel.new_parser(UdpParser);
+    assert!(peel.traverse(&[0, 53, 0x04, 0xd2, 0, 7, 0, 0], vec![]).error.is_some());
+}